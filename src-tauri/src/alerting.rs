@@ -0,0 +1,402 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tauri::{command, AppHandle, Emitter, State};
+
+use crate::command_runner::CommandSpec;
+use crate::database::ConfigStore;
+use crate::metrics_collector::SystemMetrics;
+use crate::models::{AlertEvent, AlertRule, AlertScope, AlertState, CoreError};
+use crate::workspace_manager::WorkspaceManager;
+
+// Name of the Tauri event carrying each AlertEvent
+pub const ALERT_EVENT: &str = "alert";
+
+// Environment rows older than this are too stale to evaluate against
+const ENVIRONMENT_STALENESS_MINUTES: i64 = 5;
+
+// Runs osascript's argv through `display notification`, so titles and messages are never parsed as script
+const MACOS_NOTIFICATION_SCRIPT: &str =
+    "on run argv\ndisplay notification (item 2 of argv) with title (item 1 of argv)\nend run";
+
+// Where firing and resolved alerts are delivered
+pub trait AlertSink: Send + Sync {
+    fn deliver(&self, event: &AlertEvent);
+}
+
+// Emits alerts to the webview and, when the UI config allows, as desktop notifications
+pub struct AppAlertSink {
+    app: AppHandle,
+    notifications: bool,
+}
+
+impl AppAlertSink {
+    pub fn new(app: AppHandle, notifications: bool) -> Self {
+        Self { app, notifications }
+    }
+}
+
+impl AlertSink for AppAlertSink {
+    fn deliver(&self, event: &AlertEvent) {
+        if let Err(e) = self.app.emit(ALERT_EVENT, event) {
+            tracing::warn!("Failed to emit alert event: {}", e);
+        }
+        if !self.notifications {
+            return;
+        }
+        let title = match event.state {
+            AlertState::Firing => format!("Alert: {}", event.rule_name),
+            AlertState::Resolved => format!("Resolved: {}", event.rule_name),
+        };
+        if let Some(spec) = notification_command(&title, &event.message) {
+            tauri::async_runtime::spawn(async move {
+                match spec.run_async().await {
+                    Ok(output) if !output.success => {
+                        tracing::warn!("Desktop notification failed: {}", output.stderr.trim());
+                    }
+                    Err(e) => tracing::warn!("Failed to send desktop notification: {}", e),
+                    _ => {}
+                }
+            });
+        }
+    }
+}
+
+// None where no notification tool is available
+pub fn notification_command(title: &str, body: &str) -> Option<CommandSpec> {
+    if cfg!(target_os = "macos") {
+        Some(CommandSpec::new("osascript", &["-e", MACOS_NOTIFICATION_SCRIPT, title, body]))
+    } else if cfg!(target_os = "linux") {
+        Some(CommandSpec::new("notify-send", &["--app-name", "NUFFI", title, body]))
+    } else {
+        None
+    }
+}
+
+// Checks every enabled rule against each sample. A rule fires once its condition has held for
+// `duration_seconds` and resolves as soon as it stops holding.
+pub struct AlertEvaluator {
+    store: ConfigStore,
+    workspaces: Option<Arc<Mutex<WorkspaceManager>>>,
+    sink: Option<Box<dyn AlertSink>>,
+    // When each rule's condition started holding
+    pending: HashMap<String, DateTime<Utc>>,
+    firing: HashSet<String>,
+    // Whether `firing` has been loaded from the alert history yet
+    restored: bool,
+}
+
+impl AlertEvaluator {
+    pub fn new(store: ConfigStore) -> Self {
+        Self {
+            store,
+            workspaces: None,
+            sink: None,
+            pending: HashMap::new(),
+            firing: HashSet::new(),
+            restored: false,
+        }
+    }
+
+    // Needed to evaluate workspace-scoped rules, which stop once their workspace is removed
+    pub fn with_workspaces(mut self, workspaces: Arc<Mutex<WorkspaceManager>>) -> Self {
+        self.workspaces = Some(workspaces);
+        self
+    }
+
+    pub fn with_sink(mut self, sink: impl AlertSink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    // Returns the events raised by this sample, after storing and delivering them
+    pub async fn evaluate(&mut self, metrics: &SystemMetrics) -> Result<Vec<AlertEvent>, CoreError> {
+        let rules: Vec<AlertRule> = self
+            .store
+            .list_alert_rules()
+            .await?
+            .into_iter()
+            .filter(|rule| rule.enabled)
+            .collect();
+
+        // Rules still firing when the app last stopped are not fired again, and still resolve
+        if !self.restored {
+            self.firing = self.store.firing_alert_rules().await?.into_iter().collect();
+            self.restored = true;
+        }
+
+        // Rules that were deleted or disabled stop without resolving
+        let active: HashSet<&str> = rules.iter().map(|rule| rule.id.as_str()).collect();
+        self.pending.retain(|id, _| active.contains(id.as_str()));
+        self.firing.retain(|id| active.contains(id.as_str()));
+
+        let now = metrics.timestamp;
+        let mut events = Vec::new();
+        for rule in &rules {
+            // Without a reading the rule keeps its state until the next sample
+            let Some(value) = self.value_for(rule, metrics).await? else {
+                continue;
+            };
+
+            if rule.comparator.holds(value, rule.threshold) {
+                let since = *self.pending.entry(rule.id.clone()).or_insert(now);
+                if !self.firing.contains(&rule.id) && now - since >= Duration::seconds(rule.duration_seconds as i64) {
+                    self.firing.insert(rule.id.clone());
+                    events.push(rule.event(AlertState::Firing, value, now));
+                }
+            } else {
+                self.pending.remove(&rule.id);
+                if self.firing.remove(&rule.id) {
+                    events.push(rule.event(AlertState::Resolved, value, now));
+                }
+            }
+        }
+
+        for event in &events {
+            self.store.store_alert_event(event).await?;
+            if let Some(sink) = &self.sink {
+                sink.deliver(event);
+            }
+        }
+        Ok(events)
+    }
+
+    async fn value_for(&self, rule: &AlertRule, metrics: &SystemMetrics) -> Result<Option<f64>, CoreError> {
+        match &rule.scope {
+            AlertScope::Global => Ok(global_value(metrics, &rule.metric)),
+            AlertScope::Workspace { id } => {
+                let exists = match &self.workspaces {
+                    Some(workspaces) => workspaces.lock().unwrap().get_workspace(id).is_some(),
+                    None => false,
+                };
+                Ok(if exists { workspace_value(metrics, id, &rule.metric) } else { None })
+            }
+            AlertScope::Environment { id } => {
+                let since = metrics.timestamp - Duration::minutes(ENVIRONMENT_STALENESS_MINUTES);
+                let rows = self
+                    .store
+                    .get_monitoring_data(Some(id), Some(&rule.metric), Some(since), Some(1))
+                    .await?;
+                Ok(rows.first().map(|(_, value, _, _)| *value))
+            }
+        }
+    }
+}
+
+// The collector's own metrics, plus a few derived ones that only make sense as thresholds
+fn global_value(metrics: &SystemMetrics, metric: &str) -> Option<f64> {
+    match metric {
+        "swap_usage" if metrics.swap_total > 0 => Some(metrics.swap_used as f64 / metrics.swap_total as f64 * 100.0),
+        "swap_usage" => Some(0.0),
+        // The fullest mount, so one nearly full disk is enough to alert
+        "disk_usage_percent" => metrics.disks.iter().map(|disk| disk.usage_percent as f64).reduce(f64::max),
+        "temperature" => metrics.temperatures.iter().map(|reading| reading.celsius as f64).reduce(f64::max),
+        _ => metrics.value(metric),
+    }
+}
+
+// A workspace with no running processes reads as idle
+fn workspace_value(metrics: &SystemMetrics, workspace_id: &str, metric: &str) -> Option<f64> {
+    let usage = metrics.workspaces.iter().find(|usage| usage.workspace == workspace_id);
+    match metric {
+        "cpu_usage" => Some(usage.map_or(0.0, |usage| usage.cpu_usage as f64)),
+        "memory_usage" => Some(usage.map_or(0.0, |usage| usage.memory_usage as f64)),
+        "memory" => Some(usage.map_or(0.0, |usage| usage.memory as f64)),
+        "processes" => Some(usage.map_or(0.0, |usage| usage.processes as f64)),
+        _ => None,
+    }
+}
+
+#[command]
+pub async fn get_alert_rules(store: State<'_, ConfigStore>) -> Result<Vec<AlertRule>, String> {
+    store.list_alert_rules().await.map_err(|e| e.to_string())
+}
+
+// Creates the rule or replaces the one with the same id
+#[command]
+pub async fn save_alert_rule(store: State<'_, ConfigStore>, mut rule: AlertRule) -> Result<AlertRule, String> {
+    rule.updated_at = Utc::now();
+    store.save_alert_rule(&rule).await.map_err(|e| e.to_string())?;
+    Ok(rule)
+}
+
+#[command]
+pub async fn delete_alert_rule(store: State<'_, ConfigStore>, id: String) -> Result<(), String> {
+    store.delete_alert_rule(&id).await.map_err(|e| e.to_string())
+}
+
+// Newest first, 100 by default
+#[command]
+pub async fn get_alert_history(store: State<'_, ConfigStore>, limit: Option<i64>) -> Result<Vec<AlertEvent>, String> {
+    store.list_alert_events(limit).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics_collector::{DiskMetrics, MetricsCollector, SystemMonitor, WorkspaceMetrics};
+    use crate::models::{AISettings, AlertComparator, EnvironmentConfig, MonitoringConfig, ProjectType};
+    use crate::workspace_manager::CreateWorkspaceRequest;
+    use tempfile::tempdir;
+
+    struct CollectingSink(Arc<Mutex<Vec<AlertEvent>>>);
+
+    impl AlertSink for CollectingSink {
+        fn deliver(&self, event: &AlertEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn sample(at: DateTime<Utc>, workspace: &str, workspace_memory: f32, disk: f32) -> SystemMetrics {
+        SystemMetrics {
+            disks: vec![
+                DiskMetrics { mount_point: "/".to_string(), usage_percent: 40.0, ..DiskMetrics::default() },
+                DiskMetrics { mount_point: "/data".to_string(), usage_percent: disk, ..DiskMetrics::default() },
+            ],
+            workspaces: vec![WorkspaceMetrics {
+                workspace: workspace.to_string(),
+                memory_usage: workspace_memory,
+                processes: 3,
+                ..WorkspaceMetrics::default()
+            }],
+            timestamp: at,
+            ..SystemMetrics::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rules_fire_after_their_duration_and_resolve() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+        let workspaces = Arc::new(Mutex::new(WorkspaceManager::new()));
+        let workspace = workspaces
+            .lock()
+            .unwrap()
+            .create_workspace(CreateWorkspaceRequest {
+                name: "api".to_string(),
+                workspace_type: "node".to_string(),
+                tools: vec![],
+                config: None,
+            })
+            .unwrap();
+
+        let memory = AlertRule::new(
+            "Workspace memory".to_string(),
+            "memory_usage".to_string(),
+            AlertComparator::GreaterThan,
+            80.0,
+            AlertScope::Workspace { id: workspace.id.clone() },
+        )
+        .with_duration(300);
+        let disk = AlertRule::new(
+            "Disk almost full".to_string(),
+            "disk_usage_percent".to_string(),
+            AlertComparator::GreaterOrEqual,
+            90.0,
+            AlertScope::Global,
+        );
+        store.save_alert_rule(&memory).await.unwrap();
+        store.save_alert_rule(&disk).await.unwrap();
+
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let mut evaluator = AlertEvaluator::new(store.clone())
+            .with_workspaces(Arc::clone(&workspaces))
+            .with_sink(CollectingSink(Arc::clone(&delivered)));
+
+        let start = Utc::now();
+        let at = |minutes: i64, memory: f32, disk: f32| {
+            sample(start + Duration::minutes(minutes), &workspace.id, memory, disk)
+        };
+        // The disk rule has no duration; the memory rule waits five minutes
+        let events = evaluator.evaluate(&at(0, 85.0, 95.0)).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].rule_id.as_str(), events[0].value), (disk.id.as_str(), 95.0));
+
+        let events = evaluator.evaluate(&at(4, 90.0, 95.0)).await.unwrap();
+        assert!(events.is_empty());
+        let events = evaluator.evaluate(&at(5, 90.0, 95.0)).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].rule_id.as_str(), events[0].state), (memory.id.as_str(), AlertState::Firing));
+
+        // Both clear; each resolves once
+        let events = evaluator.evaluate(&at(6, 20.0, 50.0)).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.state == AlertState::Resolved));
+        assert!(evaluator.evaluate(&at(7, 20.0, 50.0)).await.unwrap().is_empty());
+
+        // A dip below the threshold restarts the duration
+        evaluator.evaluate(&at(8, 90.0, 50.0)).await.unwrap();
+        evaluator.evaluate(&at(10, 50.0, 50.0)).await.unwrap();
+        let events = evaluator.evaluate(&at(14, 90.0, 50.0)).await.unwrap();
+        assert!(events.is_empty());
+
+        assert_eq!(delivered.lock().unwrap().len(), 4);
+        let history = store.list_alert_events(None).await.unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].state, AlertState::Resolved);
+
+        // Both fire again, then the app restarts: they are not fired twice and still resolve
+        assert_eq!(evaluator.evaluate(&at(20, 90.0, 95.0)).await.unwrap().len(), 2);
+        let mut restarted = AlertEvaluator::new(store.clone()).with_workspaces(workspaces);
+        assert!(restarted.evaluate(&at(21, 90.0, 95.0)).await.unwrap().is_empty());
+        let events = restarted.evaluate(&at(22, 20.0, 50.0)).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.state == AlertState::Resolved));
+    }
+
+    #[tokio::test]
+    async fn test_environment_rules_read_the_collected_usage() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+        // The test process itself runs in this directory
+        let environment = store
+            .create_environment(EnvironmentConfig {
+                name: "crate".to_string(),
+                path: std::env::current_dir().unwrap(),
+                project_type: ProjectType::Rust,
+                dependencies: vec![],
+                environment_variables: HashMap::new(),
+                scripts: HashMap::new(),
+                ai_settings: AISettings::default(),
+                description: None,
+            })
+            .await
+            .unwrap();
+        let rule = AlertRule::new(
+            "Crate busy".to_string(),
+            "processes".to_string(),
+            AlertComparator::GreaterOrEqual,
+            1.0,
+            AlertScope::Environment { id: environment.id.clone() },
+        );
+        store.save_alert_rule(&rule).await.unwrap();
+
+        let collector = MetricsCollector::new(store.clone(), MonitoringConfig::default(), SystemMonitor::new());
+        let metrics = SystemMetrics { timestamp: Utc::now(), ..SystemMetrics::default() };
+        assert_eq!(collector.record_environments(&metrics).await.unwrap(), 1);
+
+        let events = AlertEvaluator::new(store).evaluate(&metrics).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule_id, rule.id);
+    }
+
+    #[test]
+    fn test_derived_values() {
+        let metrics = SystemMetrics {
+            swap_total: 400,
+            swap_used: 100,
+            ..sample(Utc::now(), "api", 0.0, 70.0)
+        };
+        assert_eq!(global_value(&metrics, "swap_usage"), Some(25.0));
+        assert_eq!(global_value(&metrics, "disk_usage_percent"), Some(70.0));
+        assert_eq!(global_value(&metrics, "temperature"), None);
+        assert_eq!(workspace_value(&metrics, "api", "processes"), Some(3.0));
+        assert_eq!(workspace_value(&metrics, "web", "processes"), Some(0.0));
+        assert_eq!(workspace_value(&metrics, "api", "disk_usage"), None);
+
+        let spec = notification_command("Alert: \"quoted\"", "it's 91%");
+        if cfg!(target_os = "linux") {
+            assert_eq!(spec.unwrap().program(), "notify-send");
+        }
+    }
+}
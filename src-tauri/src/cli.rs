@@ -1,4 +1,18 @@
 use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use nuffi::database::{ConfigStore, DATABASE_FILE_NAME, HISTORY_DATABASE_FILE_NAME};
+use nuffi::diagnostics::{bundle_path, export_diagnostics, DiagnosticSources};
+use nuffi::metrics_collector::{metrics_history, SystemMetrics, SystemMonitor};
+use nuffi::models::{
+    default_app_config_dir, default_app_data_dir, AppConfig, ConfigError, EnvironmentConfig, EnvironmentStatus,
+    ProjectType, WorkflowStatus, WorkflowStepStatus, APP_CONFIG_FILE_NAME,
+};
+use nuffi::workflow_runner::run_workflow;
+
+// Recent workflow runs shown by `monitor status`
+const RECENT_EXECUTIONS: i64 = 5;
 
 #[derive(Parser)]
 #[command(name = "aidev")]
@@ -34,6 +48,12 @@ pub enum Commands {
         #[command(subcommand)]
         action: MonitorAction,
     },
+    /// Export a diagnostics archive to attach to bug reports
+    Diagnostics {
+        /// Archive to write, a timestamped file in the downloads folder by default
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -75,61 +95,320 @@ pub enum MonitorAction {
     /// Show current status
     Status,
     /// Show metrics
-    Metrics,
+    Metrics {
+        /// Recorded samples to show before the current reading
+        #[arg(long, default_value_t = 10)]
+        samples: usize,
+    },
 }
 
-fn main() {
-    let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Environment { action } => {
-            match action {
-                EnvironmentAction::List => {
-                    println!("Listing environments...");
-                    // TODO: Implement environment listing
-                }
-                EnvironmentAction::Create { name, path } => {
-                    println!("Creating environment '{}' at path '{}'", name, path);
-                    // TODO: Implement environment creation
-                }
-                EnvironmentAction::Activate { name } => {
-                    println!("Activating environment '{}'", name);
-                    // TODO: Implement environment activation
-                }
-                EnvironmentAction::Delete { name } => {
-                    println!("Deleting environment '{}'", name);
-                    // TODO: Implement environment deletion
-                }
+// The app's settings, or the defaults when it has none
+fn load_app_config() -> Result<AppConfig, ConfigError> {
+    let path = default_app_config_dir()
+        .unwrap_or_default()
+        .join(APP_CONFIG_FILE_NAME);
+    match AppConfig::load_from_file(&path) {
+        Err(ConfigError::FileNotFound(_)) => Ok(AppConfig::default()),
+        result => result,
+    }
+}
+
+// The desktop app's database, created here if the app has never run
+async fn open_store() -> Result<ConfigStore, String> {
+    let data_dir = default_app_data_dir().ok_or("No data directory on this platform")?;
+    std::fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+    ConfigStore::new(&ConfigStore::url_for(&data_dir)).await.map_err(|e| e.to_string())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_time(timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn environment_status(status: &EnvironmentStatus) -> String {
+    match status {
+        EnvironmentStatus::Error(e) => format!("error: {}", e),
+        status => format!("{:?}", status).to_lowercase(),
+    }
+}
+
+async fn run_environment(store: &ConfigStore, action: EnvironmentAction) -> Result<(), String> {
+    match action {
+        EnvironmentAction::List => {
+            let environments = store.list_environments().await.map_err(|e| e.to_string())?;
+            if environments.is_empty() {
+                println!("No environments");
             }
+            for env in &environments {
+                let marker = if env.is_active() { "*" } else { " " };
+                let project_type = format!("{:?}", env.project_type);
+                println!(
+                    "{} {:<24} {:<8} {:<10} {}",
+                    marker,
+                    env.name,
+                    project_type,
+                    environment_status(&env.status),
+                    env.path.display()
+                );
+            }
+        }
+        EnvironmentAction::Create { name, path } => {
+            let path = std::fs::canonicalize(&path).map_err(|e| format!("Invalid path {}: {}", path, e))?;
+            let project_type = ProjectType::detect(&path);
+            let env = store
+                .create_environment(EnvironmentConfig::new(name, path, project_type))
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Created {:?} environment '{}' at {}", env.project_type, env.name, env.path.display());
         }
-        Commands::Analyze { path, model } => {
-            let model_name = model.unwrap_or_else(|| "default".to_string());
-            println!("Analyzing path '{}' with model '{}'", path, model_name);
-            // TODO: Implement AI analysis
+        EnvironmentAction::Activate { name } => {
+            let env = store.get_environment_by_name(&name).await.map_err(|e| e.to_string())?;
+            store.activate_environment(&env.id).await.map_err(|e| e.to_string())?;
+            println!("Activated environment '{}'", name);
         }
-        Commands::Workflow { action } => {
-            match action {
-                WorkflowAction::List => {
-                    println!("Listing workflows...");
-                    // TODO: Implement workflow listing
+        EnvironmentAction::Delete { name } => {
+            let env = store.get_environment_by_name(&name).await.map_err(|e| e.to_string())?;
+            store.delete_environment(&env.id).await.map_err(|e| e.to_string())?;
+            println!("Deleted environment '{}'", name);
+        }
+    }
+    Ok(())
+}
+
+async fn run_workflow_action(store: &ConfigStore, action: WorkflowAction) -> Result<(), String> {
+    match action {
+        WorkflowAction::List => {
+            let workflows = store.list_workflows().await.map_err(|e| e.to_string())?;
+            let environments: HashMap<String, String> = store
+                .list_environments()
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|env| (env.id, env.name))
+                .collect();
+            if workflows.is_empty() {
+                println!("No workflows");
+            }
+            for workflow in &workflows {
+                let state = if workflow.is_enabled() { "enabled" } else { "disabled" };
+                let environment = workflow
+                    .environment_id
+                    .as_ref()
+                    .and_then(|id| environments.get(id))
+                    .map(String::as_str)
+                    .unwrap_or("-");
+                let last_run = workflow.last_executed.map(format_time).unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{:<24} {:<8} {:>3} steps  {:<16} last run {} ({} runs)",
+                    workflow.name,
+                    state,
+                    workflow.steps.len(),
+                    environment,
+                    last_run,
+                    workflow.execution_count
+                );
+            }
+        }
+        WorkflowAction::Run { name } => {
+            let workflow = store.get_workflow_by_name(&name).await.map_err(|e| e.to_string())?;
+            println!("Running workflow '{}'", workflow.name);
+            let execution = run_workflow(store, &workflow).await.map_err(|e| e.to_string())?;
+
+            for result in &execution.step_results {
+                let step = workflow.get_step(&result.step_id).map(|step| step.name.as_str()).unwrap_or("?");
+                let status = format!("{:?}", result.status).to_lowercase();
+                match &result.error_message {
+                    Some(message) if result.status != WorkflowStepStatus::Completed => {
+                        println!("  {:<9} {} ({})", status, step, message)
+                    }
+                    _ => println!("  {:<9} {}", status, step),
                 }
-                WorkflowAction::Run { name } => {
-                    println!("Running workflow '{}'", name);
-                    // TODO: Implement workflow execution
+                if let Some(output) = result.output.as_deref().filter(|output| !output.trim().is_empty()) {
+                    for line in output.lines() {
+                        println!("      {}", line);
+                    }
                 }
             }
+
+            let seconds = execution.duration().map(|d| d.num_milliseconds() as f64 / 1000.0).unwrap_or_default();
+            if execution.status == WorkflowStatus::Failed {
+                return Err(format!(
+                    "Workflow '{}' failed after {:.1}s: {}",
+                    workflow.name,
+                    seconds,
+                    execution.error_message.unwrap_or_default()
+                ));
+            }
+            println!("Workflow '{}' completed in {:.1}s", workflow.name, seconds);
+        }
+    }
+    Ok(())
+}
+
+fn print_sample(metrics: &SystemMetrics) {
+    println!(
+        "{}  cpu {:5.1}%  memory {:5.1}%  disk {:>10}  network {:>10}/s  {} processes",
+        format_time(metrics.timestamp),
+        metrics.cpu_usage,
+        metrics.memory_usage,
+        format_bytes(metrics.disk_usage),
+        format_bytes(metrics.network_io),
+        metrics.active_processes
+    );
+}
+
+fn print_current(metrics: &SystemMetrics) {
+    println!("CPU:       {:.1}%", metrics.cpu_usage);
+    println!("Memory:    {:.1}%", metrics.memory_usage);
+    if metrics.swap_total > 0 {
+        println!("Swap:      {} of {}", format_bytes(metrics.swap_used), format_bytes(metrics.swap_total));
+    }
+    if let Some(load) = &metrics.load_average {
+        println!("Load:      {:.2} {:.2} {:.2}", load.one, load.five, load.fifteen);
+    }
+    println!("Processes: {}", metrics.active_processes);
+    println!("Uptime:    {}h {}m", metrics.uptime_seconds / 3600, metrics.uptime_seconds % 3600 / 60);
+    for disk in &metrics.disks {
+        println!(
+            "Disk {}: {} of {} ({:.1}%)",
+            disk.mount_point,
+            format_bytes(disk.used_space),
+            format_bytes(disk.total_space),
+            disk.usage_percent
+        );
+    }
+    for workspace in &metrics.workspaces {
+        println!(
+            "Workspace {}: cpu {:.1}%  memory {}  {} processes",
+            workspace.workspace,
+            workspace.cpu_usage,
+            format_bytes(workspace.memory),
+            workspace.processes
+        );
+    }
+}
+
+async fn run_monitor(store: &ConfigStore, action: MonitorAction) -> Result<(), String> {
+    match action {
+        MonitorAction::Status => {
+            let environments = store.list_environments().await.map_err(|e| e.to_string())?;
+            let workflows = store.list_workflows().await.map_err(|e| e.to_string())?;
+            let active = environments.iter().find(|env| env.is_active());
+            match active {
+                Some(env) => println!("Active environment: {} ({})", env.name, env.path.display()),
+                None => println!("Active environment: none"),
+            }
+            println!("Environments: {}", environments.len());
+            println!("Workflows:    {}", workflows.len());
+
+            let executions = store
+                .list_recent_workflow_executions(RECENT_EXECUTIONS)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !executions.is_empty() {
+                println!("Recent workflow runs:");
+            }
+            for execution in &executions {
+                let name = workflows
+                    .iter()
+                    .find(|workflow| workflow.id == execution.workflow_id)
+                    .map(|workflow| workflow.name.as_str())
+                    .unwrap_or("(deleted)");
+                let status = format!("{:?}", execution.status).to_lowercase();
+                println!("  {}  {:<9} {}", format_time(execution.started_at), status, name);
+            }
+
+            let metrics = SystemMonitor::new().current().await;
+            println!(
+                "System: cpu {:.1}%  memory {:.1}%  {} processes",
+                metrics.cpu_usage, metrics.memory_usage, metrics.active_processes
+            );
         }
-        Commands::Monitor { action } => {
-            match action {
-                MonitorAction::Status => {
-                    println!("Showing status...");
-                    // TODO: Implement status display
+        MonitorAction::Metrics { samples } => {
+            if samples > 0 {
+                let history = metrics_history(store, samples).await.map_err(|e| e.to_string())?;
+                if history.is_empty() {
+                    println!("No recorded samples; the app records them while monitoring is enabled");
                 }
-                MonitorAction::Metrics => {
-                    println!("Showing metrics...");
-                    // TODO: Implement metrics display
+                for metrics in &history {
+                    print_sample(metrics);
                 }
+                println!();
             }
+            print_current(&SystemMonitor::new().current().await);
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+// Reads the same stores as the desktop app, without creating them when it has never run
+// A timestamped archive in the downloads folder, or the home directory without one
+fn default_bundle_path() -> PathBuf {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    bundle_path(&dir)
+}
+
+async fn run_diagnostics(output: Option<PathBuf>) -> Result<(), String> {
+    let data_dir = default_app_data_dir().ok_or("No data directory on this platform")?;
+    let store = if data_dir.join(DATABASE_FILE_NAME).exists() {
+        Some(ConfigStore::new(&ConfigStore::url_for(&data_dir)).await.map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let sources = DiagnosticSources {
+        config: load_app_config().map_err(|e| e.to_string())?,
+        store,
+        history_database: Some(data_dir.join(HISTORY_DATABASE_FILE_NAME)),
+    };
+    let path = output.unwrap_or_else(default_bundle_path);
+    let manifest = export_diagnostics(sources, &path).await?;
+
+    println!("Wrote diagnostics to {}", path.display());
+    for error in &manifest.errors {
+        println!("  skipped {}", error);
+    }
+    Ok(())
+}
+
+async fn run(command: Commands) -> Result<(), String> {
+    match command {
+        Commands::Environment { action } => run_environment(&open_store().await?, action).await,
+        Commands::Analyze { path, model } => Err(format!(
+            "Cannot analyze '{}' with model '{}': AI analysis is not supported by the CLI",
+            path,
+            model.as_deref().unwrap_or("default")
+        )),
+        Commands::Workflow { action } => run_workflow_action(&open_store().await?, action).await,
+        Commands::Monitor { action } => run_monitor(&open_store().await?, action).await,
+        Commands::Diagnostics { output } => run_diagnostics(output)
+            .await
+            .map_err(|e| format!("Failed to export diagnostics: {}", e)),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = run(cli.command).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
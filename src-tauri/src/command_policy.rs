@@ -0,0 +1,334 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{command, State};
+
+use crate::command_runner::{split_command_line, CommandSpec};
+use crate::database::Database;
+use crate::manifest::{ManifestRegistry, Platform};
+
+pub const POLICY_FILE_NAME: &str = "command_policy.json";
+
+// A binary the user allows, optionally limited to argument lists matching one of `args`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BinaryRule {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// An exact command line the user has approved, optionally only for one tool
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovedCommand {
+    pub command: String,
+    pub tool: Option<String>,
+}
+
+// The per-user policy file. Approvals are made by editing it, never from the webview.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct CommandPolicy {
+    #[serde(default)]
+    pub binaries: Vec<BinaryRule>,
+    #[serde(default)]
+    pub approved: Vec<ApprovedCommand>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicySource {
+    Manifest,
+    Approved,
+    BinaryRule,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyDenial {
+    pub id: String,
+    pub tool: String,
+    pub command: String,
+    pub reason: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl CommandPolicy {
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let policy: Self = serde_json::from_str(content).map_err(|e| format!("Invalid command policy: {}", e))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    // A missing file is an empty policy: only manifest commands are allowed
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::from_json(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read command policy {}: {}", path.display(), e)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for rule in &self.binaries {
+            if rule.program.trim().is_empty() {
+                return Err("Invalid command policy: a binary rule has no program".to_string());
+            }
+            for pattern in &rule.args {
+                anchored(pattern).map_err(|e| format!("Invalid argument pattern for {}: {}", rule.program, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Decides whether `command_line` may run for `tool`, and how
+    pub fn evaluate(
+        &self,
+        tool: &str,
+        command_line: &str,
+        registry: &ManifestRegistry,
+        platform: &Platform,
+    ) -> Result<(CommandSpec, PolicySource), String> {
+        let command_line = command_line.trim();
+        let argv = split_command_line(command_line).map_err(|e| e.to_string())?;
+
+        let trusted = if registry.trusts_command(tool, command_line, platform) {
+            Some(PolicySource::Manifest)
+        } else if self.approves(tool, command_line) {
+            Some(PolicySource::Approved)
+        } else {
+            None
+        };
+
+        match (argv, trusted) {
+            (Some(argv), _) if argv.is_empty() => Err("Empty command".to_string()),
+            (Some(argv), Some(source)) => Ok((CommandSpec::from_argv(argv), source)),
+            (None, Some(source)) => Ok((CommandSpec::shell(command_line), source)),
+            (Some(argv), None) if self.rule_allows(&argv) => Ok((CommandSpec::from_argv(argv), PolicySource::BinaryRule)),
+            (Some(argv), None) => Err(format!(
+                "`{}` is not part of a trusted manifest for {} and no policy rule allows it",
+                argv[0], tool
+            )),
+            (None, None) => Err("shell syntax is only allowed in trusted manifests or approved commands".to_string()),
+        }
+    }
+
+    fn approves(&self, tool: &str, command_line: &str) -> bool {
+        self.approved.iter().any(|approved| {
+            approved.command.trim() == command_line
+                && approved.tool.as_deref().map(|t| t.eq_ignore_ascii_case(tool)).unwrap_or(true)
+        })
+    }
+
+    // Rules apply to the program that actually runs, so `sudo` alone never matches a rule for another binary.
+    // A bare rule only covers the bare name, which is looked up on PATH; a path only covers that exact path.
+    fn rule_allows(&self, argv: &[String]) -> bool {
+        let (program, args) = match argv.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        let joined = args.join(" ");
+
+        self.binaries.iter().any(|rule| {
+            rule.program == *program
+                && (rule.args.is_empty()
+                    || rule.args.iter().any(|pattern| anchored(pattern).map(|re| re.is_match(&joined)).unwrap_or(false)))
+        })
+    }
+}
+
+// Argument patterns have to match the whole argument list
+fn anchored(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+pub struct PolicyEngine {
+    policy: Mutex<CommandPolicy>,
+    path: Option<PathBuf>,
+    denials: Mutex<Vec<PolicyDenial>>,
+    database: Option<Arc<Mutex<Database>>>,
+}
+
+impl PolicyEngine {
+    pub fn new(policy: CommandPolicy) -> Self {
+        Self {
+            policy: Mutex::new(policy),
+            path: None,
+            denials: Mutex::new(Vec::new()),
+            database: None,
+        }
+    }
+
+    // An unreadable policy file denies everything outside the manifests rather than failing open
+    pub fn load(path: PathBuf) -> Self {
+        let policy = CommandPolicy::load(&path).unwrap_or_else(|e| {
+            tracing::warn!("{}; falling back to manifest commands only", e);
+            CommandPolicy::default()
+        });
+        Self {
+            path: Some(path),
+            ..Self::new(policy)
+        }
+    }
+
+    pub fn with_database(mut self, database: Arc<Mutex<Database>>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    pub fn policy(&self) -> CommandPolicy {
+        self.policy.lock().unwrap().clone()
+    }
+
+    // Picks up edits to the policy file without restarting the app
+    pub fn reload(&self) -> Result<CommandPolicy, String> {
+        let path = self.path.as_ref().ok_or("Command policy was not loaded from a file")?;
+        let policy = CommandPolicy::load(path)?;
+        *self.policy.lock().unwrap() = policy.clone();
+        Ok(policy)
+    }
+
+    pub fn authorize(
+        &self,
+        tool: &str,
+        command_line: &str,
+        registry: &ManifestRegistry,
+        platform: &Platform,
+    ) -> Result<CommandSpec, String> {
+        let decision = self.policy.lock().unwrap().evaluate(tool, command_line, registry, platform);
+        match decision {
+            Ok((spec, _)) => Ok(spec),
+            Err(reason) => {
+                self.record_denial(tool, command_line, &reason);
+                Err(format!("Command rejected by policy: {}", reason))
+            }
+        }
+    }
+
+    pub fn denials(&self, limit: usize) -> Vec<PolicyDenial> {
+        if let Some(database) = &self.database {
+            match database.lock().unwrap().get_policy_denials(limit as i32) {
+                Ok(denials) => return denials,
+                Err(e) => tracing::warn!("Failed to read command policy denials: {}", e),
+            }
+        }
+        self.denials.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    fn record_denial(&self, tool: &str, command_line: &str, reason: &str) {
+        let denial = PolicyDenial {
+            id: uuid::Uuid::new_v4().to_string(),
+            tool: tool.to_string(),
+            command: command_line.to_string(),
+            reason: reason.to_string(),
+            created_at: chrono::Utc::now(),
+        };
+        tracing::warn!("Denied command for {}: {} ({})", denial.tool, denial.command, denial.reason);
+
+        if let Some(database) = &self.database {
+            if let Err(e) = database.lock().unwrap().record_policy_denial(&denial) {
+                tracing::warn!("Failed to persist command policy denial: {}", e);
+            }
+        }
+        self.denials.lock().unwrap().push(denial);
+    }
+}
+
+// Tauri commands
+#[command]
+pub async fn get_command_policy(policy: State<'_, PolicyEngine>) -> Result<CommandPolicy, String> {
+    Ok(policy.policy())
+}
+
+#[command]
+pub async fn reload_command_policy(policy: State<'_, PolicyEngine>) -> Result<CommandPolicy, String> {
+    policy.reload()
+}
+
+#[command]
+pub async fn get_policy_denials(policy: State<'_, PolicyEngine>, limit: Option<usize>) -> Result<Vec<PolicyDenial>, String> {
+    Ok(policy.denials(limit.unwrap_or(100)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ubuntu() -> Platform {
+        Platform { os: "linux".to_string(), distro: Some("ubuntu".to_string()), distro_like: Vec::new() }
+    }
+
+    fn policy() -> CommandPolicy {
+        CommandPolicy::from_json(
+            r#"{
+                "binaries": [
+                    { "program": "cargo", "args": ["install [a-z0-9_-]+( --locked)?"] },
+                    { "program": "git" },
+                    { "program": "/opt/tools/bin/deploy" }
+                ],
+                "approved": [
+                    { "command": "curl https://sh.rustup.rs -sSf | sh -s -- -y", "tool": "rust" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_sources() {
+        let registry = ManifestRegistry::bundled();
+        let policy = policy();
+
+        let (spec, source) = policy
+            .evaluate("nodejs", "curl -fsSL https://deb.nodesource.com/setup_20.x | sudo -E bash -", &registry, &ubuntu())
+            .unwrap();
+        assert_eq!(source, PolicySource::Manifest);
+        assert!(spec.shell);
+
+        let (spec, source) = policy.evaluate("rust", "curl https://sh.rustup.rs -sSf | sh -s -- -y", &registry, &ubuntu()).unwrap();
+        assert_eq!(source, PolicySource::Approved);
+        assert!(spec.shell);
+
+        let (spec, source) = policy.evaluate("ripgrep", "cargo install ripgrep --locked", &registry, &ubuntu()).unwrap();
+        assert_eq!(source, PolicySource::BinaryRule);
+        assert_eq!(spec.argv, vec!["cargo", "install", "ripgrep", "--locked"]);
+        assert_eq!(policy.evaluate("any", "git --version", &registry, &ubuntu()).unwrap().1, PolicySource::BinaryRule);
+        assert_eq!(policy.evaluate("any", "/opt/tools/bin/deploy", &registry, &ubuntu()).unwrap().1, PolicySource::BinaryRule);
+    }
+
+    #[test]
+    fn test_evaluate_denials() {
+        let registry = ManifestRegistry::bundled();
+        let policy = policy();
+
+        // Patterns match the whole argument list
+        assert!(policy.evaluate("ripgrep", "cargo install ripgrep --git https://evil.test", &registry, &ubuntu()).is_err());
+        // Approvals are tied to their tool, manifest lines to their manifest
+        assert!(policy.evaluate("python", "curl https://sh.rustup.rs -sSf | sh -s -- -y", &registry, &ubuntu()).is_err());
+        assert!(policy.evaluate("vscode", "sudo apt-get install -y nodejs", &registry, &ubuntu()).is_err());
+        assert!(policy.evaluate("any", "rm -rf ~", &registry, &ubuntu()).is_err());
+        assert!(policy.evaluate("any", "sudo git --version", &registry, &ubuntu()).is_err());
+        // A rule for `git` doesn't cover some other file named git
+        assert!(policy.evaluate("any", "/tmp/x/git --version", &registry, &ubuntu()).is_err());
+        assert!(policy.evaluate("any", "/usr/bin/git --version", &registry, &ubuntu()).is_err());
+        assert!(policy.evaluate("any", "deploy", &registry, &ubuntu()).is_err());
+    }
+
+    #[test]
+    fn test_engine_logs_denials() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(POLICY_FILE_NAME);
+        let engine = PolicyEngine::load(path.clone());
+        let registry = ManifestRegistry::bundled();
+
+        let error = engine.authorize("any", "cargo install ripgrep", &registry, &ubuntu()).unwrap_err();
+        assert!(error.starts_with("Command rejected by policy"));
+        assert_eq!(engine.denials(10)[0].command, "cargo install ripgrep");
+
+        std::fs::write(&path, serde_json::to_string(&policy()).unwrap()).unwrap();
+        engine.reload().unwrap();
+        assert!(engine.authorize("any", "cargo install ripgrep", &registry, &ubuntu()).is_ok());
+        assert_eq!(engine.denials(10).len(), 1);
+
+        std::fs::write(&path, r#"{ "binaries": [{ "program": "cargo", "args": ["("] }] }"#).unwrap();
+        assert!(engine.reload().is_err());
+    }
+}
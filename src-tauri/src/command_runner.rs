@@ -0,0 +1,456 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use thiserror::Error;
+
+use crate::manifest::{ManifestRegistry, Platform};
+
+// Bytes kept from each of stdout and stderr; anything beyond is drained and dropped
+pub const DEFAULT_OUTPUT_LIMIT: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("Empty command")]
+    Empty,
+    #[error("Unterminated quote in `{0}`")]
+    UnterminatedQuote(String),
+    #[error("`{0}` uses shell syntax; only commands from allowlisted manifests may run through a shell")]
+    ShellNotAllowed(String),
+    #[error("Failed to run {program}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Command task failed: {0}")]
+    Task(String),
+}
+
+// A command to run, either as structured argv or as a shell line
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandSpec {
+    pub argv: Vec<String>,
+    pub shell: bool,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub working_dir: Option<PathBuf>,
+    pub output_limit: usize,
+    // Written to the command's stdin, which is otherwise closed
+    #[serde(default, skip)]
+    pub stdin: Option<Vec<u8>>,
+    // Starts the command in a process group of its own (Unix)
+    #[serde(default)]
+    pub process_group: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+    pub duration_ms: u64,
+}
+
+impl CommandOutput {
+    pub fn stdout_lines(&self) -> Vec<String> {
+        self.stdout
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+impl CommandSpec {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self::from_argv(std::iter::once(program).chain(args.iter().copied()).map(|a| a.to_string()).collect())
+    }
+
+    pub fn from_argv(argv: Vec<String>) -> Self {
+        Self {
+            argv,
+            shell: false,
+            env: HashMap::new(),
+            working_dir: None,
+            output_limit: DEFAULT_OUTPUT_LIMIT,
+            stdin: None,
+            process_group: false,
+        }
+    }
+
+    // Runs through the platform shell. Callers are responsible for the line being trusted.
+    pub fn shell(command_line: &str) -> Self {
+        Self {
+            shell: true,
+            ..Self::from_argv(vec![command_line.to_string()])
+        }
+    }
+
+    // Splits a command line into argv, refusing anything that needs a shell to mean what it says
+    pub fn parse(command_line: &str) -> Result<Self, CommandError> {
+        match split_command_line(command_line)? {
+            Some(argv) if argv.is_empty() => Err(CommandError::Empty),
+            Some(argv) => Ok(Self::from_argv(argv)),
+            None => Err(CommandError::ShellNotAllowed(command_line.to_string())),
+        }
+    }
+
+    // Like `parse`, but lines that appear in an allowlisted manifest for `tool` may use the shell
+    pub fn parse_for_tool(
+        command_line: &str,
+        tool: &str,
+        registry: &ManifestRegistry,
+        platform: &Platform,
+    ) -> Result<Self, CommandError> {
+        match Self::parse(command_line) {
+            Err(CommandError::ShellNotAllowed(_)) if registry.trusts_command(tool, command_line, platform) => {
+                Ok(Self::shell(command_line))
+            }
+            result => result,
+        }
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    pub fn output_limit(mut self, bytes: usize) -> Self {
+        self.output_limit = bytes;
+        self
+    }
+
+    pub fn stdin(mut self, input: Vec<u8>) -> Self {
+        self.stdin = Some(input);
+        self
+    }
+
+    // When `run_async` is dropped before the command exits, everything it started is killed rather
+    // than only the direct child
+    pub fn process_group(mut self) -> Self {
+        self.process_group = true;
+        self
+    }
+
+    pub fn program(&self) -> &str {
+        if self.shell {
+            shell_program().0
+        } else {
+            self.argv.first().map(|p| p.as_str()).unwrap_or_default()
+        }
+    }
+
+    pub fn display(&self) -> String {
+        self.argv.join(" ")
+    }
+
+    fn to_command(&self) -> Result<Command, CommandError> {
+        let mut command = if self.shell {
+            let (program, flag) = shell_program();
+            let mut command = Command::new(program);
+            command.arg(flag).arg(self.argv.first().ok_or(CommandError::Empty)?);
+            command
+        } else {
+            let (program, args) = self.argv.split_first().ok_or(CommandError::Empty)?;
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        };
+
+        command.envs(&self.env);
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+        #[cfg(unix)]
+        if self.process_group {
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        }
+        let stdin = if self.stdin.is_some() { Stdio::piped() } else { Stdio::null() };
+        command.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped());
+        Ok(command)
+    }
+
+    pub fn run(&self) -> Result<CommandOutput, CommandError> {
+        let started = Instant::now();
+        let mut child = self.to_command()?.spawn().map_err(|source| CommandError::Spawn {
+            program: self.program().to_string(),
+            source,
+        })?;
+
+        // Input is written from its own thread, and both pipes are drained concurrently, so a chatty
+        // child can't block on a full buffer
+        if let (Some(input), Some(mut pipe)) = (self.stdin.clone(), child.stdin.take()) {
+            std::thread::spawn(move || {
+                let _ = std::io::Write::write_all(&mut pipe, &input);
+            });
+        }
+        let limit = self.output_limit;
+        let stdout = child.stdout.take().map(|pipe| std::thread::spawn(move || read_limited(pipe, limit)));
+        let stderr = child.stderr.take().map(|pipe| std::thread::spawn(move || read_limited(pipe, limit)));
+
+        let status = child.wait().map_err(|source| CommandError::Spawn {
+            program: self.program().to_string(),
+            source,
+        })?;
+        let (stdout, stdout_truncated) = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+        let (stderr, stderr_truncated) = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+
+        Ok(CommandOutput {
+            success: status.success(),
+            exit_code: status.code(),
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    // Same as `run`, but the child is killed if the future is dropped, e.g. when a timeout expires
+    pub async fn run_async(&self) -> Result<CommandOutput, CommandError> {
+        use tokio::io::AsyncWriteExt;
+
+        let started = Instant::now();
+        let spawn_error = |source| CommandError::Spawn {
+            program: self.program().to_string(),
+            source,
+        };
+        let mut command = tokio::process::Command::from(self.to_command()?);
+        let mut child = command.kill_on_drop(true).spawn().map_err(spawn_error)?;
+        let mut group = ProcessGroupGuard(child.id().filter(|_| self.process_group));
+
+        if let (Some(input), Some(mut pipe)) = (self.stdin.clone(), child.stdin.take()) {
+            tokio::spawn(async move {
+                let _ = pipe.write_all(&input).await;
+            });
+        }
+        let limit = self.output_limit;
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        let (status, (stdout, stdout_truncated), (stderr, stderr_truncated)) = tokio::join!(
+            child.wait(),
+            read_limited_async(stdout, limit),
+            read_limited_async(stderr, limit)
+        );
+        // Whatever the command left running in the background once it exited is not ours to kill
+        group.0 = None;
+        let status = status.map_err(spawn_error)?;
+
+        Ok(CommandOutput {
+            success: status.success(),
+            exit_code: status.code(),
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+// Kills the process group led by the given pid when dropped
+struct ProcessGroupGuard(Option<u32>);
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.0 {
+            // SAFETY: kill(2) has no memory-safety preconditions
+            unsafe {
+                libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+fn shell_program() -> (&'static str, &'static str) {
+    if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") }
+}
+
+fn read_limited(mut pipe: impl Read, limit: usize) -> (String, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        match pipe.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                let room = limit.saturating_sub(kept.len());
+                kept.extend_from_slice(&buffer[..read.min(room)]);
+                truncated |= read > room;
+            }
+        }
+    }
+
+    (String::from_utf8_lossy(&kept).into_owned(), truncated)
+}
+
+async fn read_limited_async(pipe: Option<impl tokio::io::AsyncRead + Unpin>, limit: usize) -> (String, bool) {
+    use tokio::io::AsyncReadExt;
+
+    let mut pipe = match pipe {
+        Some(pipe) => pipe,
+        None => return Default::default(),
+    };
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        match pipe.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                let room = limit.saturating_sub(kept.len());
+                kept.extend_from_slice(&buffer[..read.min(room)]);
+                truncated |= read > room;
+            }
+        }
+    }
+
+    (String::from_utf8_lossy(&kept).into_owned(), truncated)
+}
+
+// POSIX-style word splitting with quotes and backslash escapes.
+// Returns `None` when the line relies on pipes, redirects, substitutions or command lists.
+pub fn split_command_line(line: &str) -> Result<Option<Vec<String>>, CommandError> {
+    let mut argv = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(CommandError::UnterminatedQuote(line.to_string())),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('$') | Some('`') => return Ok(None),
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(CommandError::UnterminatedQuote(line.to_string())),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(CommandError::UnterminatedQuote(line.to_string())),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    current.push(c);
+                }
+            }
+            '|' | '&' | ';' | '<' | '>' | '$' | '`' | '(' | ')' => return Ok(None),
+            '~' if !in_word => return Ok(None),
+            c if c.is_whitespace() => {
+                if in_word {
+                    argv.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        argv.push(current);
+    }
+    Ok(Some(argv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line() {
+        let argv = split_command_line(r#"curl --create-dirs -o "/tmp/my file.deb" 'https://x.test/a b'"#).unwrap().unwrap();
+        assert_eq!(argv, vec!["curl", "--create-dirs", "-o", "/tmp/my file.deb", "https://x.test/a b"]);
+
+        let argv = split_command_line(r#"npm config set prefix '~/.npm-global'"#).unwrap().unwrap();
+        assert_eq!(argv[4], "~/.npm-global");
+        assert_eq!(split_command_line(r"echo a\ b").unwrap().unwrap(), vec!["echo", "a b"]);
+
+        assert!(split_command_line("curl -fsSL https://deb.nodesource.com/setup_20.x | sudo -E bash -").unwrap().is_none());
+        assert!(split_command_line("sudo usermod -aG docker $USER").unwrap().is_none());
+        assert!(split_command_line(r#"echo "$HOME""#).unwrap().is_none());
+        assert!(split_command_line("ls ~").unwrap().is_none());
+        assert!(split_command_line("echo 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_shell_mode_requires_allowlisted_manifest() {
+        let registry = ManifestRegistry::bundled();
+        let ubuntu = Platform { os: "linux".to_string(), distro: Some("ubuntu".to_string()), distro_like: Vec::new() };
+        let pipeline = "curl -fsSL https://deb.nodesource.com/setup_20.x | sudo -E bash -";
+
+        let spec = CommandSpec::parse_for_tool(pipeline, "nodejs", &registry, &ubuntu).unwrap();
+        assert!(spec.shell);
+
+        // The same line is refused for another tool, and arbitrary lines are refused outright
+        assert!(matches!(
+            CommandSpec::parse_for_tool(pipeline, "vscode", &registry, &ubuntu),
+            Err(CommandError::ShellNotAllowed(_))
+        ));
+        assert!(CommandSpec::parse_for_tool("rm -rf / ; echo", "nodejs", &registry, &ubuntu).is_err());
+
+        let spec = CommandSpec::parse_for_tool("sudo apt-get install -y nodejs", "nodejs", &registry, &ubuntu).unwrap();
+        assert!(!spec.shell);
+        assert_eq!(spec.argv.len(), 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_captures_output_with_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = CommandSpec::new("sh", &["-c", "echo \"$GREETING\"; pwd; echo oops >&2; exit 3"])
+            .env("GREETING", "hello world")
+            .current_dir(dir.path())
+            .run()
+            .unwrap();
+        assert!(!output.success);
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout_lines()[0], "hello world");
+        assert!(output.stdout_lines()[1].ends_with(&*dir.path().file_name().unwrap().to_string_lossy()));
+        assert_eq!(output.stderr.trim(), "oops");
+
+        let output = CommandSpec::shell("yes | head -c 100000").output_limit(1000).run().unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout.len(), 1000);
+        assert!(output.stdout_truncated);
+
+        let output = CommandSpec::new("tr", &["a-z", "A-Z"]).stdin(b"piped in".to_vec()).run().unwrap();
+        assert_eq!(output.stdout, "PIPED IN");
+
+        assert!(matches!(CommandSpec::new("definitely-not-a-program", &[]).run(), Err(CommandError::Spawn { .. })));
+    }
+}
@@ -1,5 +1,6 @@
 use sqlx::{SqlitePool, Row};
 use std::collections::HashMap;
+use std::path::Path;
 use chrono::{DateTime, Utc};
 use serde_json;
 
@@ -7,10 +8,14 @@ use crate::models::{
     Environment, EnvironmentConfig, EnvironmentStatus,
     Workflow, WorkflowExecution, WorkflowStatus,
     AnalysisRequest, AnalysisResult, AnalysisStatus, AIUsageStats,
+    MetricRollup, RollupResolution, AlertRule, AlertEvent, AlertState,
+    ApplicationLog, LogQuery,
     ConfigError, CoreError,
 };
 use crate::database::DatabaseMigrations;
 
+pub const DATABASE_FILE_NAME: &str = "app.db";
+
 #[derive(Clone)]
 pub struct ConfigStore {
     pool: SqlitePool,
@@ -22,6 +27,11 @@ impl ConfigStore {
         Ok(Self { pool })
     }
 
+    // The app and the CLI share this database in the app's data directory
+    pub fn url_for(data_dir: &Path) -> String {
+        format!("sqlite://{}", data_dir.join(DATABASE_FILE_NAME).display())
+    }
+
     pub async fn close(&self) {
         self.pool.close().await;
     }
@@ -159,6 +169,20 @@ impl ConfigStore {
         Ok(())
     }
 
+    // Marks the environment active and any other active one inactive, so only one is active at a time
+    pub async fn activate_environment(&self, id: &str) -> Result<Environment, CoreError> {
+        let mut env = self.get_environment(id).await?;
+        for mut other in self.list_environments().await? {
+            if other.id != env.id && other.is_active() {
+                other.deactivate();
+                self.update_environment(&other).await?;
+            }
+        }
+        env.activate();
+        self.update_environment(&env).await?;
+        Ok(env)
+    }
+
     pub async fn get_active_environment(&self) -> Result<Option<Environment>, CoreError> {
         let row = sqlx::query(
             "SELECT * FROM environments WHERE json_extract(status, '$') = 'Active' LIMIT 1"
@@ -218,6 +242,18 @@ impl ConfigStore {
         self.workflow_from_row(row).await
     }
 
+    pub async fn get_workflow_by_name(&self, name: &str) -> Result<Workflow, CoreError> {
+        let row = sqlx::query(
+            "SELECT * FROM workflows WHERE name = ?"
+        )
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| CoreError::Workflow(crate::models::WorkflowError::NotFound(name.to_string())))?;
+
+        self.workflow_from_row(row).await
+    }
+
     pub async fn list_workflows(&self) -> Result<Vec<Workflow>, CoreError> {
         let rows = sqlx::query(
             "SELECT * FROM workflows ORDER BY name"
@@ -356,6 +392,23 @@ impl ConfigStore {
         Ok(executions)
     }
 
+    // Newest first, across all workflows
+    pub async fn list_recent_workflow_executions(&self, limit: i64) -> Result<Vec<WorkflowExecution>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM workflow_executions ORDER BY started_at DESC LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut executions = Vec::new();
+        for row in rows {
+            executions.push(self.workflow_execution_from_row(row).await?);
+        }
+
+        Ok(executions)
+    }
+
     pub async fn update_workflow_execution(&self, execution: &WorkflowExecution) -> Result<(), CoreError> {
         let step_results_json = serde_json::to_string(&execution.step_results)?;
         let logs_json = serde_json::to_string(&execution.logs)?;
@@ -530,7 +583,19 @@ impl ConfigStore {
         metric_value: f64,
         metadata: Option<&HashMap<String, serde_json::Value>>,
     ) -> Result<(), CoreError> {
-        let metadata_json = metadata.map(|m| serde_json::to_string(m)).transpose()?;
+        self.store_monitoring_data_at(environment_id, metric_name, metric_value, metadata, Utc::now()).await
+    }
+
+    // Metrics sampled together share a timestamp, so they can be read back as one sample
+    pub async fn store_monitoring_data_at(
+        &self,
+        environment_id: Option<&str>,
+        metric_name: &str,
+        metric_value: f64,
+        metadata: Option<&HashMap<String, serde_json::Value>>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), CoreError> {
+        let metadata_json = metadata.map(serde_json::to_string).transpose()?;
 
         sqlx::query(
             r#"
@@ -542,13 +607,224 @@ impl ConfigStore {
         .bind(metric_name)
         .bind(metric_value)
         .bind(metadata_json)
-        .bind(Utc::now().to_rfc3339())
+        .bind(timestamp.to_rfc3339())
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
+    // Rows recorded for the whole system rather than for an environment, newest first
+    pub async fn get_system_monitoring_data(
+        &self,
+        limit: Option<i64>,
+    ) -> Result<Vec<(String, f64, DateTime<Utc>)>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT metric_name, metric_value, timestamp FROM monitoring_data WHERE environment_id IS NULL ORDER BY timestamp DESC LIMIT ?"
+        )
+        .bind(limit.unwrap_or(1000))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let timestamp_str: String = row.get("timestamp");
+            let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)?.with_timezone(&Utc);
+            results.push((row.get("metric_name"), row.get("metric_value"), timestamp));
+        }
+
+        Ok(results)
+    }
+
+    pub async fn delete_monitoring_data_before(&self, cutoff: DateTime<Utc>) -> Result<u64, CoreError> {
+        let result = sqlx::query(
+            "DELETE FROM monitoring_data WHERE timestamp < ?"
+        )
+        .bind(cutoff.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn earliest_monitoring_timestamp(&self) -> Result<Option<DateTime<Utc>>, CoreError> {
+        let timestamp: Option<String> = sqlx::query_scalar("SELECT MIN(timestamp) FROM monitoring_data")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(timestamp
+            .map(|ts| DateTime::parse_from_rfc3339(&ts).map(|dt| dt.with_timezone(&Utc)))
+            .transpose()?)
+    }
+
+    // Raw rows of every environment and metric in [start, end), for compaction
+    pub async fn get_monitoring_rows_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(Option<String>, String, f64, DateTime<Utc>)>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT environment_id, metric_name, metric_value, timestamp FROM monitoring_data WHERE timestamp >= ? AND timestamp < ?"
+        )
+        .bind(start.to_rfc3339())
+        .bind(end.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let timestamp_str: String = row.get("timestamp");
+            let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)?.with_timezone(&Utc);
+            results.push((row.get("environment_id"), row.get("metric_name"), row.get("metric_value"), timestamp));
+        }
+
+        Ok(results)
+    }
+
+    // Raw values of one series in [start, end), oldest first; no environment means system-wide rows
+    pub async fn get_monitoring_values(
+        &self,
+        environment_id: Option<&str>,
+        metric_name: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(f64, DateTime<Utc>)>, CoreError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT metric_value, timestamp FROM monitoring_data
+            WHERE environment_id IS ? AND metric_name = ? AND timestamp >= ? AND timestamp < ?
+            ORDER BY timestamp
+            "#,
+        )
+        .bind(environment_id)
+        .bind(metric_name)
+        .bind(start.to_rfc3339())
+        .bind(end.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let timestamp_str: String = row.get("timestamp");
+            let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)?.with_timezone(&Utc);
+            results.push((row.get("metric_value"), timestamp));
+        }
+
+        Ok(results)
+    }
+
+    // Replaces the rollups of `resolution` whose buckets start in [start, end), so compaction can be rerun
+    pub async fn replace_rollups(
+        &self,
+        resolution: RollupResolution,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        rollups: &[MetricRollup],
+    ) -> Result<(), CoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM monitoring_rollups WHERE resolution = ? AND bucket_start >= ? AND bucket_start < ?")
+            .bind(resolution.as_str())
+            .bind(start.to_rfc3339())
+            .bind(end.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+        for rollup in rollups {
+            sqlx::query(
+                r#"
+                INSERT INTO monitoring_rollups (
+                    environment_id, metric_name, resolution, bucket_start,
+                    sample_count, min_value, max_value, avg_value, p95_value
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&rollup.environment_id)
+            .bind(&rollup.metric_name)
+            .bind(rollup.resolution.as_str())
+            .bind(rollup.bucket_start.to_rfc3339())
+            .bind(rollup.count as i64)
+            .bind(rollup.min)
+            .bind(rollup.max)
+            .bind(rollup.avg)
+            .bind(rollup.p95)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Rollups of one series with buckets starting in [start, end), oldest first
+    pub async fn get_rollups(
+        &self,
+        environment_id: Option<&str>,
+        metric_name: &str,
+        resolution: RollupResolution,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<MetricRollup>, CoreError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM monitoring_rollups
+            WHERE environment_id IS ? AND metric_name = ? AND resolution = ? AND bucket_start >= ? AND bucket_start < ?
+            ORDER BY bucket_start
+            "#,
+        )
+        .bind(environment_id)
+        .bind(metric_name)
+        .bind(resolution.as_str())
+        .bind(start.to_rfc3339())
+        .bind(end.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut rollups = Vec::new();
+        for row in rows {
+            let bucket_start_str: String = row.get("bucket_start");
+            rollups.push(MetricRollup {
+                environment_id: row.get("environment_id"),
+                metric_name: row.get("metric_name"),
+                resolution,
+                bucket_start: DateTime::parse_from_rfc3339(&bucket_start_str)?.with_timezone(&Utc),
+                count: row.get::<i64, _>("sample_count") as u64,
+                min: row.get("min_value"),
+                max: row.get("max_value"),
+                avg: row.get("avg_value"),
+                p95: row.get("p95_value"),
+            });
+        }
+
+        Ok(rollups)
+    }
+
+    // Start of the newest bucket compacted at `resolution`
+    pub async fn latest_rollup_start(&self, resolution: RollupResolution) -> Result<Option<DateTime<Utc>>, CoreError> {
+        let bucket_start: Option<String> = sqlx::query_scalar(
+            "SELECT MAX(bucket_start) FROM monitoring_rollups WHERE resolution = ?"
+        )
+        .bind(resolution.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(bucket_start
+            .map(|ts| DateTime::parse_from_rfc3339(&ts).map(|dt| dt.with_timezone(&Utc)))
+            .transpose()?)
+    }
+
+    pub async fn delete_rollups_before(&self, resolution: RollupResolution, cutoff: DateTime<Utc>) -> Result<u64, CoreError> {
+        let result = sqlx::query(
+            "DELETE FROM monitoring_rollups WHERE resolution = ? AND bucket_start < ?"
+        )
+        .bind(resolution.as_str())
+        .bind(cutoff.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn get_monitoring_data(
         &self,
         environment_id: Option<&str>,
@@ -610,6 +886,237 @@ impl ConfigStore {
         Ok(results)
     }
 
+    // Alert operations
+    pub async fn save_alert_rule(&self, rule: &AlertRule) -> Result<(), CoreError> {
+        rule.validate()?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO alert_rules (
+                id, name, metric, comparator, threshold, duration_seconds, scope, enabled, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name, metric = excluded.metric, comparator = excluded.comparator,
+                threshold = excluded.threshold, duration_seconds = excluded.duration_seconds,
+                scope = excluded.scope, enabled = excluded.enabled, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&rule.id)
+        .bind(&rule.name)
+        .bind(&rule.metric)
+        .bind(serde_json::to_string(&rule.comparator)?)
+        .bind(rule.threshold)
+        .bind(rule.duration_seconds as i64)
+        .bind(serde_json::to_string(&rule.scope)?)
+        .bind(rule.enabled)
+        .bind(rule.created_at.to_rfc3339())
+        .bind(rule.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_alert_rules(&self) -> Result<Vec<AlertRule>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM alert_rules ORDER BY name"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut rules = Vec::new();
+        for row in rows {
+            let comparator_json: String = row.get("comparator");
+            let scope_json: String = row.get("scope");
+            let created_at_str: String = row.get("created_at");
+            let updated_at_str: String = row.get("updated_at");
+            rules.push(AlertRule {
+                id: row.get("id"),
+                name: row.get("name"),
+                metric: row.get("metric"),
+                comparator: serde_json::from_str(&comparator_json)?,
+                threshold: row.get("threshold"),
+                duration_seconds: row.get::<i64, _>("duration_seconds") as u64,
+                scope: serde_json::from_str(&scope_json)?,
+                enabled: row.get("enabled"),
+                created_at: DateTime::parse_from_rfc3339(&created_at_str)?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&updated_at_str)?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(rules)
+    }
+
+    pub async fn delete_alert_rule(&self, id: &str) -> Result<(), CoreError> {
+        let result = sqlx::query(
+            "DELETE FROM alert_rules WHERE id = ?"
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(CoreError::Config(ConfigError::ValidationFailed(format!("Alert rule not found: {}", id))));
+        }
+
+        Ok(())
+    }
+
+    pub async fn store_alert_event(&self, event: &AlertEvent) -> Result<(), CoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_history (
+                id, rule_id, rule_name, state, metric, scope, value, threshold, message, timestamp
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&event.id)
+        .bind(&event.rule_id)
+        .bind(&event.rule_name)
+        .bind(serde_json::to_string(&event.state)?)
+        .bind(&event.metric)
+        .bind(serde_json::to_string(&event.scope)?)
+        .bind(event.value)
+        .bind(event.threshold)
+        .bind(&event.message)
+        .bind(event.timestamp.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Rules whose latest event is a firing one
+    pub async fn firing_alert_rules(&self) -> Result<Vec<String>, CoreError> {
+        let rule_ids = sqlx::query_scalar(
+            r#"
+            SELECT rule_id FROM alert_history AS latest
+            WHERE state = ? AND timestamp = (
+                SELECT MAX(timestamp) FROM alert_history WHERE rule_id = latest.rule_id
+            )
+            "#,
+        )
+        .bind(serde_json::to_string(&AlertState::Firing)?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rule_ids)
+    }
+
+    // Newest first
+    pub async fn list_alert_events(&self, limit: Option<i64>) -> Result<Vec<AlertEvent>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM alert_history ORDER BY timestamp DESC LIMIT ?"
+        )
+        .bind(limit.unwrap_or(100))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let state_json: String = row.get("state");
+            let scope_json: String = row.get("scope");
+            let timestamp_str: String = row.get("timestamp");
+            events.push(AlertEvent {
+                id: row.get("id"),
+                rule_id: row.get("rule_id"),
+                rule_name: row.get("rule_name"),
+                state: serde_json::from_str(&state_json)?,
+                metric: row.get("metric"),
+                scope: serde_json::from_str(&scope_json)?,
+                value: row.get("value"),
+                threshold: row.get("threshold"),
+                message: row.get("message"),
+                timestamp: DateTime::parse_from_rfc3339(&timestamp_str)?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(events)
+    }
+
+    // Application log operations
+    pub async fn store_application_logs(&self, logs: &[ApplicationLog]) -> Result<(), CoreError> {
+        let mut tx = self.pool.begin().await?;
+        for log in logs {
+            sqlx::query(
+                "INSERT INTO application_logs (level, message, module, file, line, timestamp) VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&log.level)
+            .bind(&log.message)
+            .bind(&log.module)
+            .bind(&log.file)
+            .bind(log.line)
+            .bind(log.timestamp.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Newest first, 500 by default
+    pub async fn query_application_logs(&self, query: &LogQuery) -> Result<Vec<ApplicationLog>, CoreError> {
+        let levels = query.levels()?;
+        let mut sql = "SELECT * FROM application_logs WHERE 1=1".to_string();
+        if let Some(levels) = levels {
+            sql.push_str(&format!(" AND level IN ({})", vec!["?"; levels.len()].join(", ")));
+        }
+        if query.module.is_some() {
+            sql.push_str(" AND (module = ? OR substr(module, 1, ?) = ?)");
+        }
+        if query.since.is_some() {
+            sql.push_str(" AND timestamp >= ?");
+        }
+        if query.until.is_some() {
+            sql.push_str(" AND timestamp < ?");
+        }
+        sql.push_str(" ORDER BY timestamp DESC, id DESC LIMIT ?");
+
+        let mut sqlx_query = sqlx::query(&sql);
+        for level in levels.unwrap_or_default() {
+            sqlx_query = sqlx_query.bind(*level);
+        }
+        if let Some(module) = &query.module {
+            let prefix = format!("{}::", module);
+            sqlx_query = sqlx_query.bind(module.clone()).bind(prefix.len() as i64).bind(prefix);
+        }
+        if let Some(since) = query.since {
+            sqlx_query = sqlx_query.bind(since.to_rfc3339());
+        }
+        if let Some(until) = query.until {
+            sqlx_query = sqlx_query.bind(until.to_rfc3339());
+        }
+        let rows = sqlx_query.bind(query.limit.unwrap_or(500)).fetch_all(&self.pool).await?;
+
+        let mut logs = Vec::new();
+        for row in rows {
+            let timestamp_str: String = row.get("timestamp");
+            logs.push(ApplicationLog {
+                id: row.get("id"),
+                level: row.get("level"),
+                message: row.get("message"),
+                module: row.get("module"),
+                file: row.get("file"),
+                line: row.get("line"),
+                timestamp: DateTime::parse_from_rfc3339(&timestamp_str)?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(logs)
+    }
+
+    pub async fn delete_application_logs_before(&self, cutoff: DateTime<Utc>) -> Result<u64, CoreError> {
+        let result = sqlx::query(
+            "DELETE FROM application_logs WHERE timestamp < ?"
+        )
+        .bind(cutoff.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     // Helper methods for converting database rows to structs
     async fn environment_from_row(&self, row: sqlx::sqlite::SqliteRow) -> Result<Environment, CoreError> {
         let dependencies_json: String = row.get("dependencies");
@@ -726,6 +1233,8 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let db_path = temp_dir.path().join("test.db");
         let database_url = format!("sqlite://{}", db_path.display());
+        // The pool opens more connections to the file while the store is in use, so the directory is kept
+        std::mem::forget(temp_dir);
         ConfigStore::new(&database_url).await.unwrap()
     }
 
@@ -815,6 +1324,17 @@ mod tests {
         assert!(active_env.is_some());
         assert_eq!(active_env.unwrap().id, env.id);
 
+        // Activating another environment deactivates the current one
+        let other_dir = tempfile::tempdir().unwrap();
+        let other_config = EnvironmentConfig::new("other-env".to_string(), other_dir.path().to_path_buf(), ProjectType::Rust);
+        let other = store.create_environment(other_config).await.unwrap();
+        let activated = store.activate_environment(&other.id).await.unwrap();
+        assert!(activated.is_active() && activated.last_activated.is_some());
+        assert!(!store.get_environment(&env.id).await.unwrap().is_active());
+        assert_eq!(store.get_active_environment().await.unwrap().unwrap().id, other.id);
+        assert!(store.activate_environment("non-existent").await.is_err());
+        store.delete_environment(&other.id).await.unwrap();
+
         // Delete environment
         store.delete_environment(&env.id).await.unwrap();
         let result = store.get_environment(&env.id).await;
@@ -868,6 +1388,8 @@ mod tests {
         // Retrieve workflow
         let retrieved_workflow = store.get_workflow(&workflow.id).await.unwrap();
         assert_eq!(retrieved_workflow.name, workflow.name);
+        assert_eq!(store.get_workflow_by_name("Test Workflow").await.unwrap().id, workflow.id);
+        assert!(store.get_workflow_by_name("Missing").await.is_err());
         assert_eq!(retrieved_workflow.description, workflow.description);
         assert_eq!(retrieved_workflow.triggers.len(), 1);
         assert_eq!(retrieved_workflow.steps.len(), 1);
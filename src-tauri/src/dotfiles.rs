@@ -0,0 +1,1018 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{command, State};
+use walkdir::WalkDir;
+
+use crate::command_runner::CommandSpec;
+use crate::dotfile_template::{KeyringSecrets, TemplateContext, TEMPLATE_EXTENSION};
+use crate::installer::{InstallCommand, UniversalInstaller};
+use crate::scanner::SystemScanner;
+use crate::workspace_manager::WorkspaceManager;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const INSTALL_SCRIPT_NAME: &str = "install.sh";
+
+// Repository metadata that never belongs in $HOME
+const DEFAULT_EXCLUDES: &[&str] = &[".git", ".gitignore", ".gitmodules", ".github", ".DS_Store"];
+
+// Where dotfiles come from and which of them to link
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DotfilesConfig {
+    // A local directory, or a git URL that is cloned first
+    pub source: String,
+    #[serde(default)]
+    pub target: Option<String>,
+    // Glob patterns relative to the source; `*` stays within a directory, `**` crosses them.
+    // A pattern below a directory (`.config/nvim`) links that entry instead of the whole directory.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // Per-machine values for `*.tmpl` files, available to templates as `profile.<name>`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkAction {
+    Link,
+    AlreadyLinked,
+    // Something else is in the way; it is moved to the backup directory first
+    BackupAndLink,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedLink {
+    pub path: String,
+    pub source: String,
+    pub target: String,
+    pub action: LinkAction,
+    // For `*.tmpl` files: the template; `source` is then its rendered copy
+    #[serde(default)]
+    pub template: Option<String>,
+    // Never sent to the frontend, it may contain secrets
+    #[serde(skip)]
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotfilesPreview {
+    pub source: String,
+    pub target: String,
+    pub links: Vec<PlannedLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkRecord {
+    pub path: String,
+    pub source: String,
+    pub target: String,
+    pub backup: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    pub linked_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Every link we created, so uninstalling touches nothing else
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DotfilesManifest {
+    pub source: String,
+    pub target: String,
+    pub links: Vec<LinkRecord>,
+    // The rules the links were made with, reused when syncing
+    #[serde(default)]
+    pub config: Option<DotfilesConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DotfilesUninstallReport {
+    pub removed: Vec<String>,
+    pub restored: Vec<String>,
+    // Links the user replaced since they were installed; left alone
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkState {
+    Linked,
+    // The link was deleted
+    Missing,
+    // A file or another link now sits where our link was
+    Replaced,
+    // The rendered copy of a template was edited, or no longer matches the template
+    Modified,
+    // The repository no longer has the file
+    SourceMissing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkStatus {
+    pub path: String,
+    pub target: String,
+    pub state: LinkState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamStatus {
+    pub branch: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    // Uncommitted edits, including edits made through a link
+    pub local_changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotfilesStatus {
+    pub source: String,
+    pub upstream: Option<UpstreamStatus>,
+    pub links: Vec<LinkStatus>,
+    // Files the rules select that are not linked yet
+    pub new_files: Vec<String>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl DotfilesStatus {
+    pub fn in_sync(&self) -> bool {
+        self.new_files.is_empty()
+            && self.links.iter().all(|link| link.state == LinkState::Linked)
+            && self.upstream.as_ref().map(|u| u.behind == 0 && u.local_changes.is_empty()).unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SyncOptions {
+    #[serde(default)]
+    pub pull: bool,
+    #[serde(default)]
+    pub relink_replaced: bool,
+    #[serde(default)]
+    pub run_install_script: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DotfilesSyncReport {
+    pub pulled: bool,
+    pub linked: Vec<String>,
+    pub pruned: Vec<String>,
+    pub kept: Vec<String>,
+    pub install_script: Option<String>,
+    // The installation job running the install script, when one was requested
+    pub script_job_id: Option<String>,
+}
+
+struct Rules {
+    include: Vec<regex::Regex>,
+    include_patterns: Vec<String>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl Rules {
+    fn new(config: &DotfilesConfig) -> Result<Self, String> {
+        let compile = |patterns: &[String]| patterns.iter().map(|p| glob_regex(p)).collect::<Result<Vec<_>, _>>();
+        Ok(Self {
+            include: compile(&config.include)?,
+            include_patterns: config.include.clone(),
+            exclude: compile(&config.exclude)?,
+        })
+    }
+
+    fn excluded(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        DEFAULT_EXCLUDES.contains(&name) || self.exclude.iter().any(|rule| rule.is_match(path) || rule.is_match(name))
+    }
+
+    fn includes(&self, path: &str) -> bool {
+        !self.excluded(path) && (self.include.is_empty() || self.include.iter().any(|rule| rule.is_match(path)))
+    }
+
+    // Whether an include rule points inside this top-level directory
+    fn descends_into(&self, name: &str) -> bool {
+        let prefix = format!("{}/", name);
+        self.include_patterns.iter().any(|pattern| pattern.starts_with(&prefix))
+    }
+}
+
+fn glob_regex(pattern: &str) -> Result<regex::Regex, String> {
+    let mut expression = String::from("^");
+    let mut chars = pattern.trim_end_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                expression.push_str(".*");
+            }
+            '*' => expression.push_str("[^/]*"),
+            '?' => expression.push_str("[^/]"),
+            c => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expression.push('$');
+    regex::Regex::new(&expression).map_err(|e| format!("Invalid pattern {}: {}", pattern, e))
+}
+
+pub struct DotfilesManager {
+    state_dir: PathBuf,
+    context: TemplateContext,
+}
+
+impl DotfilesManager {
+    pub fn new(state_dir: PathBuf) -> Self {
+        let context = match SystemScanner::new().get_system_info() {
+            Ok(system) => TemplateContext::for_machine(&system),
+            Err(_) => TemplateContext::new(Arc::new(KeyringSecrets)),
+        };
+        Self { state_dir, context }
+    }
+
+    pub fn with_template_context(mut self, context: TemplateContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    // The workspace's environment variables, available to templates as `env.<name>`
+    pub fn with_environment(mut self, variables: &HashMap<String, String>) -> Self {
+        self.context = self.context.with_environment(variables);
+        self
+    }
+
+    fn plan(&self, source: &Path, target: &Path, config: &DotfilesConfig) -> Result<Vec<PlannedLink>, String> {
+        let context = self.context.clone().with_profile(&config.variables);
+        plan_links(source, target, &Rules::new(config)?, &self.state_dir.join("rendered"), &context)
+    }
+
+    pub fn default_state_dir() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("nuffi")
+            .join("dotfiles")
+    }
+
+    pub fn manifest(&self) -> Option<DotfilesManifest> {
+        let content = std::fs::read_to_string(self.state_dir.join(MANIFEST_FILE_NAME)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_manifest(&self, manifest: &DotfilesManifest) -> Result<(), String> {
+        std::fs::create_dir_all(&self.state_dir).map_err(|e| format!("Failed to create {}: {}", self.state_dir.display(), e))?;
+        let content = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+        std::fs::write(self.state_dir.join(MANIFEST_FILE_NAME), content).map_err(|e| format!("Failed to save dotfiles manifest: {}", e))
+    }
+
+    // Git sources are read from their clone under the state directory, which only `fetch_source` touches
+    fn resolve_source(&self, source: &str) -> Result<PathBuf, String> {
+        if !is_git_url(source) {
+            let path = crate::install_journal::expand_home(source);
+            return path
+                .canonicalize()
+                .map_err(|e| format!("Dotfiles source {} is not available: {}", source, e));
+        }
+
+        let checkout = self.checkout();
+        match git(&checkout, &["config", "--get", "remote.origin.url"]) {
+            Ok(url) if checkout.join(".git").exists() && url == source => Ok(checkout),
+            _ => Err(format!("Dotfiles from {} have not been fetched yet", source)),
+        }
+    }
+
+    // Clones a git source, or updates its clone. A clone of another URL is replaced rather than pulled.
+    pub fn fetch_source(&self, source: &str) -> Result<(), String> {
+        if !is_git_url(source) {
+            return Ok(());
+        }
+
+        let checkout = self.checkout();
+        if checkout.exists() && self.resolve_source(source).is_err() {
+            std::fs::remove_dir_all(&checkout).map_err(|e| format!("Failed to remove {}: {}", checkout.display(), e))?;
+        }
+        let spec = if checkout.exists() {
+            CommandSpec::new("git", &["pull", "--ff-only"]).current_dir(&checkout)
+        } else {
+            CommandSpec::new("git", &["clone", "--depth", "1", source, &checkout.display().to_string()])
+        };
+        let output = spec.run().map_err(|e| e.to_string())?;
+        if !output.success {
+            return Err(format!("Failed to fetch dotfiles from {}: {}", source, output.stderr.trim()));
+        }
+        Ok(())
+    }
+
+    fn checkout(&self) -> PathBuf {
+        self.state_dir.join("repo")
+    }
+
+    fn target_dir(config: &DotfilesConfig) -> Result<PathBuf, String> {
+        match &config.target {
+            Some(target) => Ok(crate::install_journal::expand_home(target)),
+            None => dirs::home_dir().ok_or_else(|| "Home directory not found".to_string()),
+        }
+    }
+
+    pub fn preview(&self, config: &DotfilesConfig) -> Result<DotfilesPreview, String> {
+        let source = self.resolve_source(&config.source)?;
+        let target = Self::target_dir(config)?;
+        Ok(DotfilesPreview {
+            links: self.plan(&source, &target, config)?,
+            source: source.display().to_string(),
+            target: target.display().to_string(),
+        })
+    }
+
+    // Links everything the preview lists. A failure undoes the links made so far and restores their backups.
+    pub fn install(&self, config: &DotfilesConfig) -> Result<DotfilesManifest, String> {
+        self.fetch_source(&config.source)?;
+        let preview = self.preview(config)?;
+        let created = self.apply_links(&preview.links)?;
+
+        // Reinstalling keeps the records, and backups, of links made earlier
+        let mut manifest = self.manifest().unwrap_or_default();
+        manifest.source = preview.source;
+        manifest.target = preview.target;
+        manifest.config = Some(config.clone());
+        record_links(&mut manifest, created);
+        self.save_manifest(&manifest)?;
+        Ok(manifest)
+    }
+
+    fn apply_links(&self, links: &[PlannedLink]) -> Result<Vec<LinkRecord>, String> {
+        let backup_dir = self
+            .state_dir
+            .join("backups")
+            // Unique per run, so a second sync within the same second can't overwrite older backups
+            .join(format!("{}-{}", chrono::Utc::now().format("%Y%m%d%H%M%S"), &uuid::Uuid::new_v4().to_string()[..8]));
+
+        // Rendered copies are rewritten even when their link is already in place
+        for link in links {
+            if let Some(content) = &link.rendered {
+                write_rendered(Path::new(&link.source), content)?;
+            }
+        }
+
+        let mut created: Vec<LinkRecord> = Vec::new();
+        for link in links.iter().filter(|link| link.action != LinkAction::AlreadyLinked) {
+            match link_one(link, &backup_dir) {
+                Ok(record) => created.push(record),
+                Err(error) => {
+                    for record in created.iter().rev() {
+                        let _ = unlink_one(record);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(created)
+    }
+
+    fn installed(&self) -> Result<(DotfilesManifest, DotfilesConfig), String> {
+        let manifest = self.manifest().ok_or_else(|| "Dotfiles are not installed".to_string())?;
+        // Manifests written before sync existed only know where the links go
+        let config = manifest.config.clone().unwrap_or_else(|| DotfilesConfig {
+            source: manifest.source.clone(),
+            target: Some(manifest.target.clone()),
+            ..DotfilesConfig::default()
+        });
+        Ok((manifest, config))
+    }
+
+    // Compares the links on disk with the manifest, and the clone with its upstream
+    pub fn status(&self, fetch: bool) -> Result<DotfilesStatus, String> {
+        let (manifest, config) = self.installed()?;
+        let source = PathBuf::from(&manifest.source);
+
+        let upstream = if source.join(".git").exists() {
+            if fetch {
+                git(&source, &["fetch", "--quiet"])?;
+            }
+            Some(upstream_status(&source)?)
+        } else {
+            None
+        };
+
+        let planned = self.plan(&source, Path::new(&manifest.target), &config)?;
+        let links: Vec<LinkStatus> = manifest
+            .links
+            .iter()
+            .map(|record| LinkStatus {
+                path: record.path.clone(),
+                target: record.target.clone(),
+                state: drift(record, &planned),
+            })
+            .collect();
+
+        let linked: Vec<&str> = manifest.links.iter().map(|record| record.path.as_str()).collect();
+        let new_files = planned
+            .into_iter()
+            .map(|link| link.path)
+            .filter(|path| !linked.contains(&path.as_str()))
+            .collect();
+
+        Ok(DotfilesStatus {
+            source: manifest.source,
+            upstream,
+            links,
+            new_files,
+            checked_at: chrono::Utc::now(),
+        })
+    }
+
+    // Pulls the clone, drops links whose file left the repo and links new or missing files.
+    // Links the user replaced, and edited template output, are only taken back with `relink_replaced`;
+    // a replacement is backed up, an edited rendered copy is rendered again.
+    pub fn sync(&self, options: &SyncOptions) -> Result<DotfilesSyncReport, String> {
+        let (mut manifest, config) = self.installed()?;
+        let source = PathBuf::from(&manifest.source);
+        let target = PathBuf::from(&manifest.target);
+        let mut report = DotfilesSyncReport::default();
+
+        if options.pull && source.join(".git").exists() {
+            let before = git(&source, &["rev-parse", "HEAD"])?;
+            git(&source, &["pull", "--ff-only"])?;
+            report.pulled = git(&source, &["rev-parse", "HEAD"])? != before;
+        }
+
+        let planned = self.plan(&source, &target, &config)?;
+        let mut kept = Vec::new();
+        for record in std::mem::take(&mut manifest.links) {
+            match drift(&record, &planned) {
+                LinkState::SourceMissing => {
+                    unlink_one(&record)?;
+                    report.pruned.push(record.target.clone());
+                    continue;
+                }
+                LinkState::Replaced | LinkState::Modified if !options.relink_replaced => kept.push(record.target.clone()),
+                _ => {}
+            }
+            manifest.links.push(record);
+        }
+
+        let links: Vec<PlannedLink> = planned
+            .into_iter()
+            .filter(|link| !kept.contains(&link.target))
+            .collect();
+        let created = self.apply_links(&links)?;
+        report.linked = created.iter().map(|record| record.target.clone()).collect();
+        report.kept = kept;
+        record_links(&mut manifest, created);
+        self.save_manifest(&manifest)?;
+
+        if options.run_install_script {
+            let script = source.join(INSTALL_SCRIPT_NAME);
+            if !script.is_file() {
+                return Err(format!("{} has no {}", manifest.source, INSTALL_SCRIPT_NAME));
+            }
+            report.install_script = Some(script.display().to_string());
+        }
+        Ok(report)
+    }
+
+    pub fn uninstall(&self) -> Result<DotfilesUninstallReport, String> {
+        let manifest = match self.manifest() {
+            Some(manifest) => manifest,
+            None => return Ok(DotfilesUninstallReport::default()),
+        };
+
+        let mut report = DotfilesUninstallReport::default();
+        let mut remaining = Vec::new();
+        for record in manifest.links.iter().rev() {
+            match unlink_one(record) {
+                Ok(UnlinkOutcome::Removed { restored }) => {
+                    report.removed.push(record.target.clone());
+                    if restored {
+                        report.restored.push(record.target.clone());
+                    }
+                }
+                Ok(UnlinkOutcome::Changed) => report.skipped.push(record.target.clone()),
+                Err(error) => {
+                    report.skipped.push(format!("{}: {}", record.target, error));
+                    remaining.push(record.clone());
+                }
+            }
+        }
+
+        if remaining.is_empty() {
+            let _ = std::fs::remove_file(self.state_dir.join(MANIFEST_FILE_NAME));
+        } else {
+            remaining.reverse();
+            self.save_manifest(&DotfilesManifest { links: remaining, ..manifest })?;
+        }
+        Ok(report)
+    }
+}
+
+fn record_links(manifest: &mut DotfilesManifest, created: Vec<LinkRecord>) {
+    for record in created {
+        manifest.links.retain(|existing| existing.target != record.target);
+        manifest.links.push(record);
+    }
+}
+
+// `*.tmpl` files are rendered into `rendered_dir` and linked from there, without the extension
+fn plan_links(
+    source: &Path,
+    target: &Path,
+    rules: &Rules,
+    rendered_dir: &Path,
+    context: &TemplateContext,
+) -> Result<Vec<PlannedLink>, String> {
+    let mut links = Vec::new();
+    for path in candidates(source, rules)? {
+        let mut link_source = source.join(&path);
+        let (mut path, mut template, mut rendered) = (path, None, None);
+        if let Some(stripped) = path.strip_suffix(TEMPLATE_EXTENSION).filter(|_| link_source.is_file()).map(str::to_string) {
+            let content = std::fs::read_to_string(&link_source).map_err(|e| format!("Failed to read {}: {}", link_source.display(), e))?;
+            rendered = Some(context.render(&content).map_err(|e| format!("{}: {}", path, e))?);
+            template = Some(link_source.display().to_string());
+            link_source = rendered_dir.join(&stripped);
+            path = stripped;
+        }
+        let link_target = target.join(&path);
+        let action = match std::fs::read_link(&link_target) {
+            Ok(existing) if existing == link_source => LinkAction::AlreadyLinked,
+            Ok(_) => LinkAction::BackupAndLink,
+            Err(_) if link_target.exists() => LinkAction::BackupAndLink,
+            Err(_) => LinkAction::Link,
+        };
+        links.push(PlannedLink {
+            path,
+            source: link_source.display().to_string(),
+            target: link_target.display().to_string(),
+            action,
+            template,
+            rendered,
+        });
+    }
+    Ok(links)
+}
+
+fn link_state(record: &LinkRecord) -> LinkState {
+    let source = Path::new(&record.source);
+    // A rendered copy outlives its template, so the template decides
+    let source_missing = match &record.template {
+        Some(template) => !Path::new(template).is_file(),
+        None => source.symlink_metadata().is_err(),
+    };
+    match std::fs::read_link(&record.target) {
+        Ok(existing) if existing == source && source_missing => LinkState::SourceMissing,
+        Ok(existing) if existing == source => LinkState::Linked,
+        Err(_) if Path::new(&record.target).symlink_metadata().is_err() && source_missing => LinkState::SourceMissing,
+        Err(_) if Path::new(&record.target).symlink_metadata().is_err() => LinkState::Missing,
+        _ => LinkState::Replaced,
+    }
+}
+
+fn drift(record: &LinkRecord, planned: &[PlannedLink]) -> LinkState {
+    match link_state(record) {
+        LinkState::Linked if record.template.is_some() => {
+            let expected = planned.iter().find(|link| link.target == record.target).and_then(|link| link.rendered.as_deref());
+            match (expected, std::fs::read_to_string(&record.source)) {
+                (Some(expected), Ok(actual)) if expected == actual => LinkState::Linked,
+                _ => LinkState::Modified,
+            }
+        }
+        state => state,
+    }
+}
+
+// Rendered copies can hold secrets, so only the user may ever read them: the content goes into a
+// new 0600 file that then replaces the old copy
+fn write_rendered(path: &Path, content: &str) -> Result<(), String> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let partial = path.with_file_name(format!(".{}.{}", file_name, uuid::Uuid::new_v4()));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options
+        .open(&partial)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .and_then(|()| std::fs::rename(&partial, path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!("Failed to write {}: {}", path.display(), e));
+    }
+    Ok(())
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = CommandSpec::new("git", args).current_dir(repo).run().map_err(|e| e.to_string())?;
+    if !output.success {
+        return Err(format!("`git {}` failed: {}", args.join(" "), output.stderr.trim()));
+    }
+    Ok(output.stdout.trim().to_string())
+}
+
+fn upstream_status(repo: &Path) -> Result<UpstreamStatus, String> {
+    let branch = git(repo, &["rev-parse", "--abbrev-ref", "HEAD"]).ok();
+    // A clone without an upstream branch has nothing to be ahead of or behind
+    let (ahead, behind) = git(repo, &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
+        .ok()
+        .and_then(|counts| {
+            let mut counts = counts.split_whitespace().map(|c| c.parse::<u32>().ok());
+            Some((counts.next()??, counts.next()??))
+        })
+        .unwrap_or((0, 0));
+    let local_changes = git(repo, &["status", "--porcelain"])?
+        .lines()
+        .filter_map(|line| line.get(3..))
+        .map(|path| path.to_string())
+        .collect();
+
+    Ok(UpstreamStatus { branch, ahead, behind, local_changes })
+}
+
+fn is_git_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("git@") || source.starts_with("ssh://") || source.ends_with(".git")
+}
+
+// Top-level dot entries of the source, expanded one path at a time where include rules reach inside them
+fn candidates(source: &Path, rules: &Rules) -> Result<Vec<String>, String> {
+    let entries = std::fs::read_dir(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with('.'))
+        .collect();
+    names.sort();
+
+    let mut paths = Vec::new();
+    for name in names {
+        if rules.excluded(&name) {
+            continue;
+        }
+        if !rules.descends_into(&name) {
+            if rules.includes(&name) {
+                paths.push(name);
+            }
+            continue;
+        }
+
+        let mut walker = WalkDir::new(source.join(&name)).min_depth(1).sort_by_file_name().into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if rules.includes(&relative) {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                paths.push(relative);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+fn link_one(link: &PlannedLink, backup_dir: &Path) -> Result<LinkRecord, String> {
+    let (source, target) = (Path::new(&link.source), Path::new(&link.target));
+    let mut backup = None;
+
+    if link.action == LinkAction::BackupAndLink {
+        let destination = backup_dir.join(&link.path);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::rename(target, &destination).map_err(|e| format!("Failed to back up {}: {}", target.display(), e))?;
+        backup = Some(destination.display().to_string());
+    }
+
+    let record = LinkRecord {
+        path: link.path.clone(),
+        source: link.source.clone(),
+        target: link.target.clone(),
+        backup,
+        template: link.template.clone(),
+        linked_at: chrono::Utc::now(),
+    };
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    if let Err(e) = symlink(source, target) {
+        let _ = restore_backup(&record);
+        return Err(format!("Failed to link {}: {}", target.display(), e));
+    }
+    Ok(record)
+}
+
+enum UnlinkOutcome {
+    Removed { restored: bool },
+    Changed,
+}
+
+// Only removes the link if it still points where we left it
+fn unlink_one(record: &LinkRecord) -> Result<UnlinkOutcome, String> {
+    let target = Path::new(&record.target);
+    match std::fs::read_link(target) {
+        Ok(existing) if existing == Path::new(&record.source) => {
+            std::fs::remove_file(target).map_err(|e| format!("Failed to remove {}: {}", target.display(), e))?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        _ => return Ok(UnlinkOutcome::Changed),
+    }
+    if record.template.is_some() {
+        let _ = std::fs::remove_file(&record.source);
+    }
+    Ok(UnlinkOutcome::Removed { restored: restore_backup(record)? })
+}
+
+fn restore_backup(record: &LinkRecord) -> Result<bool, String> {
+    let backup = match &record.backup {
+        Some(backup) if Path::new(backup).symlink_metadata().is_ok() => backup,
+        _ => return Ok(false),
+    };
+    std::fs::rename(backup, &record.target).map_err(|e| format!("Failed to restore {}: {}", record.target, e))?;
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, target)
+    } else {
+        std::os::windows::fs::symlink_file(source, target)
+    }
+}
+
+// Tauri commands
+// Templates see the environment variables of the workspace the dotfiles are applied for
+fn manager_for(workspaces: &Arc<Mutex<WorkspaceManager>>, workspace_id: Option<&str>) -> DotfilesManager {
+    let variables = workspace_id
+        .and_then(|id| workspaces.lock().unwrap().get_workspace(id))
+        .map(|workspace| workspace.config.environment_variables)
+        .unwrap_or_default();
+    DotfilesManager::new(DotfilesManager::default_state_dir()).with_environment(&variables)
+}
+
+#[command]
+pub async fn preview_dotfiles(
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    config: DotfilesConfig,
+    fetch: Option<bool>,
+    workspace_id: Option<String>,
+) -> Result<DotfilesPreview, String> {
+    let manager = manager_for(&workspaces, workspace_id.as_deref());
+    // Previews only reach the network when asked to
+    tokio::task::spawn_blocking(move || {
+        if fetch.unwrap_or(false) {
+            manager.fetch_source(&config.source)?;
+        }
+        manager.preview(&config)
+    })
+        .await
+        .map_err(|e| format!("Dotfiles preview failed: {}", e))?
+}
+
+#[command]
+pub async fn install_dotfiles(
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    config: DotfilesConfig,
+    workspace_id: Option<String>,
+) -> Result<DotfilesManifest, String> {
+    let manager = manager_for(&workspaces, workspace_id.as_deref());
+    tokio::task::spawn_blocking(move || manager.install(&config))
+        .await
+        .map_err(|e| format!("Dotfiles install failed: {}", e))?
+}
+
+#[command]
+pub async fn uninstall_dotfiles() -> Result<DotfilesUninstallReport, String> {
+    DotfilesManager::new(DotfilesManager::default_state_dir()).uninstall()
+}
+
+#[command]
+pub async fn get_dotfiles_status(
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    fetch: Option<bool>,
+    workspace_id: Option<String>,
+) -> Result<DotfilesStatus, String> {
+    let manager = manager_for(&workspaces, workspace_id.as_deref());
+    tokio::task::spawn_blocking(move || manager.status(fetch.unwrap_or(false)))
+        .await
+        .map_err(|e| format!("Dotfiles status failed: {}", e))?
+}
+
+// The install script runs as an installation job, so its output is kept and can be followed like an install
+#[command]
+pub async fn sync_dotfiles(
+    installer: State<'_, UniversalInstaller>,
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    workspace_id: Option<String>,
+    options: Option<SyncOptions>,
+) -> Result<DotfilesSyncReport, String> {
+    let options = options.unwrap_or_default();
+    let manager = manager_for(&workspaces, workspace_id.as_deref());
+    let mut report = tokio::task::spawn_blocking(move || manager.sync(&options))
+        .await
+        .map_err(|e| format!("Dotfiles sync failed: {}", e))??;
+
+    if let Some(script) = &report.install_script {
+        let directory = Path::new(script).parent().unwrap_or(Path::new(".")).display().to_string();
+        let command = InstallCommand::new("sh", &["-c", r#"cd "$1" && exec bash ./install.sh"#, "sh", &directory]);
+        report.script_job_id = Some(installer.run_script(workspace_id.unwrap_or_else(|| "dotfiles".to_string()), "dotfiles", command));
+    }
+    Ok(report)
+}
+
+#[command]
+pub async fn get_dotfiles_manifest() -> Result<Option<DotfilesManifest>, String> {
+    Ok(DotfilesManager::new(DotfilesManager::default_state_dir()).manifest())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn setup() -> (tempfile::TempDir, DotfilesConfig, DotfilesManager) {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("dotfiles");
+        let home = dir.path().join("home");
+        std::fs::create_dir_all(source.join(".config/nvim")).unwrap();
+        std::fs::create_dir_all(source.join(".config/fish")).unwrap();
+        std::fs::create_dir_all(source.join(".git")).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(source.join(".zshrc"), "zsh").unwrap();
+        std::fs::write(source.join(".vimrc"), "vim").unwrap();
+        std::fs::write(source.join("README.md"), "readme").unwrap();
+        std::fs::write(source.join(".config/nvim/init.lua"), "nvim").unwrap();
+        std::fs::write(home.join(".zshrc"), "original zsh").unwrap();
+
+        let config = DotfilesConfig {
+            source: source.display().to_string(),
+            target: Some(home.display().to_string()),
+            include: Vec::new(),
+            exclude: vec![".vim*".to_string()],
+            variables: HashMap::new(),
+        };
+        let manager = DotfilesManager::new(dir.path().join("state"));
+        (dir, config, manager)
+    }
+
+    #[test]
+    fn test_preview_applies_rules() {
+        let (_dir, mut config, manager) = setup();
+        let preview = manager.preview(&config).unwrap();
+        let paths: Vec<&str> = preview.links.iter().map(|l| l.path.as_str()).collect();
+        assert_eq!(paths, vec![".config", ".zshrc"]);
+        assert_eq!(preview.links[1].action, LinkAction::BackupAndLink);
+
+        // Reaching into .config links only the matching entry
+        config.include = vec![".config/nvim".to_string(), ".zshrc".to_string()];
+        let preview = manager.preview(&config).unwrap();
+        let paths: Vec<&str> = preview.links.iter().map(|l| l.path.as_str()).collect();
+        assert_eq!(paths, vec![".config/nvim", ".zshrc"]);
+    }
+
+    #[test]
+    fn test_install_and_uninstall_restore_originals() {
+        let (dir, config, manager) = setup();
+        let home = dir.path().join("home");
+
+        let manifest = manager.install(&config).unwrap();
+        assert_eq!(manifest.links.len(), 2);
+        assert_eq!(std::fs::read_to_string(home.join(".zshrc")).unwrap(), "zsh");
+        assert!(manifest.links.iter().any(|l| l.backup.is_some()));
+
+        // Installing again changes nothing
+        let preview = manager.preview(&config).unwrap();
+        assert!(preview.links.iter().all(|l| l.action == LinkAction::AlreadyLinked));
+        assert_eq!(manager.install(&config).unwrap().links.len(), 2);
+
+        let report = manager.uninstall().unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.restored.len(), 1);
+        assert_eq!(std::fs::read_to_string(home.join(".zshrc")).unwrap(), "original zsh");
+        assert!(home.join(".config").symlink_metadata().is_err());
+        assert!(manager.manifest().is_none());
+    }
+
+    #[test]
+    fn test_status_and_sync_handle_drift() {
+        let (dir, mut config, manager) = setup();
+        let (source, home) = (dir.path().join("dotfiles"), dir.path().join("home"));
+        std::fs::remove_dir_all(source.join(".git")).unwrap();
+        config.exclude.push(".config".to_string());
+        std::fs::write(source.join(".gitconfig"), "git").unwrap();
+        manager.install(&config).unwrap();
+        assert!(manager.status(false).unwrap().in_sync());
+
+        // The user replaces one link, a file leaves the repo and another one arrives
+        std::fs::remove_file(home.join(".zshrc")).unwrap();
+        std::fs::write(home.join(".zshrc"), "edited locally").unwrap();
+        std::fs::remove_file(source.join(".gitconfig")).unwrap();
+        std::fs::write(source.join(".tmux.conf"), "tmux").unwrap();
+
+        let status = manager.status(false).unwrap();
+        assert!(!status.in_sync());
+        assert_eq!(status.new_files, vec![".tmux.conf".to_string()]);
+        let state = |path: &str| status.links.iter().find(|l| l.path == path).unwrap().state;
+        assert_eq!(state(".zshrc"), LinkState::Replaced);
+        assert_eq!(state(".gitconfig"), LinkState::SourceMissing);
+
+        let report = manager.sync(&SyncOptions::default()).unwrap();
+        assert_eq!(report.pruned.len(), 1);
+        assert_eq!(report.kept.len(), 1);
+        assert_eq!(report.linked, vec![home.join(".tmux.conf").display().to_string()]);
+        assert!(home.join(".gitconfig").symlink_metadata().is_err());
+        assert_eq!(std::fs::read_to_string(home.join(".zshrc")).unwrap(), "edited locally");
+
+        // Taking the replaced link back keeps the local edit as a backup
+        let report = manager.sync(&SyncOptions { relink_replaced: true, ..SyncOptions::default() }).unwrap();
+        assert_eq!(report.linked, vec![home.join(".zshrc").display().to_string()]);
+        assert!(manager.status(false).unwrap().in_sync());
+        assert!(manager.sync(&SyncOptions { run_install_script: true, ..SyncOptions::default() }).is_err());
+    }
+
+    struct Secrets;
+
+    impl crate::dotfile_template::SecretSource for Secrets {
+        fn secret(&self, name: &str) -> Result<String, String> {
+            Ok(format!("<{}>", name))
+        }
+    }
+
+    #[test]
+    fn test_git_sources_follow_the_configured_url() {
+        let (dir, mut config, manager) = setup();
+        for (name, content) in [("a.git", "zsh from a"), ("b.git", "zsh from b")] {
+            let repo = dir.path().join(name);
+            std::fs::create_dir_all(&repo).unwrap();
+            std::fs::write(repo.join(".zshrc"), content).unwrap();
+            git(&repo, &["init", "-q"]).unwrap();
+            git(&repo, &["add", "."]).unwrap();
+            git(&repo, &["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-qm", "init"]).unwrap();
+        }
+
+        // Previews read the existing clone and never clone or pull themselves
+        config.source = dir.path().join("a.git").display().to_string();
+        assert!(manager.preview(&config).unwrap_err().contains("not been fetched"));
+        manager.fetch_source(&config.source).unwrap();
+        let source = PathBuf::from(manager.preview(&config).unwrap().source);
+        assert_eq!(std::fs::read_to_string(source.join(".zshrc")).unwrap(), "zsh from a");
+
+        // A different URL gets a fresh clone instead of a pull into the old one
+        config.source = dir.path().join("b.git").display().to_string();
+        assert!(manager.preview(&config).is_err());
+        manager.fetch_source(&config.source).unwrap();
+        let source = PathBuf::from(manager.preview(&config).unwrap().source);
+        assert_eq!(std::fs::read_to_string(source.join(".zshrc")).unwrap(), "zsh from b");
+    }
+
+    #[test]
+    fn test_templates_are_rendered_and_linked() {
+        let (dir, mut config, manager) = setup();
+        let (source, home) = (dir.path().join("dotfiles"), dir.path().join("home"));
+        std::fs::remove_dir_all(source.join(".git")).unwrap();
+        std::fs::write(
+            source.join(".gitconfig.tmpl"),
+            "email = {{ profile.email }}\n{{#if os == \"linux\"}}proxy = {{ env.HTTP_PROXY }}\n{{/if}}token = {{ secret.gh }}\n",
+        )
+        .unwrap();
+        config.variables.insert("email".to_string(), "dev@example.com".to_string());
+
+        let context = TemplateContext::new(Arc::new(Secrets)).with_value("system.os", "linux");
+        let manager = manager.with_template_context(context);
+        assert!(manager.preview(&config).unwrap_err().contains("env.HTTP_PROXY"));
+
+        let manager = manager.with_environment(&HashMap::from([("HTTP_PROXY".to_string(), "http://proxy:3128".to_string())]));
+        let preview = manager.preview(&config).unwrap();
+        let link = preview.links.iter().find(|l| l.path == ".gitconfig").unwrap();
+        assert!(link.template.is_some());
+        assert!(!serde_json::to_string(&preview).unwrap().contains("<gh>"));
+
+        manager.install(&config).unwrap();
+        let rendered = std::fs::read_to_string(home.join(".gitconfig")).unwrap();
+        assert_eq!(rendered, "email = dev@example.com\nproxy = http://proxy:3128\ntoken = <gh>\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(std::fs::read_link(home.join(".gitconfig")).unwrap()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Editing the rendered copy is drift; syncing with relink renders it again
+        std::fs::write(home.join(".gitconfig"), "edited").unwrap();
+        let status = manager.status(false).unwrap();
+        assert_eq!(status.links.iter().find(|l| l.path == ".gitconfig").unwrap().state, LinkState::Modified);
+        manager.sync(&SyncOptions { relink_replaced: true, ..SyncOptions::default() }).unwrap();
+        assert_eq!(std::fs::read_to_string(home.join(".gitconfig")).unwrap(), rendered);
+
+        let rendered_copy = std::fs::read_link(home.join(".gitconfig")).unwrap();
+        manager.uninstall().unwrap();
+        assert!(!rendered_copy.exists());
+    }
+}
@@ -0,0 +1,320 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::integrity::{checksum_digest, hash_file, parse_checksum, verify_checksum, verify_signature, IntegrityError, SignatureSpec};
+
+const INDEX_FILE_NAME: &str = "index.json";
+
+// A downloaded artifact, stored once under its SHA-256 digest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedArtifact {
+    pub url: String,
+    pub digest: String,
+    pub size: u64,
+    pub cached_at: chrono::DateTime<chrono::Utc>,
+}
+
+// What a fetch checked before handing the artifact out
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FetchedArtifact {
+    pub artifact: CachedArtifact,
+    pub checksum_verified: bool,
+    pub signature_verified: bool,
+    pub warning: Option<String>,
+}
+
+// Installers are cached by content so identical files fetched from different URLs are stored once
+pub struct DownloadCache {
+    root: PathBuf,
+    // URL -> artifact, for signed downloads whose manifest has no checksum
+    index: Mutex<HashMap<String, CachedArtifact>>,
+}
+
+impl DownloadCache {
+    pub fn new(root: PathBuf) -> Self {
+        let index = std::fs::read_to_string(root.join(INDEX_FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Vec<CachedArtifact>>(&content).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|artifact| (artifact.url.clone(), artifact))
+            .collect();
+
+        Self {
+            root,
+            index: Mutex::new(index),
+        }
+    }
+
+    pub fn default_root() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("nuffi")
+            .join("downloads")
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn object_path(&self, digest: &str) -> PathBuf {
+        self.root.join("sha256").join(digest)
+    }
+
+    pub fn entries(&self) -> Vec<CachedArtifact> {
+        let mut entries: Vec<CachedArtifact> = self.index.lock().unwrap().values().cloned().collect();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries
+    }
+
+    // A cached copy is only returned after its content has been hashed again
+    pub fn lookup(&self, url: &str, checksum: Option<&str>) -> Option<CachedArtifact> {
+        let artifact = match checksum.and_then(parse_checksum) {
+            Some(digest) => {
+                let path = self.object_path(&digest);
+                let size = std::fs::metadata(&path).ok()?.len();
+                CachedArtifact { url: url.to_string(), digest, size, cached_at: chrono::Utc::now() }
+            }
+            None => self.index.lock().unwrap().get(url).cloned()?,
+        };
+
+        match hash_file(&self.object_path(&artifact.digest)) {
+            Ok((digest, _)) if digest == artifact.digest => Some(artifact),
+            _ => {
+                let _ = std::fs::remove_file(self.object_path(&artifact.digest));
+                None
+            }
+        }
+    }
+
+    // Moves a file into the cache and records it under `url`, refusing it if the checksum does not match
+    pub fn insert_file(&self, url: &str, file: &Path, checksum: Option<&str>) -> Result<CachedArtifact, IntegrityError> {
+        let (digest, size) = hash_file(file).map_err(|e| IntegrityError::Io(format!("Failed to read {}: {}", file.display(), e)))?;
+        verify_checksum(url, &digest, checksum)?;
+
+        let object = self.object_path(&digest);
+        if !object.exists() {
+            std::fs::create_dir_all(object.parent().unwrap_or(&self.root)).map_err(|e| IntegrityError::Io(e.to_string()))?;
+            if std::fs::rename(file, &object).is_err() {
+                std::fs::copy(file, &object).map_err(|e| IntegrityError::Io(format!("Failed to cache {}: {}", url, e)))?;
+            }
+        }
+
+        let artifact = CachedArtifact {
+            url: url.to_string(),
+            digest,
+            size,
+            cached_at: chrono::Utc::now(),
+        };
+        self.index.lock().unwrap().insert(url.to_string(), artifact.clone());
+        self.save_index()?;
+        Ok(artifact)
+    }
+
+    // Returns the cached artifact for `url`, downloading it first if needed. Downloads with neither
+    // a checksum nor a signature are refused: nothing pins what such a URL serves.
+    // Signatures are checked on every fetch, against a signature file that is cached alongside.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        checksum: Option<&str>,
+        signature: Option<&SignatureSpec>,
+    ) -> Result<FetchedArtifact, IntegrityError> {
+        checksum_digest(checksum)?;
+        if checksum.is_none() && signature.is_none() {
+            return Err(IntegrityError::Unverified { url: url.to_string() });
+        }
+        let artifact = self.fetch_object(url, checksum).await?;
+        let mut fetched = FetchedArtifact {
+            checksum_verified: checksum.is_some(),
+            signature_verified: false,
+            warning: None,
+            artifact,
+        };
+
+        let signature = match signature {
+            Some(signature) => signature,
+            None => return Ok(fetched),
+        };
+
+        let signature_file = self.fetch_object(&signature.url, None).await?;
+        match verify_signature(url, &self.object_path(&fetched.artifact.digest), &self.object_path(&signature_file.digest), signature) {
+            Ok(()) => fetched.signature_verified = true,
+            // Without the verifier the checksum alone has to do, if there is one
+            Err(IntegrityError::SignatureUnavailable { program, .. }) if fetched.checksum_verified => {
+                fetched.warning = Some(format!("Signature of {} not checked: {} is not installed", url, program));
+            }
+            Err(e) => {
+                if matches!(e, IntegrityError::SignatureMismatch { .. }) {
+                    let _ = self.remove(url);
+                }
+                return Err(e);
+            }
+        }
+        Ok(fetched)
+    }
+
+    async fn fetch_object(&self, url: &str, checksum: Option<&str>) -> Result<CachedArtifact, IntegrityError> {
+        if let Some(artifact) = self.lookup(url, checksum) {
+            return Ok(artifact);
+        }
+
+        let partial_dir = self.root.join("partial");
+        std::fs::create_dir_all(&partial_dir).map_err(|e| IntegrityError::Io(format!("Failed to create download cache: {}", e)))?;
+        let partial = partial_dir.join(uuid::Uuid::new_v4().to_string());
+
+        let result = match download_to(url, &partial).await {
+            Ok(()) => self.insert_file(url, &partial, checksum),
+            Err(e) => Err(e),
+        };
+        let _ = std::fs::remove_file(&partial);
+        result
+    }
+
+    // Caches whatever `url` serves now under its digest, so an offline bundle can pin it
+    pub async fn fetch_unverified(&self, url: &str) -> Result<CachedArtifact, IntegrityError> {
+        self.fetch_object(url, None).await
+    }
+
+    // Places a copy of the verified artifact where the install commands expect it. An unverified
+    // download is only made when `allow_unverified` is set, and only cached when a bundle pins it.
+    pub async fn materialize(
+        &self,
+        url: &str,
+        checksum: Option<&str>,
+        signature: Option<&SignatureSpec>,
+        allow_unverified: bool,
+        destination: &Path,
+    ) -> Result<FetchedArtifact, IntegrityError> {
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| IntegrityError::Io(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        if checksum.is_none() && signature.is_none() {
+            if !allow_unverified {
+                return Err(IntegrityError::Unverified { url: url.to_string() });
+            }
+            // A copy imported from an offline bundle is used as is, so offline machines can install it
+            if let Some(artifact) = self.lookup(url, None) {
+                std::fs::copy(self.object_path(&artifact.digest), destination)
+                    .map_err(|e| IntegrityError::Io(format!("Failed to copy {} to {}: {}", url, destination.display(), e)))?;
+                return Ok(FetchedArtifact {
+                    warning: Some(format!("{} has no checksum or signature; using the copy pinned at sha256:{}", url, artifact.digest)),
+                    artifact,
+                    checksum_verified: false,
+                    signature_verified: false,
+                });
+            }
+            download_to(url, destination).await?;
+            let (digest, size) = hash_file(destination).map_err(|e| IntegrityError::Io(format!("Failed to read {}: {}", destination.display(), e)))?;
+            return Ok(FetchedArtifact {
+                artifact: CachedArtifact { url: url.to_string(), digest, size, cached_at: chrono::Utc::now() },
+                checksum_verified: false,
+                signature_verified: false,
+                warning: Some(format!("{} has no checksum or signature and was not verified", url)),
+            });
+        }
+
+        let fetched = self.fetch(url, checksum, signature).await?;
+        std::fs::copy(self.object_path(&fetched.artifact.digest), destination)
+            .map_err(|e| IntegrityError::Io(format!("Failed to copy {} to {}: {}", url, destination.display(), e)))?;
+        Ok(fetched)
+    }
+
+    pub fn remove(&self, url: &str) -> Result<(), IntegrityError> {
+        let removed = self.index.lock().unwrap().remove(url);
+        if let Some(artifact) = removed {
+            // Another URL may share the same content
+            let shared = self.index.lock().unwrap().values().any(|a| a.digest == artifact.digest);
+            if !shared {
+                let _ = std::fs::remove_file(self.object_path(&artifact.digest));
+            }
+        }
+        self.save_index()
+    }
+
+    fn save_index(&self) -> Result<(), IntegrityError> {
+        let io_error = |e: String| IntegrityError::Io(format!("Failed to write download cache index: {}", e));
+        std::fs::create_dir_all(&self.root).map_err(|e| io_error(e.to_string()))?;
+        let content = serde_json::to_string_pretty(&self.entries()).map_err(|e| io_error(e.to_string()))?;
+        std::fs::write(self.root.join(INDEX_FILE_NAME), content).map_err(|e| io_error(e.to_string()))
+    }
+}
+
+async fn download_to(url: &str, destination: &Path) -> Result<(), IntegrityError> {
+    let download_error = |e: String| IntegrityError::Download { url: url.to_string(), reason: e };
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| download_error(e.to_string()))?;
+
+    let mut file = std::fs::File::create(destination).map_err(|e| download_error(e.to_string()))?;
+    while let Some(chunk) = response.chunk().await.map_err(|e| download_error(e.to_string()))? {
+        file.write_all(&chunk).map_err(|e| download_error(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_insert_and_lookup() {
+        let dir = tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let file = dir.path().join("tool.deb");
+        std::fs::write(&file, b"installer bytes").unwrap();
+        let (digest, _) = hash_file(&file).unwrap();
+
+        let wrong = format!("sha256:{}", "0".repeat(64));
+        assert!(matches!(
+            cache.insert_file("https://x.test/tool.deb", &file, Some(&wrong)),
+            Err(IntegrityError::ChecksumMismatch { .. })
+        ));
+
+        let artifact = cache.insert_file("https://x.test/tool.deb", &file, Some(&format!("sha256:{}", digest))).unwrap();
+        assert_eq!(artifact.digest, digest);
+        assert_eq!(artifact.size, 15);
+
+        // Found by checksum from any URL, by URL when there is no checksum, and again after a reload
+        assert!(cache.lookup("https://mirror.test/tool.deb", Some(&digest)).is_some());
+        let reloaded = DownloadCache::new(dir.path().join("cache"));
+        assert_eq!(reloaded.lookup("https://x.test/tool.deb", None).unwrap().digest, digest);
+        assert!(reloaded.lookup("https://x.test/other.deb", None).is_none());
+
+        // Corrupted objects are evicted instead of being handed out
+        std::fs::write(cache.object_path(&digest), b"tampered").unwrap();
+        assert!(cache.lookup("https://x.test/tool.deb", None).is_none());
+        assert!(!cache.object_path(&digest).exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses_bad_checksums() {
+        let dir = tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let file = dir.path().join("tool.deb");
+        std::fs::write(&file, b"installer bytes").unwrap();
+        let artifact = cache.insert_file("https://x.test/tool.deb", &file, None).unwrap();
+
+        // Cached by URL, but a manifest pinning a different hash still gets nothing
+        let wrong = format!("sha256:{}", "0".repeat(64));
+        assert!(cache.fetch("https://x.test/tool.deb", Some("sha256:abc123..."), None).await.is_err());
+        assert!(cache.lookup("https://x.test/tool.deb", Some(&wrong)).is_none());
+
+        let fetched = cache.fetch("https://x.test/tool.deb", Some(&artifact.digest), None).await.unwrap();
+        assert!(fetched.checksum_verified && fetched.warning.is_none());
+
+        // Unpinned URLs are neither handed out from the cache nor downloaded without an opt-in
+        assert!(matches!(cache.fetch("https://x.test/tool.deb", None, None).await, Err(IntegrityError::Unverified { .. })));
+        let destination = dir.path().join("out").join("tool.deb");
+        assert!(matches!(
+            cache.materialize("https://x.test/tool.deb", None, None, false, &destination).await,
+            Err(IntegrityError::Unverified { .. })
+        ));
+        assert!(!destination.exists());
+    }
+}
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::command_runner::CommandSpec;
+use crate::installer::InstallCommand;
+use crate::privilege_broker::PrivilegeBroker;
+use crate::shell_profile::{Shell, ShellProfile};
+use crate::version_manager::manager_search_path;
+
+// Everything an installation changed on the machine, in the order it happened
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalEntry {
+    PackageInstalled {
+        manager: String,
+        packages: Vec<String>,
+        undo: InstallCommand,
+    },
+    PathAdded {
+        profile: String,
+        line: String,
+    },
+    FileCreated {
+        path: String,
+    },
+    ProfileUpdated {
+        shell: Shell,
+        profile: String,
+        owner: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RollbackReport {
+    pub undone: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl RollbackReport {
+    pub fn succeeded(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn merge(&mut self, other: RollbackReport) {
+        self.undone.extend(other.undone);
+        self.failed.extend(other.failed);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InstallJournal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl InstallJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        if !self.entries.contains(&entry) {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Records files from `candidates` that exist now but were missing in `existing_before`
+    pub fn record_created_files(&mut self, candidates: &[String], existing_before: &[String]) {
+        for candidate in candidates {
+            if existing_before.contains(candidate) {
+                continue;
+            }
+            if expand_home(candidate).exists() {
+                self.record(JournalEntry::FileCreated { path: candidate.clone() });
+            }
+        }
+    }
+
+    // Undoes entries newest first; failures are collected rather than aborting the rollback.
+    // Anything that needed root to install needs it again to be removed.
+    pub fn rollback(&self, broker: &PrivilegeBroker) -> RollbackReport {
+        let mut report = RollbackReport::default();
+
+        for entry in self.entries.iter().rev() {
+            let (description, result) = match entry {
+                JournalEntry::PackageInstalled { manager, packages, undo } => (
+                    format!("{} packages {}", manager, packages.join(", ")),
+                    run_undo(undo, broker),
+                ),
+                JournalEntry::PathAdded { profile, line } => (
+                    format!("PATH entry in {}", profile),
+                    remove_profile_line(&expand_home(profile), line),
+                ),
+                JournalEntry::FileCreated { path } => (format!("file {}", path), remove_path(&expand_home(path), broker)),
+                JournalEntry::ProfileUpdated { shell, profile, owner } => (
+                    format!("{} entries in {}", owner, profile),
+                    ShellProfile::new(*shell, expand_home(profile)).remove_owner(owner).map(|_| ()),
+                ),
+            };
+
+            match result {
+                Ok(()) => report.undone.push(description),
+                Err(error) => report.failed.push(format!("{}: {}", description, error)),
+            }
+        }
+
+        report
+    }
+}
+
+pub fn existing_files(candidates: &[String]) -> Vec<String> {
+    candidates
+        .iter()
+        .filter(|candidate| expand_home(candidate).exists())
+        .cloned()
+        .collect()
+}
+
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn run_undo(command: &InstallCommand, broker: &PrivilegeBroker) -> Result<(), String> {
+    let output = if command.needs_sudo() {
+        broker.run_elevated(&command.to_spec(), None, "rollback")?
+    } else {
+        command.to_spec().env("PATH", &manager_search_path().to_string_lossy()).run().map_err(|e| e.to_string())?
+    };
+
+    if output.success {
+        Ok(())
+    } else {
+        Err(output.stderr.trim().to_string())
+    }
+}
+
+fn remove_profile_line(profile: &Path, line: &str) -> Result<(), String> {
+    let content = match std::fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {}", profile.display(), e)),
+    };
+
+    let remaining: Vec<&str> = content.lines().filter(|l| l.trim() != line.trim()).collect();
+    let mut updated = remaining.join("\n");
+    if content.ends_with('\n') {
+        updated.push('\n');
+    }
+
+    std::fs::write(profile, updated).map_err(|e| format!("Failed to write {}: {}", profile.display(), e))
+}
+
+// Files written by root, such as apt keyrings, are removed through the broker
+fn remove_path(path: &Path, broker: &PrivilegeBroker) -> Result<(), String> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            let path = path.display().to_string();
+            let output = broker.run_elevated(&CommandSpec::new("rm", &["-rf", &path]), None, "rollback")?;
+            if output.success {
+                Ok(())
+            } else {
+                Err(format!("Failed to remove {}: {}", path, output.stderr.trim()))
+            }
+        }
+        Err(e) => Err(format!("Failed to remove {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privilege_broker::ElevationMethod;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rollback_removes_created_files_and_profile_lines() {
+        let dir = tempdir().unwrap();
+        let created = dir.path().join("download.deb");
+        let existing = dir.path().join("existing.txt");
+        let profile = dir.path().join(".zshrc");
+        std::fs::write(&existing, "keep").unwrap();
+        std::fs::write(&profile, "alias ll='ls -l'\nexport PATH=\"/opt/tool/bin:$PATH\"\n").unwrap();
+
+        let candidates = vec![created.display().to_string(), existing.display().to_string()];
+        let before = existing_files(&candidates);
+        std::fs::write(&created, "installer").unwrap();
+
+        let mut journal = InstallJournal::new();
+        journal.record_created_files(&candidates, &before);
+        journal.record(JournalEntry::PathAdded {
+            profile: profile.display().to_string(),
+            line: "export PATH=\"/opt/tool/bin:$PATH\"".to_string(),
+        });
+        assert_eq!(journal.entries.len(), 2);
+
+        let report = journal.rollback(&PrivilegeBroker::new(ElevationMethod::Unavailable));
+        assert!(report.succeeded());
+        assert_eq!(report.undone.len(), 2);
+        assert!(!created.exists());
+        assert!(existing.exists());
+        assert_eq!(std::fs::read_to_string(&profile).unwrap(), "alias ll='ls -l'\n");
+    }
+}
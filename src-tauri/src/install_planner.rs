@@ -0,0 +1,921 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::command_runner::CommandSpec;
+use crate::installer::{InstallCommand, ToolInstallRequest, UniversalInstaller};
+use crate::integrity::SignatureSpec;
+use crate::manifest::{ManifestRegistry, Platform, PlatformSpec};
+use crate::package_command::PackageInstall;
+use crate::version_manager::VersionManager;
+use crate::workspace_manager::InstalledTool;
+
+// Tool ids of the package managers the installer knows how to bootstrap
+const PACKAGE_MANAGER_TOOLS: &[(&str, &str)] = &[("homebrew", "Homebrew")];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub tool_id: String,
+    pub tool: ToolInstallRequest,
+    pub manifest_id: Option<String>,
+    pub depends_on: Vec<String>,
+    pub stage: usize,
+    pub commands: Vec<InstallCommand>,
+    pub requires_sudo: bool,
+    pub download_size: Option<u64>,
+    pub path_updates: Vec<String>,
+    pub files_touched: Vec<String>,
+    pub verify_commands: Vec<InstallCommand>,
+    #[serde(default)]
+    pub download: Option<PlannedDownload>,
+}
+
+#[cfg(test)]
+impl PlanStep {
+    // A required CLI tool in the first stage with nothing to run, for tests to fill in
+    pub(crate) fn for_tool(tool_id: &str) -> Self {
+        Self {
+            tool_id: tool_id.to_string(),
+            tool: ToolInstallRequest {
+                name: tool_id.to_string(),
+                tool_type: "cli".to_string(),
+                version: None,
+                required: true,
+                alternatives: Vec::new(),
+                allow_unverified: false,
+            },
+            manifest_id: None,
+            depends_on: Vec::new(),
+            stage: 0,
+            commands: Vec::new(),
+            requires_sudo: false,
+            download_size: None,
+            path_updates: Vec::new(),
+            files_touched: Vec::new(),
+            verify_commands: Vec::new(),
+            download: None,
+        }
+    }
+}
+
+// An installer the step fetches through the download cache instead of running curl
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedDownload {
+    pub url: String,
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub signature: Option<SignatureSpec>,
+    pub destination: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedTool {
+    pub tool_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallPlan {
+    pub id: String,
+    pub platform: Platform,
+    pub steps: Vec<PlanStep>,
+    pub stages: Vec<Vec<String>>,
+    pub skipped: Vec<SkippedTool>,
+    pub unresolved: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// What a plan would do to the machine, without running any of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallPreview {
+    pub plan: InstallPlan,
+    pub command_count: usize,
+    pub requires_sudo: bool,
+    pub elevated_steps: Vec<String>,
+    pub download_size: u64,
+    pub unknown_size_tools: Vec<String>,
+    // Tools whose installer download has no checksum or signature; they need the user's opt-in
+    pub unverified_downloads: Vec<String>,
+    pub path_updates: Vec<String>,
+    pub files_touched: Vec<String>,
+}
+
+pub struct InstallPlanner<'a> {
+    registry: &'a ManifestRegistry,
+    platform: Platform,
+    installed: Vec<InstalledTool>,
+}
+
+impl InstallPlan {
+    pub fn step(&self, tool_id: &str) -> Option<&PlanStep> {
+        self.steps.iter().find(|s| s.tool_id == tool_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    // Steps with at least one command that has to run as root
+    pub fn elevated_steps(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .filter(|s| s.requires_sudo)
+            .map(|s| s.tool_id.clone())
+            .collect()
+    }
+
+    pub fn preview(self) -> InstallPreview {
+        let mut path_updates: Vec<String> = Vec::new();
+        let mut files_touched: Vec<String> = Vec::new();
+        for step in &self.steps {
+            for path in &step.path_updates {
+                if !path_updates.contains(path) {
+                    path_updates.push(path.clone());
+                }
+            }
+            for file in &step.files_touched {
+                if !files_touched.contains(file) {
+                    files_touched.push(file.clone());
+                }
+            }
+        }
+
+        InstallPreview {
+            command_count: self.steps.iter().map(|s| s.commands.len()).sum(),
+            requires_sudo: self.steps.iter().any(|s| s.requires_sudo),
+            elevated_steps: self.elevated_steps(),
+            download_size: self.steps.iter().filter_map(|s| s.download_size).sum(),
+            unknown_size_tools: self
+                .steps
+                .iter()
+                .filter(|s| s.download_size.is_none())
+                .map(|s| s.tool_id.clone())
+                .collect(),
+            unverified_downloads: self
+                .steps
+                .iter()
+                .filter(|s| s.download.as_ref().map(|d| d.checksum.is_none() && d.signature.is_none()).unwrap_or(false))
+                .map(|s| s.tool_id.clone())
+                .collect(),
+            path_updates,
+            files_touched,
+            plan: self,
+        }
+    }
+}
+
+impl<'a> InstallPlanner<'a> {
+    pub fn new(registry: &'a ManifestRegistry, platform: Platform) -> Self {
+        Self {
+            registry,
+            platform,
+            installed: Vec::new(),
+        }
+    }
+
+    pub fn with_installed(mut self, tools: Vec<InstalledTool>) -> Self {
+        self.installed = tools;
+        self
+    }
+
+    pub fn plan_template(&self, template_id: &str) -> Result<InstallPlan, String> {
+        let template = self
+            .registry
+            .template(template_id)
+            .ok_or_else(|| format!("Template not found: {}", template_id))?;
+
+        let requests = template
+            .tool_ids()
+            .into_iter()
+            .map(|id| self.request_for(&id, true))
+            .collect();
+
+        self.plan(requests)
+    }
+
+    pub fn plan(&self, requests: Vec<ToolInstallRequest>) -> Result<InstallPlan, String> {
+        let mut steps: HashMap<String, PlanStep> = HashMap::new();
+        let mut order: Vec<String> = Vec::new();
+        let mut skipped = Vec::new();
+        let mut unresolved = Vec::new();
+        let mut satisfied: HashSet<String> = HashSet::new();
+
+        // Resolve requests and pull in their dependencies, breadth first
+        let mut queue: Vec<ToolInstallRequest> = requests;
+        let mut index = 0;
+        while index < queue.len() {
+            let request = queue[index].clone();
+            index += 1;
+
+            let tool_id = self.canonical_id(&request.name);
+            if steps.contains_key(&tool_id) || satisfied.contains(&tool_id) {
+                continue;
+            }
+
+            if let Some(installed) = self.find_installed(&request.name) {
+                skipped.push(SkippedTool {
+                    tool_id: tool_id.clone(),
+                    reason: format!("Already installed ({} {})", installed.name, installed.version),
+                });
+                satisfied.insert(tool_id);
+                continue;
+            }
+
+            if let Some(alternative) = request.alternatives.iter().find(|alt| self.find_installed(alt).is_some()) {
+                skipped.push(SkippedTool {
+                    tool_id: tool_id.clone(),
+                    reason: format!("Satisfied by installed alternative {}", alternative),
+                });
+                satisfied.insert(tool_id);
+                continue;
+            }
+
+            if !request.required {
+                let planned_alternative = request
+                    .alternatives
+                    .iter()
+                    .find(|alt| steps.contains_key(&self.canonical_id(alt)));
+                if let Some(alternative) = planned_alternative {
+                    skipped.push(SkippedTool {
+                        tool_id: tool_id.clone(),
+                        reason: format!("Alternative {} is already part of the plan", alternative),
+                    });
+                    satisfied.insert(tool_id);
+                    continue;
+                }
+            }
+
+            let step = match self.resolve_step(&request) {
+                Some(step) => step,
+                None => {
+                    unresolved.push(tool_id);
+                    continue;
+                }
+            };
+
+            // Opting in to unverified downloads for a tool covers what it pulls in, such as Homebrew
+            for dependency in &step.depends_on {
+                let mut dependency = self.request_for(dependency, true);
+                dependency.allow_unverified = request.allow_unverified;
+                queue.push(dependency);
+            }
+
+            order.push(tool_id.clone());
+            steps.insert(tool_id, step);
+        }
+
+        // Dependencies that are already installed impose no ordering
+        for step in steps.values_mut() {
+            step.depends_on.retain(|dep| !satisfied.contains(dep));
+        }
+
+        // Nothing is installed on top of a dependency that won't be, nor on top of what needs it
+        loop {
+            let mut blocked: Vec<(String, String)> = steps
+                .iter()
+                .filter_map(|(tool_id, step)| {
+                    let missing = step.depends_on.iter().find(|dep| !steps.contains_key(*dep))?;
+                    Some((tool_id.clone(), missing.clone()))
+                })
+                .collect();
+            if blocked.is_empty() {
+                break;
+            }
+            blocked.sort();
+            for (tool_id, missing) in blocked {
+                steps.remove(&tool_id);
+                unresolved.push(format!("{} (requires {})", tool_id, missing));
+            }
+        }
+        order.retain(|tool_id| steps.contains_key(tool_id));
+
+        let conflicts = self.detect_conflicts(&order);
+        let stages = Self::topological_stages(&mut steps, &order)?;
+
+        let mut ordered_steps = Vec::new();
+        for stage in &stages {
+            for tool_id in stage {
+                if let Some(step) = steps.remove(tool_id) {
+                    ordered_steps.push(step);
+                }
+            }
+        }
+
+        Ok(InstallPlan {
+            id: uuid::Uuid::new_v4().to_string(),
+            platform: self.platform.clone(),
+            steps: ordered_steps,
+            stages,
+            skipped,
+            unresolved,
+            conflicts,
+            created_at: chrono::Utc::now(),
+        })
+    }
+
+    // Resolves a single request into an executable step; dependencies are listed but not planned
+    pub fn resolve_step(&self, request: &ToolInstallRequest) -> Option<PlanStep> {
+        let tool_id = self.canonical_id(&request.name);
+        let manifest = self.registry.get(&request.name);
+        let spec = manifest.and_then(|m| m.platform_spec(&self.platform));
+        let mut commands = self.commands_for(request)?;
+
+        let mut depends_on: Vec<String> = manifest
+            .map(|m| m.dependencies.iter().map(|d| self.canonical_id(d)).collect())
+            .unwrap_or_default();
+
+        // Package-manager based installs need the package manager itself
+        if let Some(manager) = spec.as_ref().and_then(|s| s.package_manager.as_deref()) {
+            if let Some((manager_id, _)) = PACKAGE_MANAGER_TOOLS.iter().find(|(id, _)| *id == manager) {
+                depends_on.push(manager_id.to_string());
+            }
+        }
+        dedup(&mut depends_on);
+
+        let mut files_touched: Vec<String> = Vec::new();
+        let mut download = None;
+        if let Some(spec) = &spec {
+            if let Some(installer) = &spec.installer {
+                let fetch = self.manifest_command(&tool_id, &installer.download_command(&tool_id));
+                commands.retain(|command| Some(command) != fetch.as_ref());
+                download = Some(PlannedDownload {
+                    url: installer.url.clone(),
+                    checksum: installer.checksum.clone(),
+                    signature: installer.signature.clone(),
+                    destination: installer.download_path(&tool_id).display().to_string(),
+                });
+                files_touched.extend(installer.install_path.clone());
+            }
+            files_touched.extend(spec.settings_files());
+        }
+        // A version manager that still has to be bootstrapped brings its install script
+        if let Some(manager) = VersionManager::for_language(&request.name).filter(|_| spec.is_none() && request.tool_type == "language") {
+            if !manager.is_installed() {
+                download = manager.bootstrap_download();
+            }
+        }
+        for command in &commands {
+            files_touched.extend(written_paths(command));
+        }
+        dedup(&mut files_touched);
+
+        Some(PlanStep {
+            verify_commands: self.verify_commands_for(request, spec.as_ref()),
+            requires_sudo: commands.iter().any(|c| c.needs_sudo())
+                || spec.as_ref().map(|s| s.requires_admin()).unwrap_or(false),
+            download_size: spec.as_ref().and_then(|s| s.download_size()),
+            path_updates: match &spec {
+                Some(spec) => spec.path_updates.clone(),
+                None => VersionManager::for_language(&request.name)
+                    .filter(|_| request.tool_type == "language")
+                    .map(|manager| manager.path_updates())
+                    .unwrap_or_default(),
+            },
+            files_touched,
+            commands,
+            tool_id,
+            tool: request.clone(),
+            manifest_id: manifest.map(|m| m.id.clone()),
+            depends_on,
+            stage: 0,
+            download,
+        })
+    }
+
+    pub fn resolve_uninstall(&self, request: &ToolInstallRequest) -> Option<PlanStep> {
+        let spec = self.spec_for(request);
+        let mut step = self.resolve_step(request)?;
+
+        let commands: Vec<InstallCommand> = match spec.as_ref().filter(|s| !s.uninstall_commands.is_empty()) {
+            Some(spec) => spec
+                .uninstall_commands
+                .iter()
+                .map(|c| self.manifest_command(&step.tool_id, c))
+                .collect::<Option<Vec<_>>>()?,
+            None => {
+                let mut commands: Vec<InstallCommand> = Self::main_package_install(&step.commands)
+                    .map(|install| install.undo(&install.packages))
+                    .into_iter()
+                    .collect();
+
+                // App bundles copied out of a disk image are removed directly
+                if let Some(install_path) = spec.as_ref().and_then(|s| s.installer.as_ref()).and_then(|i| i.install_path.as_deref()) {
+                    commands.push(InstallCommand::new("rm", &["-rf", install_path]));
+                }
+                commands
+            }
+        };
+
+        if commands.is_empty() {
+            return None;
+        }
+
+        step.requires_sudo = commands.iter().any(|c| c.needs_sudo());
+        step.commands = commands;
+        step.download_size = None;
+        step.download = None;
+        step.files_touched = Vec::new();
+        step.depends_on = Vec::new();
+        Some(step)
+    }
+
+    pub fn resolve_upgrade(&self, request: &ToolInstallRequest) -> Option<PlanStep> {
+        let spec = self.spec_for(request);
+        let mut step = self.resolve_step(request)?;
+
+        let upgrade = match spec.as_ref().filter(|s| !s.upgrade_commands.is_empty()) {
+            Some(spec) => Some(
+                spec.upgrade_commands
+                    .iter()
+                    .map(|c| self.manifest_command(&step.tool_id, c))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            None => Self::main_package_install(&step.commands)
+                .and_then(|install| install.upgrade(&install.packages))
+                .map(|command| vec![command]),
+        };
+
+        // Without an in-place upgrade, re-running the installer fetches the manifest version
+        if let Some(commands) = upgrade {
+            step.requires_sudo = commands.iter().any(|c: &InstallCommand| c.needs_sudo());
+            step.download_size = None;
+            step.download = None;
+            step.commands = commands;
+        }
+        step.depends_on = Vec::new();
+        Some(step)
+    }
+
+    // The last package-manager install is the tool itself; earlier ones are prerequisites
+    fn main_package_install(commands: &[InstallCommand]) -> Option<PackageInstall> {
+        commands
+            .iter()
+            .filter_map(PackageInstall::parse)
+            .filter(|install| install.manager != "code" && install.manager != "npm")
+            .last()
+    }
+
+    fn spec_for(&self, request: &ToolInstallRequest) -> Option<PlatformSpec> {
+        self.registry.get(&request.name).and_then(|m| m.platform_spec(&self.platform))
+    }
+
+    fn verify_commands_for(&self, request: &ToolInstallRequest, spec: Option<&PlatformSpec>) -> Vec<InstallCommand> {
+        let manifest_commands: Vec<InstallCommand> = spec
+            .and_then(|s| s.verification.as_ref())
+            .map(|v| v.all_commands())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|command| self.manifest_command(&request.name, command))
+            .collect();
+
+        if !manifest_commands.is_empty() {
+            return manifest_commands;
+        }
+
+        UniversalInstaller::builtin_binary(&request.name)
+            .map(|binary| vec![InstallCommand::new(binary, &["--version"])])
+            .unwrap_or_default()
+    }
+
+    // Manifest lines run as argv; a line that needs a shell is refused unless its manifest is allowlisted
+    fn manifest_command(&self, tool: &str, command_line: &str) -> Option<InstallCommand> {
+        CommandSpec::parse_for_tool(command_line, tool, self.registry, &self.platform)
+            .ok()
+            .map(InstallCommand::from)
+    }
+
+    // Manifest commands for this platform take precedence over the builtin installers
+    pub fn commands_for(&self, request: &ToolInstallRequest) -> Option<Vec<InstallCommand>> {
+        let manifest = self.registry.get(&request.name);
+        let spec: Option<PlatformSpec> = manifest.and_then(|m| m.platform_spec(&self.platform));
+
+        match (manifest, spec) {
+            (Some(manifest), Some(spec)) => {
+                let commands = spec
+                    .commands(&manifest.id)
+                    .iter()
+                    .map(|command| self.manifest_command(&manifest.id, command))
+                    .collect::<Option<Vec<_>>>()?;
+                (!commands.is_empty()).then_some(commands)
+            }
+            _ => UniversalInstaller::builtin_commands(request).ok(),
+        }
+    }
+
+    // Kahn's algorithm, grouped into stages whose steps can run in parallel
+    fn topological_stages(steps: &mut HashMap<String, PlanStep>, order: &[String]) -> Result<Vec<Vec<String>>, String> {
+        for tool_id in order {
+            if let Some(missing) = steps[tool_id].depends_on.iter().find(|dep| !steps.contains_key(*dep)) {
+                return Err(format!("{} depends on {}, which is not part of the plan", tool_id, missing));
+            }
+        }
+
+        let mut remaining: Vec<String> = order.to_vec();
+        let mut done: HashSet<String> = HashSet::new();
+        let mut stages = Vec::new();
+
+        while !remaining.is_empty() {
+            let ready: Vec<String> = remaining
+                .iter()
+                .filter(|id| {
+                    steps[*id]
+                        .depends_on
+                        .iter()
+                        .all(|dep| done.contains(dep))
+                })
+                .cloned()
+                .collect();
+
+            if ready.is_empty() {
+                return Err(format!("Dependency cycle detected between: {}", remaining.join(", ")));
+            }
+
+            for id in &ready {
+                if let Some(step) = steps.get_mut(id) {
+                    step.stage = stages.len();
+                }
+                done.insert(id.clone());
+            }
+            remaining.retain(|id| !done.contains(id));
+            stages.push(ready);
+        }
+
+        Ok(stages)
+    }
+
+    fn detect_conflicts(&self, planned: &[String]) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        for tool_id in planned {
+            for installed in &self.installed {
+                if installed.conflicts.iter().any(|c| self.canonical_id(c) == *tool_id) {
+                    conflicts.push(format!("{} conflicts with installed {}", tool_id, installed.name));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    fn canonical_id(&self, name: &str) -> String {
+        if let Some(manifest) = self.registry.get(name) {
+            return manifest.id.clone();
+        }
+        if let Some((id, _)) = PACKAGE_MANAGER_TOOLS.iter().find(|(id, label)| name.eq_ignore_ascii_case(id) || name.eq_ignore_ascii_case(label)) {
+            return id.to_string();
+        }
+        name.to_lowercase()
+    }
+
+    fn find_installed(&self, name: &str) -> Option<&InstalledTool> {
+        let tool_id = self.canonical_id(name);
+        self.installed.iter().find(|tool| {
+            tool.name.eq_ignore_ascii_case(name) || self.canonical_id(&tool.name) == tool_id
+        })
+    }
+
+    fn request_for(&self, tool_id: &str, required: bool) -> ToolInstallRequest {
+        if let Some(manifest) = self.registry.get(tool_id) {
+            return ToolInstallRequest {
+                name: manifest.name.clone(),
+                tool_type: manifest.tool_type(),
+                version: manifest.version.clone(),
+                required,
+                alternatives: Vec::new(),
+                allow_unverified: false,
+            };
+        }
+
+        if let Some((_, label)) = PACKAGE_MANAGER_TOOLS.iter().find(|(id, _)| *id == tool_id) {
+            return ToolInstallRequest {
+                name: label.to_string(),
+                tool_type: "package".to_string(),
+                version: None,
+                required,
+                alternatives: Vec::new(),
+                allow_unverified: false,
+            };
+        }
+
+        let (tool_type, name) = UniversalInstaller::builtin_tool(tool_id).unwrap_or(("cli", tool_id));
+        ToolInstallRequest {
+            name: name.to_string(),
+            tool_type: tool_type.to_string(),
+            version: None,
+            required,
+            alternatives: Vec::new(),
+            allow_unverified: false,
+        }
+    }
+}
+
+// Drops repeats anywhere in the list, keeping the first occurrence and the original order
+pub(crate) fn dedup(items: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    items.retain(|item| seen.insert(item.clone()));
+}
+
+// Best-effort detection of files a shell command writes to (redirects, tee, curl -o)
+fn written_paths(command: &InstallCommand) -> Vec<String> {
+    let tokens = if command.shell {
+        shell_tokens(&command.display())
+    } else {
+        command.args.clone()
+    };
+
+    let mut paths = Vec::new();
+    let mut expect_path = false;
+    for token in tokens {
+        if expect_path && token.starts_with('-') {
+            continue;
+        }
+        if expect_path && token != "/dev/null" && (token.starts_with('/') || token.starts_with('~')) {
+            paths.push(token.clone());
+        }
+        expect_path = matches!(token.as_str(), ">" | ">>" | "tee" | "-o" | "--output");
+    }
+
+    paths
+}
+
+// Whitespace split that keeps quoted words together, redirects included
+fn shell_tokens(command: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' | '\'' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ToolManifest;
+
+    fn macos() -> Platform {
+        Platform { os: "macos".to_string(), distro: None, distro_like: Vec::new() }
+    }
+
+    fn installed(name: &str, conflicts: Vec<&str>) -> InstalledTool {
+        InstalledTool {
+            name: name.to_string(),
+            tool_type: "cli".to_string(),
+            version: "1.0.0".to_string(),
+            path: format!("/usr/bin/{}", name),
+            size: 0,
+            status: "installed".to_string(),
+            dependencies: Vec::new(),
+            conflicts: conflicts.into_iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn request(name: &str, tool_type: &str, required: bool, alternatives: Vec<&str>) -> ToolInstallRequest {
+        ToolInstallRequest {
+            name: name.to_string(),
+            tool_type: tool_type.to_string(),
+            version: None,
+            required,
+            alternatives: alternatives.into_iter().map(|s| s.to_string()).collect(),
+            allow_unverified: false,
+        }
+    }
+
+    fn manifest(id: &str, dependencies: Vec<&str>) -> ToolManifest {
+        ToolManifest::from_json(&serde_json::json!({
+            "id": id,
+            "name": id,
+            "type": "CLI",
+            "category": "tool",
+            "platforms": { "macos": { "installCommand": format!("brew install {}", id) } },
+            "dependencies": dependencies,
+        }).to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_plan_pulls_in_package_manager() {
+        let registry = ManifestRegistry::bundled();
+        let planner = InstallPlanner::new(&registry, macos());
+
+        let plan = planner.plan(vec![request("Node.js", "language", true, vec![])]).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.stages, vec![vec!["homebrew".to_string()], vec!["nodejs".to_string()]]);
+        assert_eq!(plan.step("nodejs").unwrap().depends_on, vec!["homebrew".to_string()]);
+        assert_eq!(plan.step("nodejs").unwrap().stage, 1);
+
+        // With Homebrew already present the dependency disappears
+        let planner = InstallPlanner::new(&registry, macos())
+            .with_installed(vec![installed("Homebrew", vec![])]);
+        let plan = planner.plan(vec![request("Node.js", "language", true, vec![])]).unwrap();
+        assert_eq!(plan.steps.len(), 1);
+        assert!(plan.step("nodejs").unwrap().depends_on.is_empty());
+        assert_eq!(plan.skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_plan_skips_installed_and_alternatives() {
+        let registry = ManifestRegistry::bundled();
+        let planner = InstallPlanner::new(&registry, macos())
+            .with_installed(vec![installed("Node.js", vec![]), installed("MySQL", vec![])]);
+
+        let plan = planner.plan(vec![
+            request("nodejs", "language", true, vec![]),
+            request("PostgreSQL", "database", true, vec!["MySQL"]),
+            request("Git", "cli", true, vec![]),
+        ]).unwrap();
+
+        assert_eq!(plan.skipped.len(), 2);
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].tool_id, "git");
+    }
+
+    #[test]
+    fn test_optional_tool_skipped_when_alternative_planned() {
+        let registry = ManifestRegistry::bundled();
+        let planner = InstallPlanner::new(&registry, macos());
+
+        let plan = planner.plan(vec![
+            request("PostgreSQL", "database", true, vec![]),
+            request("MySQL", "database", false, vec!["PostgreSQL"]),
+        ]).unwrap();
+
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.skipped[0].tool_id, "mysql");
+    }
+
+    #[test]
+    fn test_plan_orders_dependencies_and_detects_cycles() {
+        let mut registry = ManifestRegistry::new();
+        registry.add_manifest(manifest("app", vec!["lib", "runtime"]));
+        registry.add_manifest(manifest("lib", vec!["runtime"]));
+        registry.add_manifest(manifest("runtime", vec![]));
+        registry.add_manifest(manifest("tool", vec![]));
+
+        let planner = InstallPlanner::new(&registry, macos());
+        let plan = planner.plan(vec![request("app", "cli", true, vec![]), request("tool", "cli", true, vec![])]).unwrap();
+
+        let ids: Vec<&str> = plan.steps.iter().map(|s| s.tool_id.as_str()).collect();
+        assert_eq!(plan.stages.len(), 3);
+        assert!(plan.stages[0].contains(&"runtime".to_string()));
+        assert!(plan.stages[0].contains(&"tool".to_string()));
+        assert_eq!(plan.stages[1], vec!["lib".to_string()]);
+        assert_eq!(plan.stages[2], vec!["app".to_string()]);
+        assert_eq!(ids.last(), Some(&"app"));
+
+        let mut cyclic = ManifestRegistry::new();
+        cyclic.add_manifest(manifest("a", vec!["b"]));
+        cyclic.add_manifest(manifest("b", vec!["a"]));
+        let planner = InstallPlanner::new(&cyclic, macos());
+        let result = planner.plan(vec![request("a", "cli", true, vec![])]);
+        assert!(result.unwrap_err().contains("cycle"));
+
+        let mut steps: HashMap<String, PlanStep> = HashMap::new();
+        steps.insert("app".to_string(), planner.resolve_step(&request("a", "cli", true, vec![])).unwrap());
+        assert!(InstallPlanner::topological_stages(&mut steps, &["app".to_string()]).unwrap_err().contains("not part of the plan"));
+    }
+
+    #[test]
+    fn test_plan_drops_steps_on_top_of_unresolved_dependencies() {
+        let mut registry = ManifestRegistry::new();
+        registry.add_manifest(manifest("app", vec!["ghost"]));
+        registry.add_manifest(manifest("cli", vec!["app"]));
+        registry.add_manifest(manifest("tool", vec![]));
+
+        let plan = InstallPlanner::new(&registry, macos())
+            .plan(vec![request("cli", "cli", true, vec![]), request("tool", "cli", true, vec![])])
+            .unwrap();
+        assert_eq!(plan.stages, vec![vec!["tool".to_string()]]);
+        assert!(plan.unresolved.contains(&"ghost".to_string()));
+        assert!(plan.unresolved.contains(&"app (requires ghost)".to_string()));
+        assert!(plan.unresolved.contains(&"cli (requires app)".to_string()));
+    }
+
+    #[test]
+    fn test_plan_template_reports_unresolved_and_conflicts() {
+        let registry = ManifestRegistry::bundled();
+        let planner = InstallPlanner::new(&registry, macos())
+            .with_installed(vec![installed("Homebrew", vec![]), installed("colima", vec!["docker-desktop"])]);
+
+        let plan = planner.plan_template("backend-complete").unwrap();
+        assert!(plan.step("nodejs").is_some());
+        assert!(plan.step("vscode").is_some());
+        assert!(plan.step("python").is_some());
+        assert!(plan.unresolved.contains(&"postman".to_string()));
+        assert_eq!(plan.conflicts.len(), 1);
+
+        assert!(planner.plan_template("missing-template").is_err());
+    }
+
+    #[test]
+    fn test_plan_preview() {
+        let registry = ManifestRegistry::bundled();
+        let ubuntu = Platform { os: "linux".to_string(), distro: Some("ubuntu".to_string()), distro_like: Vec::new() };
+        let planner = InstallPlanner::new(&registry, ubuntu);
+
+        let preview = planner
+            .plan(vec![request("nodejs", "language", true, vec![]), request("docker-desktop", "cli", true, vec![])])
+            .unwrap()
+            .preview();
+
+        assert!(preview.requires_sudo);
+        assert_eq!(preview.elevated_steps.len(), 2);
+        assert_eq!(preview.download_size, 240 * 1024 * 1024);
+        assert_eq!(preview.command_count, 9);
+        assert!(preview.files_touched.contains(&"/usr/share/keyrings/docker-archive-keyring.gpg".to_string()));
+        assert!(preview.files_touched.contains(&"/etc/apt/sources.list.d/docker.list".to_string()));
+
+        let step = preview.plan.step("nodejs").unwrap();
+        assert_eq!(step.commands[1].display(), "sudo apt-get install -y nodejs");
+        assert_eq!(step.verify_commands.len(), 2);
+
+        // Builtin installers have no size information
+        let preview = InstallPlanner::new(&registry, macos())
+            .plan(vec![request("Rust", "language", true, vec![])])
+            .unwrap()
+            .preview();
+        assert!(!preview.requires_sudo);
+        assert!(preview.elevated_steps.is_empty());
+        assert_eq!(preview.unknown_size_tools, vec!["rust".to_string()]);
+        // rustup itself is installed first when it is missing, then the toolchain becomes the default
+        let commands = &preview.plan.steps[0].commands;
+        assert_eq!(commands[commands.len() - 2].display(), "rustup toolchain install stable");
+        assert_eq!(commands.last().unwrap().display(), "rustup default stable");
+
+        // Installers the manifest doesn't pin are called out before anything runs
+        let preview = InstallPlanner::new(&registry, macos())
+            .plan(vec![request("vscode", "gui", true, vec![])])
+            .unwrap()
+            .preview();
+        assert_eq!(preview.unverified_downloads, vec!["vscode".to_string()]);
+    }
+
+    #[test]
+    fn test_unverified_opt_in_covers_dependencies() {
+        let registry = ManifestRegistry::bundled();
+        let planner = InstallPlanner::new(&registry, macos());
+
+        // Homebrew's installer has no checksum, so Node.js on macOS needs the opt-in for it too
+        let preview = planner.plan(vec![request("nodejs", "language", true, vec![])]).unwrap().preview();
+        assert_eq!(preview.unverified_downloads, vec!["homebrew".to_string()]);
+        assert!(!preview.plan.step("homebrew").unwrap().tool.allow_unverified);
+
+        let mut node = request("nodejs", "language", true, vec![]);
+        node.allow_unverified = true;
+        let plan = planner.plan(vec![node]).unwrap();
+        assert!(plan.step("homebrew").unwrap().tool.allow_unverified);
+    }
+
+    #[test]
+    fn test_dedup_drops_repeats_that_are_not_adjacent() {
+        let mut items: Vec<String> = ["homebrew", "git", "homebrew", "/etc/x", "git"].iter().map(|s| s.to_string()).collect();
+        dedup(&mut items);
+        assert_eq!(items, vec!["homebrew", "git", "/etc/x"]);
+    }
+
+    #[test]
+    fn test_resolve_uninstall_and_upgrade() {
+        let registry = ManifestRegistry::bundled();
+        let ubuntu = Platform { os: "linux".to_string(), distro: Some("ubuntu".to_string()), distro_like: Vec::new() };
+        let planner = InstallPlanner::new(&registry, ubuntu);
+
+        // Only the tool's own packages are removed, not the prerequisites installed before it
+        let docker = request("docker-desktop", "cli", true, vec![]);
+        let step = planner.resolve_uninstall(&docker).unwrap();
+        assert_eq!(step.commands.len(), 1);
+        assert_eq!(
+            step.commands[0].display(),
+            "sudo apt-get remove -y docker-ce docker-ce-cli containerd.io docker-compose-plugin"
+        );
+        assert!(step.requires_sudo);
+
+        let step = planner.resolve_upgrade(&docker).unwrap();
+        assert!(step.commands[0].display().starts_with("sudo apt-get install --only-upgrade -y docker-ce"));
+
+        let macos_planner = InstallPlanner::new(&registry, macos());
+        let vscode = request("vscode", "ide", true, vec![]);
+        let step = macos_planner.resolve_uninstall(&vscode).unwrap();
+        assert_eq!(step.commands[0].display(), "rm -rf /Applications/Visual Studio Code.app");
+
+        // Installer-based tools are upgraded by running the installer again, fetched through the download cache
+        let step = macos_planner.resolve_upgrade(&vscode).unwrap();
+        let download = step.download.unwrap();
+        assert_eq!(download.url, "https://code.visualstudio.com/sha/download?build=stable&os=darwin");
+        assert!(step.commands[0].display().starts_with("hdiutil attach"));
+
+        let git = request("Git", "cli", true, vec![]);
+        assert_eq!(macos_planner.resolve_uninstall(&git).unwrap().commands[0].display(), "brew uninstall git");
+        assert_eq!(macos_planner.resolve_upgrade(&git).unwrap().verify_commands[0].display(), "git --version");
+    }
+}
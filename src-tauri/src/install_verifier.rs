@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::install_journal::expand_home;
+use crate::install_planner::PlanStep;
+use crate::installer::{InstallCommand, UniversalInstaller};
+
+// Requested versions that name a channel rather than a number can't be compared
+const VERSION_CHANNELS: &[&str] = &["latest", "stable", "lts", "current", "nightly", "beta"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerificationCheck {
+    pub command: String,
+    pub success: bool,
+    pub version: Option<String>,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerificationReport {
+    pub checks: Vec<VerificationCheck>,
+    pub requested_version: Option<String>,
+    pub detected_version: Option<String>,
+    pub binary: Option<String>,
+    pub resolved_path: Option<String>,
+    pub warnings: Vec<String>,
+    pub failures: Vec<String>,
+    pub verified_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl VerificationReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    // The job status this report leads to once the install commands themselves succeeded
+    pub fn job_status(&self) -> &'static str {
+        if !self.failures.is_empty() {
+            "failed_verification"
+        } else if !self.warnings.is_empty() {
+            "completed_with_warnings"
+        } else {
+            "completed"
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        self.failures.iter().chain(self.warnings.iter()).cloned().collect::<Vec<_>>().join("; ")
+    }
+}
+
+// Runs every verification command of a step against the PATH the workspace will see
+pub struct InstallVerifier {
+    search_path: OsString,
+}
+
+impl InstallVerifier {
+    pub fn new() -> Self {
+        Self {
+            search_path: std::env::var_os("PATH").unwrap_or_default(),
+        }
+    }
+
+    pub fn with_search_path(mut self, search_path: impl Into<OsString>) -> Self {
+        self.search_path = search_path.into();
+        self
+    }
+
+    // Directories a manifest adds to PATH come first, as they will once the shell profile is reloaded
+    pub fn with_path_updates(mut self, path_updates: &[String]) -> Self {
+        let dirs: Vec<PathBuf> = path_updates
+            .iter()
+            .map(|dir| expand_home(dir))
+            .chain(std::env::split_paths(&self.search_path))
+            .collect();
+        if let Ok(joined) = std::env::join_paths(dirs) {
+            self.search_path = joined;
+        }
+        self
+    }
+
+    pub fn search_path(&self) -> &OsString {
+        &self.search_path
+    }
+
+    pub async fn verify(&self, step: &PlanStep) -> VerificationReport {
+        let mut checks = Vec::new();
+        for command in &step.verify_commands {
+            checks.push(self.run_check(command).await);
+        }
+
+        let binary = step_binary(step);
+        let resolved_path = binary.as_deref().and_then(|binary| find_in_path(binary, &self.search_path));
+        evaluate(step, checks, binary, resolved_path)
+    }
+
+    async fn run_check(&self, command: &InstallCommand) -> VerificationCheck {
+        let spec = command.to_spec().env("PATH", &self.search_path.to_string_lossy());
+        match spec.run_async().await {
+            Ok(output) => {
+                // Some tools print their version on stderr
+                let text = format!("{}\n{}", output.stdout, output.stderr);
+                VerificationCheck {
+                    command: command.display(),
+                    success: output.success,
+                    version: if output.success { parse_version(&text) } else { None },
+                    output: if output.success { output.stdout.trim() } else { output.stderr.trim() }.to_string(),
+                }
+            }
+            Err(e) => VerificationCheck {
+                command: command.display(),
+                success: false,
+                version: None,
+                output: e.to_string(),
+            },
+        }
+    }
+}
+
+impl Default for InstallVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn evaluate(
+    step: &PlanStep,
+    checks: Vec<VerificationCheck>,
+    binary: Option<String>,
+    resolved_path: Option<PathBuf>,
+) -> VerificationReport {
+    let mut warnings = Vec::new();
+    let mut failures = Vec::new();
+    let requested_version = step.tool.version.clone();
+    let detected_version = checks.iter().find_map(|check| check.version.clone());
+
+    let failed: Vec<&VerificationCheck> = checks.iter().filter(|check| !check.success).collect();
+    if !checks.is_empty() && failed.len() == checks.len() {
+        failures.push(format!("No verification check for {} succeeded", step.tool.name));
+    } else {
+        for check in &failed {
+            warnings.push(format!("Check `{}` failed: {}", check.command, check.output));
+        }
+    }
+
+    if !checks.is_empty() && failed.len() < checks.len() {
+        match (requested_version.as_deref(), detected_version.as_deref()) {
+            (Some(requested), Some(detected)) => {
+                if version_satisfies(requested, detected) == Some(false) {
+                    failures.push(format!("{} {} was requested but {} is installed", step.tool.name, requested, detected));
+                }
+            }
+            (Some(requested), None) if comparable(requested) => {
+                warnings.push(format!("Could not determine the installed version of {}", step.tool.name));
+            }
+            _ => {}
+        }
+    }
+
+    if let (Some(binary), None) = (&binary, &resolved_path) {
+        warnings.push(format!("`{}` does not resolve on the workspace PATH", binary));
+    }
+
+    VerificationReport {
+        checks,
+        requested_version,
+        detected_version,
+        binary,
+        resolved_path: resolved_path.map(|p| p.display().to_string()),
+        warnings,
+        failures,
+        verified_at: chrono::Utc::now(),
+    }
+}
+
+// The program a step installs: the first verification command that runs a binary directly,
+// or the binary the builtin installer is known for
+fn step_binary(step: &PlanStep) -> Option<String> {
+    step.verify_commands
+        .iter()
+        .find_map(|command| {
+            let program = if command.shell {
+                command.display().split_whitespace().next()?.to_string()
+            } else {
+                command.program.clone()
+            };
+            (!matches!(program.as_str(), "sudo" | "sh" | "bash" | "cmd" | "echo" | "true" | "test" | "exit")).then_some(program)
+        })
+        .or_else(|| UniversalInstaller::builtin_binary(&step.tool.name).map(|b| b.to_string()))
+}
+
+pub fn find_in_path(program: &str, search_path: &OsString) -> Option<PathBuf> {
+    if Path::new(program).components().count() > 1 {
+        return Path::new(program).is_file().then(|| PathBuf::from(program));
+    }
+    let names: Vec<String> = if cfg!(windows) {
+        vec![program.to_string(), format!("{}.exe", program), format!("{}.cmd", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::split_paths(search_path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn parse_version(output: &str) -> Option<String> {
+    let version = regex::Regex::new(r"\d+\.\d+(\.\d+)?").ok()?;
+    version.find(output).map(|m| m.as_str().to_string())
+}
+
+fn comparable(requested: &str) -> bool {
+    !VERSION_CHANNELS.contains(&requested.trim().to_ascii_lowercase().as_str())
+        && requested.chars().any(|c| c.is_ascii_digit())
+}
+
+// A requested version is a prefix by component: "18" accepts 18.19.0, "3.11" accepts 3.11.4.
+// `None` means the request isn't a version number (e.g. "stable").
+pub fn version_satisfies(requested: &str, detected: &str) -> Option<bool> {
+    if !comparable(requested) {
+        return None;
+    }
+    let components = |version: &str| -> Vec<String> {
+        version
+            .trim()
+            .trim_start_matches(|c: char| matches!(c, 'v' | 'V' | '^' | '~' | '=' | '@'))
+            .split(['.', '-', '+'])
+            .filter(|part| !part.is_empty() && *part != "x" && *part != "*")
+            .map(|part| part.to_string())
+            .collect()
+    };
+
+    let requested = components(requested);
+    let detected = components(detected);
+    Some(requested.len() <= detected.len() && requested.iter().zip(detected.iter()).all(|(r, d)| r == d))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(version: Option<&str>, verify_commands: Vec<InstallCommand>) -> PlanStep {
+        let mut step = PlanStep { verify_commands, ..PlanStep::for_tool("tool-a") };
+        step.tool.version = version.map(|v| v.to_string());
+        step
+    }
+
+    fn check(success: bool, version: Option<&str>) -> VerificationCheck {
+        VerificationCheck {
+            command: "tool-a --version".to_string(),
+            success,
+            version: version.map(|v| v.to_string()),
+            output: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_version_satisfies() {
+        assert_eq!(version_satisfies("18", "18.19.0"), Some(true));
+        assert_eq!(version_satisfies("v3.11", "3.11.4"), Some(true));
+        assert_eq!(version_satisfies("3.1", "3.11.4"), Some(false));
+        assert_eq!(version_satisfies("20.x", "18.19.0"), Some(false));
+        assert_eq!(version_satisfies("stable", "1.75.0"), None);
+    }
+
+    #[test]
+    fn test_evaluate_statuses() {
+        let binary = Some("tool-a".to_string());
+        let resolved = Some(PathBuf::from("/usr/bin/tool-a"));
+
+        let report = evaluate(&step(Some("2.5"), Vec::new()), vec![check(true, Some("2.5.1"))], binary.clone(), resolved.clone());
+        assert_eq!(report.job_status(), "completed");
+
+        // A failing secondary check or a binary missing from PATH only warns
+        let report = evaluate(&step(Some("2.5"), Vec::new()), vec![check(true, Some("2.5.1")), check(false, None)], binary.clone(), resolved.clone());
+        assert_eq!(report.job_status(), "completed_with_warnings");
+        let report = evaluate(&step(None, Vec::new()), vec![check(true, Some("2.5.1"))], binary.clone(), None);
+        assert_eq!(report.job_status(), "completed_with_warnings");
+
+        let report = evaluate(&step(Some("3"), Vec::new()), vec![check(true, Some("2.5.1"))], binary.clone(), resolved.clone());
+        assert_eq!(report.job_status(), "failed_verification");
+        let report = evaluate(&step(None, Vec::new()), vec![check(false, None)], binary, resolved);
+        assert_eq!(report.job_status(), "failed_verification");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_verify_uses_workspace_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        let tool = bin.join("tool-a");
+        std::fs::write(&tool, "#!/bin/sh\necho tool-a version 2.5.1\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let step = step(Some("2.5"), vec![InstallCommand::new("tool-a", &["--version"])]);
+        let report = InstallVerifier::new().with_search_path("/usr/bin:/bin").verify(&step).await;
+        assert_eq!(report.job_status(), "failed_verification");
+
+        let verifier = InstallVerifier::new()
+            .with_search_path("/usr/bin:/bin")
+            .with_path_updates(&[bin.display().to_string()]);
+        let report = verifier.verify(&step).await;
+        assert_eq!(report.job_status(), "completed");
+        assert_eq!(report.detected_version.as_deref(), Some("2.5.1"));
+        assert_eq!(report.resolved_path, Some(tool.display().to_string()));
+    }
+}
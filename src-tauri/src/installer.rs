@@ -1,18 +1,61 @@
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
-use tauri::command;
-use tokio::sync::mpsc;
+use tauri::{command, State};
+use tokio::sync::{mpsc, Semaphore};
+
+use crate::command_runner::{CommandOutput, CommandSpec};
+use crate::database::Database;
+use crate::download_cache::DownloadCache;
+use crate::integrity::{hash_file, IntegrityError};
+use crate::install_journal::{existing_files, InstallJournal, JournalEntry, RollbackReport};
+use crate::install_verifier::{InstallVerifier, VerificationReport};
+use crate::package_command::PackageInstall;
+use crate::privilege_broker::PrivilegeBroker;
+use crate::install_planner::{dedup, InstallPlan, InstallPlanner, InstallPreview, PlanStep};
+use crate::manifest::{ManifestRegistry, Platform};
+use crate::scanner::SystemScanner;
+use crate::shell_profile::ShellProfiles;
+use crate::version_manager::{manager_search_path, VersionManager};
+use crate::workspace_manager::{InstalledTool, WorkspaceManager};
+
+// Tools with a hardcoded installer, as (tool_type, name, binary)
+const BUILTIN_TOOLS: &[(&str, &str, &str)] = &[
+    ("language", "Python", "python3"),
+    ("language", "Node.js", "node"),
+    ("language", "Rust", "rustc"),
+    ("language", "Go", "go"),
+    ("language", "Ruby", "ruby"),
+    ("language", "Java", "java"),
+    ("language", "Deno", "deno"),
+    ("language", "Bun", "bun"),
+    ("language", "Elixir", "elixir"),
+    ("database", "PostgreSQL", "psql"),
+    ("database", "MySQL", "mysql"),
+    ("database", "Redis", "redis-server"),
+    ("ide", "Visual Studio Code", "code"),
+    ("cli", "Git", "git"),
+    ("cli", "Docker", "docker"),
+    ("package", "Homebrew", "brew"),
+];
+
+const DEFAULT_MAX_PARALLEL_INSTALLS: usize = 3;
+
+const ROLLBACK_POLICIES: &[&str] = &["automatic", "confirm", "never"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstallationJob {
     pub id: String,
     pub workspace_id: String,
+    pub batch_id: Option<String>,
+    pub operation: String,
     pub tool: ToolInstallRequest,
     pub status: String,
     pub progress: u8,
     pub log: Vec<String>,
+    pub journal: InstallJournal,
+    pub rollback_status: Option<String>,
+    pub verification: Option<VerificationReport>,
     pub started_at: Option<chrono::DateTime<chrono::Utc>>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub error: Option<String>,
@@ -25,6 +68,89 @@ pub struct ToolInstallRequest {
     pub version: Option<String>,
     pub required: bool,
     pub alternatives: Vec<String>,
+    // Set by the user to install this tool from a download its manifest doesn't pin
+    #[serde(default)]
+    pub allow_unverified: bool,
+}
+
+// A single step of an installation. Manifest commands are shell strings and run through the platform shell.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstallCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub shell: bool,
+    pub optional: bool,
+}
+
+impl InstallCommand {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            shell: false,
+            optional: false,
+        }
+    }
+
+    pub fn shell(command_line: &str) -> Self {
+        let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        Self {
+            program: program.to_string(),
+            args: vec![flag.to_string(), command_line.to_string()],
+            shell: true,
+            optional: false,
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn display(&self) -> String {
+        if self.shell {
+            return self.args.last().cloned().unwrap_or_default();
+        }
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(|a| a.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn needs_sudo(&self) -> bool {
+        self.program == "sudo" || (self.shell && self.display().split_whitespace().any(|token| token == "sudo"))
+    }
+
+    // Points the command at a different file, such as the private copy of a downloaded installer
+    pub fn replace_path(&self, from: &str, to: &str) -> Self {
+        Self {
+            program: self.program.replace(from, to),
+            args: self.args.iter().map(|arg| arg.replace(from, to)).collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn to_spec(&self) -> CommandSpec {
+        if self.shell {
+            return CommandSpec::shell(&self.display());
+        }
+        CommandSpec::from_argv(std::iter::once(self.program.clone()).chain(self.args.iter().cloned()).collect())
+    }
+}
+
+impl From<CommandSpec> for InstallCommand {
+    fn from(spec: CommandSpec) -> Self {
+        if spec.shell {
+            return Self::shell(&spec.display());
+        }
+        let (program, args) = spec.argv.split_first().map(|(p, a)| (p.clone(), a.to_vec())).unwrap_or_default();
+        Self {
+            program,
+            args,
+            shell: false,
+            optional: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,315 +161,872 @@ pub struct InstallationProgress {
     pub status: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallationBatch {
+    pub id: String,
+    pub workspace_id: String,
+    pub plan: InstallPlan,
+    pub job_ids: HashMap<String, String>,
+    pub status: String,
+    pub max_parallel: usize,
+    pub rollback_policy: String,
+    pub rollback_status: Option<String>,
+    pub rollback_report: Option<RollbackReport>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Clone)]
 pub struct UniversalInstaller {
     jobs: Arc<Mutex<HashMap<String, InstallationJob>>>,
+    batches: Arc<Mutex<HashMap<String, InstallationBatch>>>,
+    registry: Arc<ManifestRegistry>,
+    database: Option<Arc<Mutex<Database>>>,
+    workspaces: Option<Arc<Mutex<WorkspaceManager>>>,
+    broker: Arc<PrivilegeBroker>,
+    downloads: Arc<DownloadCache>,
+    profiles: Arc<ShellProfiles>,
 }
 
 impl UniversalInstaller {
     pub fn new() -> Self {
         Self {
             jobs: Arc::new(Mutex::new(HashMap::new())),
+            batches: Arc::new(Mutex::new(HashMap::new())),
+            registry: Arc::new(ManifestRegistry::bundled()),
+            database: None,
+            workspaces: None,
+            broker: Arc::new(PrivilegeBroker::detect()),
+            downloads: Arc::new(DownloadCache::new(DownloadCache::default_root())),
+            profiles: Arc::new(ShellProfiles::detect()),
         }
     }
 
-    pub fn install_tool(&self, workspace_id: String, tool: ToolInstallRequest) -> Result<String, String> {
+    pub fn with_database(mut self, database: Arc<Mutex<Database>>) -> Self {
+        self.broker = Arc::new(PrivilegeBroker::new(self.broker.method().clone()).with_database(Arc::clone(&database)));
+        self.database = Some(database);
+        self
+    }
+
+    pub fn with_broker(mut self, broker: PrivilegeBroker) -> Self {
+        self.broker = Arc::new(broker);
+        self
+    }
+
+    pub fn broker(&self) -> Arc<PrivilegeBroker> {
+        Arc::clone(&self.broker)
+    }
+
+    pub fn with_download_cache(mut self, cache: DownloadCache) -> Self {
+        self.downloads = Arc::new(cache);
+        self
+    }
+
+    pub fn download_cache(&self) -> Arc<DownloadCache> {
+        Arc::clone(&self.downloads)
+    }
+
+    pub fn with_shell_profiles(mut self, profiles: ShellProfiles) -> Self {
+        self.profiles = Arc::new(profiles);
+        self
+    }
+
+    pub fn with_workspaces(mut self, workspaces: Arc<Mutex<WorkspaceManager>>) -> Self {
+        self.workspaces = Some(workspaces);
+        self
+    }
+
+    pub fn registry(&self) -> &ManifestRegistry {
+        &self.registry
+    }
+
+    pub fn builtin_tool(name: &str) -> Option<(&'static str, &'static str)> {
+        BUILTIN_TOOLS
+            .iter()
+            .find(|(_, builtin, _)| builtin.eq_ignore_ascii_case(name))
+            .map(|(tool_type, builtin, _)| (*tool_type, *builtin))
+    }
+
+    pub fn builtin_binary(name: &str) -> Option<&'static str> {
+        BUILTIN_TOOLS
+            .iter()
+            .find(|(_, builtin, _)| builtin.eq_ignore_ascii_case(name))
+            .map(|(_, _, binary)| *binary)
+    }
+
+    fn queue_job(&self, workspace_id: String, batch_id: Option<String>, operation: &str, tool: ToolInstallRequest) -> String {
         let job_id = uuid::Uuid::new_v4().to_string();
         
         let job = InstallationJob {
             id: job_id.clone(),
             workspace_id,
-            tool: tool.clone(),
+            batch_id,
+            operation: operation.to_string(),
+            tool,
             status: "queued".to_string(),
             progress: 0,
             log: Vec::new(),
+            journal: InstallJournal::new(),
+            rollback_status: None,
+            verification: None,
             started_at: None,
             completed_at: None,
             error: None,
         };
 
-        {
-            let mut jobs = self.jobs.lock().unwrap();
-            jobs.insert(job_id.clone(), job);
+        self.persist_job(&job);
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.insert(job_id.clone(), job);
+        job_id
+    }
+
+    // The database is optional so the installer keeps working if it could not be opened
+    fn persist_job(&self, job: &InstallationJob) {
+        if let Some(database) = &self.database {
+            if let Err(e) = database.lock().unwrap().save_installation_job(job) {
+                tracing::warn!("Failed to persist installation job {}: {}", job.id, e);
+            }
+        }
+    }
+
+    fn persist_job_by_id(&self, job_id: &str) {
+        if let Some(job) = self.get_job(job_id) {
+            self.persist_job(&job);
         }
+    }
 
-        // Start installation in background
-        let jobs_clone = Arc::clone(&self.jobs);
+    // Runs a one-off script, such as a dotfiles install.sh, as a job so its output is kept like an install's
+    pub fn run_script(&self, workspace_id: String, name: &str, command: InstallCommand) -> String {
+        let tool = ToolInstallRequest {
+            name: name.to_string(),
+            tool_type: "script".to_string(),
+            version: None,
+            required: false,
+            alternatives: Vec::new(),
+            allow_unverified: false,
+        };
+        let job_id = self.queue_job(workspace_id, None, "script", tool);
+
+        let installer = self.clone();
         let job_id_clone = job_id.clone();
-        
         tokio::spawn(async move {
-            Self::execute_installation(jobs_clone, job_id_clone, tool).await;
+            installer.execute_script(job_id_clone, command).await;
         });
 
-        Ok(job_id)
+        job_id
     }
 
-    async fn execute_installation(
-        jobs: Arc<Mutex<HashMap<String, InstallationJob>>>,
-        job_id: String,
-        tool: ToolInstallRequest,
-    ) {
-        // Update job status to installing
+    async fn execute_script(&self, job_id: String, command: InstallCommand) {
         {
-            let mut jobs_guard = jobs.lock().unwrap();
+            let mut jobs_guard = self.jobs.lock().unwrap();
             if let Some(job) = jobs_guard.get_mut(&job_id) {
                 job.status = "installing".to_string();
                 job.started_at = Some(chrono::Utc::now());
                 job.progress = 10;
-                job.log.push("Starting installation...".to_string());
+                job.log.push(format!("$ {}", command.display()));
             }
         }
+        self.persist_job_by_id(&job_id);
 
-        let result = match tool.tool_type.as_str() {
-            "language" => Self::install_language(&tool).await,
-            "database" => Self::install_database(&tool).await,
-            "ide" => Self::install_ide(&tool).await,
-            "cli" => Self::install_cli_tool(&tool).await,
-            "package" => Self::install_package_manager(&tool).await,
-            _ => Err("Unknown tool type".to_string()),
-        };
-
-        // Update job with result
+        let outcome = self.run_step_command(&job_id, "script", &command).await;
         {
-            let mut jobs_guard = jobs.lock().unwrap();
+            let mut jobs_guard = self.jobs.lock().unwrap();
             if let Some(job) = jobs_guard.get_mut(&job_id) {
-                match result {
-                    Ok(log_messages) => {
+                match outcome {
+                    Ok(output) => {
+                        job.log.extend(output);
                         job.status = "completed".to_string();
                         job.progress = 100;
-                        job.log.extend(log_messages);
-                        job.completed_at = Some(chrono::Utc::now());
                     }
                     Err(error) => {
                         job.status = "failed".to_string();
-                        job.error = Some(error.clone());
                         job.log.push(format!("Error: {}", error));
-                        job.completed_at = Some(chrono::Utc::now());
+                        job.error = Some(error);
                     }
                 }
+                job.completed_at = Some(chrono::Utc::now());
             }
         }
+        self.persist_job_by_id(&job_id);
     }
 
-    async fn install_language(tool: &ToolInstallRequest) -> Result<Vec<String>, String> {
-        let mut log = Vec::new();
+    pub fn install_tool(&self, workspace_id: String, tool: ToolInstallRequest) -> Result<String, String> {
+        let step = InstallPlanner::new(&self.registry, Platform::current())
+            .resolve_step(&tool)
+            .ok_or_else(|| format!("No installer available for {} on this platform", tool.name))?;
+        let job_id = self.queue_job(workspace_id, None, "install", tool);
+
+        // Start installation in background
+        let installer = self.clone();
+        let job_id_clone = job_id.clone();
         
-        match tool.name.as_str() {
-            "Python" => {
-                log.push("Installing Python...".to_string());
-                
-                // Use pyenv for Python installation
-                let output = Command::new("pyenv")
-                    .args(&["install", tool.version.as_deref().unwrap_or("3.11.0")])
-                    .output()
-                    .map_err(|e| format!("Failed to run pyenv: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Python installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Python installation failed: {}", error));
+        tokio::spawn(async move {
+            installer.execute_installation(job_id_clone, step).await;
+        });
+
+        Ok(job_id)
+    }
+
+    pub fn uninstall_tool(&self, workspace_id: String, tool: ToolInstallRequest, force: bool) -> Result<String, String> {
+        let step = InstallPlanner::new(&self.registry, Platform::current())
+            .resolve_uninstall(&tool)
+            .ok_or_else(|| format!("No uninstaller available for {} on this platform", tool.name))?;
+        self.start_operation(workspace_id, "uninstall", step, force)
+    }
+
+    pub fn upgrade_tool(&self, workspace_id: String, tool: ToolInstallRequest, force: bool) -> Result<String, String> {
+        let step = InstallPlanner::new(&self.registry, Platform::current())
+            .resolve_upgrade(&tool)
+            .ok_or_else(|| format!("No installer available for {} on this platform", tool.name))?;
+        self.start_operation(workspace_id, "upgrade", step, force)
+    }
+
+    // Re-runs the post-install checks for a tool, e.g. for a workspace health check
+    pub async fn verify_tool(&self, workspace_id: &str, tool: ToolInstallRequest) -> Result<VerificationReport, String> {
+        let step = InstallPlanner::new(&self.registry, Platform::current())
+            .resolve_step(&tool)
+            .ok_or_else(|| format!("No installer available for {} on this platform", tool.name))?;
+        if step.verify_commands.is_empty() {
+            return Err(format!("No verification checks are known for {}", tool.name));
+        }
+        Ok(self.verifier_for(workspace_id, &step).verify(&step).await)
+    }
+
+    fn start_operation(&self, workspace_id: String, operation: &str, step: PlanStep, force: bool) -> Result<String, String> {
+        // Other workspaces relying on the tool have to be acknowledged before it changes under them
+        if let Some(workspaces) = &self.workspaces {
+            let names = self.tool_aliases(&step);
+            let dependents = workspaces.lock().unwrap().tool_dependents(&names, Some(&workspace_id));
+            if !dependents.is_empty() && !force {
+                return Err(format!(
+                    "Cannot {} {} while other workspaces depend on it: {}",
+                    operation,
+                    step.tool.name,
+                    dependents.join("; ")
+                ));
+            }
+        }
+
+        let job_id = self.queue_job(workspace_id, None, operation, step.tool.clone());
+        let installer = self.clone();
+        let job_id_clone = job_id.clone();
+
+        tokio::spawn(async move {
+            installer.execute_installation(job_id_clone, step).await;
+        });
+
+        Ok(job_id)
+    }
+
+    fn tool_aliases(&self, step: &PlanStep) -> Vec<String> {
+        let mut names = vec![step.tool_id.clone(), step.tool.name.clone()];
+        if let Some(manifest) = self.registry.get(&step.tool_id) {
+            names.push(manifest.id.clone());
+            names.push(manifest.name.clone());
+        }
+        dedup(&mut names);
+        names
+    }
+
+    // Checks the outcome of a finished operation and keeps the workspaces' tool lists in sync
+    async fn finish_operation(&self, job_id: &str, step: &PlanStep) -> Result<Vec<String>, String> {
+        let (operation, workspace_id) = match self.get_job(job_id) {
+            Some(job) => (job.operation, job.workspace_id),
+            None => return Ok(Vec::new()),
+        };
+        let names = self.tool_aliases(step);
+        let mut log = Vec::new();
+
+        if operation == "uninstall" {
+            for verify_command in &step.verify_commands {
+                if Self::run_install_command(verify_command).await.is_ok() {
+                    return Err(format!("{} is still available after uninstall (`{}` succeeded)", step.tool.name, verify_command.display()));
                 }
             }
-            "Node.js" => {
-                log.push("Installing Node.js...".to_string());
-                
-                // Use nvm for Node.js installation
-                let output = Command::new("nvm")
-                    .args(&["install", tool.version.as_deref().unwrap_or("18")])
-                    .output()
-                    .map_err(|e| format!("Failed to run nvm: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Node.js installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Node.js installation failed: {}", error));
+            for profile in self.profiles.remove_owner(&profile_owner(step))? {
+                log.push(format!("Removed {} entries from {}", step.tool.name, profile.path.display()));
+            }
+        }
+
+        let mut report = None;
+        if operation != "uninstall" && !step.verify_commands.is_empty() {
+            let verification = self.verifier_for(&workspace_id, step).verify(step).await;
+            for check in &verification.checks {
+                log.push(format!("Verified `{}`: {}", check.command, if check.success { "ok" } else { "failed" }));
+            }
+            log.extend(verification.warnings.iter().map(|warning| format!("Warning: {}", warning)));
+            {
+                let mut jobs_guard = self.jobs.lock().unwrap();
+                if let Some(job) = jobs_guard.get_mut(job_id) {
+                    job.verification = Some(verification.clone());
                 }
             }
-            "Rust" => {
-                log.push("Installing Rust...".to_string());
-                
-                // Use rustup for Rust installation
-                let output = Command::new("rustup")
-                    .args(&["toolchain", "install", tool.version.as_deref().unwrap_or("stable")])
-                    .output()
-                    .map_err(|e| format!("Failed to run rustup: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Rust installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Rust installation failed: {}", error));
+            if !verification.passed() {
+                return Err(format!("Verification failed: {}", verification.failures.join("; ")));
+            }
+            report = Some(verification);
+        }
+        let version = report.as_ref().and_then(|r| r.detected_version.clone());
+        let path = report.and_then(|r| r.resolved_path).unwrap_or_default();
+
+        let workspaces = match &self.workspaces {
+            Some(workspaces) => workspaces,
+            None => return Ok(log),
+        };
+        let mut workspaces = workspaces.lock().unwrap();
+
+        match operation.as_str() {
+            "uninstall" => {
+                let affected = workspaces.remove_tool(&names);
+                log.push(format!("Removed {} from {} workspace(s)", step.tool.name, affected.len()));
+            }
+            "upgrade" => {
+                if let Some(version) = &version {
+                    let affected = workspaces.update_tool_version(&names, version);
+                    log.push(format!("Updated {} to {} in {} workspace(s)", step.tool.name, version, affected.len()));
                 }
             }
             _ => {
-                return Err(format!("Unsupported language: {}", tool.name));
+                let tool = InstalledTool {
+                    name: step.tool.name.clone(),
+                    tool_type: step.tool.tool_type.clone(),
+                    version: version.or_else(|| step.tool.version.clone()).unwrap_or_default(),
+                    path,
+                    size: 0,
+                    status: "installed".to_string(),
+                    dependencies: step.depends_on.clone(),
+                    conflicts: Vec::new(),
+                };
+                if workspaces.record_tool(&workspace_id, tool).is_err() {
+                    log.push(format!("Workspace {} not found, tool not recorded", workspace_id));
+                }
             }
         }
 
         Ok(log)
     }
 
-    async fn install_database(tool: &ToolInstallRequest) -> Result<Vec<String>, String> {
-        let mut log = Vec::new();
-        
-        match tool.name.as_str() {
-            "PostgreSQL" => {
-                log.push("Installing PostgreSQL...".to_string());
-                
-                // Use Homebrew on macOS
-                let output = Command::new("brew")
-                    .args(&["install", "postgresql"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
-
-                if output.status.success() {
-                    log.push("PostgreSQL installed successfully".to_string());
-                    log.push("Starting PostgreSQL service...".to_string());
-                    
-                    // Start the service
-                    let _ = Command::new("brew")
-                        .args(&["services", "start", "postgresql"])
-                        .output();
-                        
-                    log.push("PostgreSQL service started".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("PostgreSQL installation failed: {}", error));
+    // Verification resolves binaries on the workspace's PATH override when it sets one
+    fn verifier_for(&self, workspace_id: &str, step: &PlanStep) -> InstallVerifier {
+        let workspace_path = self
+            .workspaces
+            .as_ref()
+            .and_then(|workspaces| workspaces.lock().unwrap().get_workspace(workspace_id))
+            .and_then(|workspace| workspace.config.environment_variables.get("PATH").cloned());
+
+        match workspace_path {
+            Some(path) => InstallVerifier::new().with_search_path(path),
+            None => InstallVerifier::new(),
+        }
+        .with_path_updates(&step.path_updates)
+    }
+
+    async fn execute_installation(&self, job_id: String, step: PlanStep) {
+        // Update job status to installing
+        {
+            let mut jobs_guard = self.jobs.lock().unwrap();
+            if let Some(job) = jobs_guard.get_mut(&job_id) {
+                job.status = "installing".to_string();
+                job.started_at = Some(chrono::Utc::now());
+                job.progress = 10;
+                job.log.push("Starting installation...".to_string());
+            }
+        }
+        self.persist_job_by_id(&job_id);
+
+        let files_before = existing_files(&step.files_touched);
+        let mut journal = InstallJournal::new();
+
+        // Direct downloads come from the cache, which verifies them and works offline once populated.
+        // The installer is staged in a private directory so nobody can swap it before it runs.
+        let mut result = Ok(());
+        let mut staged: Option<(tempfile::TempDir, std::path::PathBuf, String)> = None;
+        if let Some(download) = &step.download {
+            let outcome = match tempfile::Builder::new().prefix("nuffi-install-").tempdir() {
+                Ok(dir) => {
+                    let file_name = std::path::Path::new(&download.destination).file_name().unwrap_or_default();
+                    let path = dir.path().join(file_name);
+                    let outcome = self
+                        .downloads
+                        .materialize(
+                            &download.url,
+                            download.checksum.as_deref(),
+                            download.signature.as_ref(),
+                            step.tool.allow_unverified,
+                            &path,
+                        )
+                        .await;
+                    if let Ok(fetched) = &outcome {
+                        staged = Some((dir, path, fetched.artifact.digest.clone()));
+                    }
+                    outcome
+                }
+                Err(e) => Err(IntegrityError::Io(format!("Failed to create a directory for {}: {}", download.url, e))),
+            };
+            let mut jobs_guard = self.jobs.lock().unwrap();
+            if let Some(job) = jobs_guard.get_mut(&job_id) {
+                match &outcome {
+                    Ok(fetched) => {
+                        let checks = match (fetched.checksum_verified, fetched.signature_verified) {
+                            (true, true) => "checksum and signature verified",
+                            (true, false) => "checksum verified",
+                            (false, true) => "signature verified",
+                            (false, false) => "unverified",
+                        };
+                        job.log.push(format!("Using {} (sha256:{}, {})", download.url, fetched.artifact.digest, checks));
+                        job.log.extend(fetched.warning.iter().map(|warning| format!("Warning: {}", warning)));
+                    }
+                    Err(error) => job.log.push(format!("Refusing download: {}", error)),
                 }
             }
-            "MySQL" => {
-                log.push("Installing MySQL...".to_string());
-                
-                let output = Command::new("brew")
-                    .args(&["install", "mysql"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
+            result = outcome.map(|_| ()).map_err(|e| e.to_string());
+        }
 
-                if output.status.success() {
-                    log.push("MySQL installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("MySQL installation failed: {}", error));
+        let commands: Vec<InstallCommand> = match (&result, &step.download, &staged) {
+            (Err(_), _, _) => Vec::new(),
+            (Ok(()), Some(download), Some((_, path, _))) => step
+                .commands
+                .iter()
+                .map(|command| command.replace_path(&download.destination, &path.display().to_string()))
+                .collect(),
+            (Ok(()), _, _) => step.commands.clone(),
+        };
+        let total = commands.len().max(1);
+        for (index, install_command) in commands.iter().enumerate() {
+            // The staged installer is hashed again right before anything runs it
+            if let Some((_, path, digest)) = &staged {
+                if install_command.display().contains(&path.display().to_string()) {
+                    if let Err(error) = check_staged(path, digest) {
+                        result = Err(error);
+                        break;
+                    }
                 }
             }
-            "Redis" => {
-                log.push("Installing Redis...".to_string());
-                
-                let output = Command::new("brew")
-                    .args(&["install", "redis"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
 
-                if output.status.success() {
-                    log.push("Redis installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Redis installation failed: {}", error));
+            // Only packages that were missing beforehand belong to this install
+            let package_install = PackageInstall::parse(install_command);
+            let mut preexisting = Vec::new();
+            if let Some(package_install) = &package_install {
+                for package in &package_install.packages {
+                    if let Some(probe) = package_install.probe(package) {
+                        if Self::run_install_command(&probe).await.is_ok() {
+                            preexisting.push(package.clone());
+                        }
+                    }
                 }
             }
-            _ => {
-                return Err(format!("Unsupported database: {}", tool.name));
+
+            let outcome = self.run_step_command(&job_id, &step.tool.name, install_command).await;
+
+            if let (Ok(_), Some(package_install)) = (&outcome, &package_install) {
+                let installed: Vec<String> = package_install
+                    .packages
+                    .iter()
+                    .filter(|p| !preexisting.contains(p))
+                    .cloned()
+                    .collect();
+                if !installed.is_empty() {
+                    journal.record(JournalEntry::PackageInstalled {
+                        manager: package_install.manager.clone(),
+                        undo: package_install.undo(&installed),
+                        packages: installed,
+                    });
+                }
             }
-        }
 
-        Ok(log)
-    }
+            let mut jobs_guard = self.jobs.lock().unwrap();
+            let job = match jobs_guard.get_mut(&job_id) {
+                Some(job) => job,
+                None => return,
+            };
+            job.log.push(format!("$ {}", install_command.display()));
 
-    async fn install_ide(tool: &ToolInstallRequest) -> Result<Vec<String>, String> {
-        let mut log = Vec::new();
-        
-        match tool.name.as_str() {
-            "Visual Studio Code" => {
-                log.push("Installing Visual Studio Code...".to_string());
-                
-                let output = Command::new("brew")
-                    .args(&["install", "--cask", "visual-studio-code"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Visual Studio Code installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("VS Code installation failed: {}", error));
+            match outcome {
+                Ok(output) => job.log.extend(output),
+                Err(error) if install_command.optional => {
+                    job.log.push(format!("Ignoring failed optional step: {}", error));
+                }
+                Err(error) => {
+                    result = Err(error);
+                    break;
                 }
             }
-            _ => {
-                return Err(format!("Unsupported IDE: {}", tool.name));
+            job.progress = (10 + 90 * (index + 1) / total) as u8;
+        }
+
+        // Manifest PATH updates go into the shell profiles' NUFFI block, owned by this tool
+        let operation = self.get_job(&job_id).map(|job| job.operation).unwrap_or_default();
+        if result.is_ok() && operation != "uninstall" {
+            let owner = profile_owner(&step);
+            match self.profiles.add(&owner, &ShellProfiles::path_settings(&step.path_updates)) {
+                Ok(changed) => {
+                    let mut jobs_guard = self.jobs.lock().unwrap();
+                    for profile in changed {
+                        if let Some(job) = jobs_guard.get_mut(&job_id) {
+                            job.log.push(format!("Added PATH entries to {}", profile.path.display()));
+                        }
+                        journal.record(JournalEntry::ProfileUpdated {
+                            shell: profile.shell,
+                            profile: profile.path.display().to_string(),
+                            owner: owner.clone(),
+                        });
+                    }
+                }
+                Err(error) => result = Err(error),
             }
         }
 
-        Ok(log)
+        // Files are journaled even on failure so a rollback can clean up partial work
+        journal.record_created_files(&step.files_touched, &files_before);
+
+        let result = match result {
+            Ok(()) => self.finish_operation(&job_id, &step).await,
+            Err(error) => Err(error),
+        };
+
+        // Update job with result
+        {
+            let mut jobs_guard = self.jobs.lock().unwrap();
+            if let Some(job) = jobs_guard.get_mut(&job_id) {
+                job.journal = journal;
+                match result {
+                    Ok(log_messages) => {
+                        job.status = job.verification.as_ref().map(|v| v.job_status()).unwrap_or("completed").to_string();
+                        job.progress = 100;
+                        job.log.extend(log_messages);
+                        if job.status == "completed" {
+                            job.log.push(format!("{} {} finished successfully", job.tool.name, job.operation));
+                        } else {
+                            job.log.push(format!("{} {} finished with warnings", job.tool.name, job.operation));
+                        }
+                        job.completed_at = Some(chrono::Utc::now());
+                    }
+                    Err(error) => {
+                        let verification_failed = job.verification.as_ref().map(|v| !v.passed()).unwrap_or(false);
+                        job.status = if verification_failed { "failed_verification" } else { "failed" }.to_string();
+                        job.error = Some(error.clone());
+                        job.log.push(format!("Error: {}", error));
+                        job.completed_at = Some(chrono::Utc::now());
+                    }
+                }
+            }
+        }
+        self.persist_job_by_id(&job_id);
     }
 
-    async fn install_cli_tool(tool: &ToolInstallRequest) -> Result<Vec<String>, String> {
-        let mut log = Vec::new();
-        
-        match tool.name.as_str() {
-            "Git" => {
-                log.push("Installing Git...".to_string());
-                
-                let output = Command::new("brew")
-                    .args(&["install", "git"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Git installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Git installation failed: {}", error));
+    // Version managers installed during this session aren't on the app's PATH yet
+    async fn run_install_command(install_command: &InstallCommand) -> Result<Vec<String>, String> {
+        let spec = install_command.to_spec().env("PATH", &manager_search_path().to_string_lossy());
+        let output = spec.run_async().await.map_err(|e| e.to_string())?;
+        Self::command_result(install_command, output)
+    }
+
+    // Commands that need root go through the broker; everything else runs as the app user
+    async fn run_step_command(&self, job_id: &str, tool: &str, install_command: &InstallCommand) -> Result<Vec<String>, String> {
+        if !install_command.needs_sudo() {
+            return Self::run_install_command(install_command).await;
+        }
+
+        let broker = Arc::clone(&self.broker);
+        let spec = install_command.to_spec();
+        let (job_id, tool) = (job_id.to_string(), tool.to_string());
+        let output = tokio::task::spawn_blocking(move || broker.run_elevated(&spec, Some(&job_id), &tool))
+            .await
+            .map_err(|e| format!("Installation task failed: {}", e))??;
+        Self::command_result(install_command, output)
+    }
+
+    fn command_result(install_command: &InstallCommand, output: CommandOutput) -> Result<Vec<String>, String> {
+        if output.success {
+            Ok(output.stdout_lines())
+        } else {
+            Err(format!("`{}` failed: {}", install_command.display(), output.stderr.trim()))
+        }
+    }
+
+    // Commands for the tools we know how to install without a manifest
+    pub fn builtin_commands(tool: &ToolInstallRequest) -> Result<Vec<InstallCommand>, String> {
+        let version = tool.version.as_deref();
+
+        let commands = match (tool.tool_type.as_str(), tool.name.as_str()) {
+            // Languages go through their version manager, which is installed first when missing
+            ("language", name) => match VersionManager::for_language(name) {
+                Some(manager) => manager.install_commands(version)?,
+                None => return Err(format!("Unsupported language: {}", tool.name)),
+            },
+            ("database", "PostgreSQL") => vec![
+                InstallCommand::new("brew", &["install", "postgresql"]),
+                InstallCommand::new("brew", &["services", "start", "postgresql"]).optional(),
+            ],
+            ("database", "MySQL") => vec![InstallCommand::new("brew", &["install", "mysql"])],
+            ("database", "Redis") => vec![InstallCommand::new("brew", &["install", "redis"])],
+            ("database", _) => return Err(format!("Unsupported database: {}", tool.name)),
+            ("ide", "Visual Studio Code") => vec![InstallCommand::new("brew", &["install", "--cask", "visual-studio-code"])],
+            ("ide", _) => return Err(format!("Unsupported IDE: {}", tool.name)),
+            ("cli", "Git") => vec![InstallCommand::new("brew", &["install", "git"])],
+            ("cli", "Docker") => vec![InstallCommand::new("brew", &["install", "--cask", "docker"])],
+            ("cli", _) => return Err(format!("Unsupported CLI tool: {}", tool.name)),
+            // The install script comes from the Homebrew manifest, through the download cache
+            ("package", "Homebrew") => return Err("Homebrew is not available on this platform".to_string()),
+            ("package", _) => return Err(format!("Unsupported package manager: {}", tool.name)),
+            _ => return Err("Unknown tool type".to_string()),
+        };
+
+        Ok(commands)
+    }
+
+    pub fn plan(
+        &self,
+        tools: Vec<ToolInstallRequest>,
+        template_id: Option<&str>,
+        installed: Vec<InstalledTool>,
+    ) -> Result<InstallPlan, String> {
+        let planner = InstallPlanner::new(&self.registry, Platform::current()).with_installed(installed);
+
+        let mut plan = match template_id {
+            Some(template_id) => planner.plan_template(template_id)?,
+            None => planner.plan(Vec::new())?,
+        };
+
+        if !tools.is_empty() {
+            let mut requests: Vec<ToolInstallRequest> = plan.steps.iter().map(|s| s.tool.clone()).collect();
+            requests.extend(tools);
+            let skipped = plan.skipped;
+            plan = planner.plan(requests)?;
+            plan.skipped.extend(skipped);
+        }
+
+        Ok(plan)
+    }
+
+    // Dry run: resolve everything a batch install would do without executing it
+    pub fn preview(
+        &self,
+        tools: Vec<ToolInstallRequest>,
+        template_id: Option<&str>,
+        installed: Vec<InstalledTool>,
+    ) -> Result<InstallPreview, String> {
+        Ok(self.plan(tools, template_id, installed)?.preview())
+    }
+
+    pub fn install_plan(
+        &self,
+        workspace_id: String,
+        plan: InstallPlan,
+        max_parallel: Option<usize>,
+        rollback_policy: Option<String>,
+        force: bool,
+    ) -> Result<String, String> {
+        if plan.is_empty() {
+            return Err("Installation plan has no steps".to_string());
+        }
+
+        // An incomplete plan only runs once the caller has acknowledged what it leaves out
+        if !force && (!plan.unresolved.is_empty() || !plan.conflicts.is_empty()) {
+            let mut problems: Vec<String> = plan.unresolved.iter().map(|tool| format!("unresolved {}", tool)).collect();
+            problems.extend(plan.conflicts.iter().cloned());
+            return Err(format!("Installation plan is incomplete: {}", problems.join("; ")));
+        }
+
+        let rollback_policy = rollback_policy.unwrap_or_else(|| "confirm".to_string());
+        if !ROLLBACK_POLICIES.contains(&rollback_policy.as_str()) {
+            return Err(format!("Unknown rollback policy: {}", rollback_policy));
+        }
+
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        let max_parallel = max_parallel.unwrap_or(DEFAULT_MAX_PARALLEL_INSTALLS).max(1);
+
+        // Queue every job up front so the whole batch is visible immediately
+        let job_ids: HashMap<String, String> = plan
+            .steps
+            .iter()
+            .map(|step| {
+                let job_id = self.queue_job(workspace_id.clone(), Some(batch_id.clone()), "install", step.tool.clone());
+                (step.tool_id.clone(), job_id)
+            })
+            .collect();
+
+        let batch = InstallationBatch {
+            id: batch_id.clone(),
+            workspace_id,
+            plan,
+            job_ids,
+            status: "installing".to_string(),
+            max_parallel,
+            rollback_policy,
+            rollback_status: None,
+            rollback_report: None,
+            started_at: chrono::Utc::now(),
+            completed_at: None,
+        };
+
+        {
+            let mut batches = self.batches.lock().unwrap();
+            batches.insert(batch_id.clone(), batch.clone());
+        }
+
+        let installer = self.clone();
+        tokio::spawn(async move {
+            installer.execute_batch(batch).await;
+        });
+
+        Ok(batch_id)
+    }
+
+    async fn execute_batch(&self, batch: InstallationBatch) {
+        // Administrator rights are requested once, before the first step, rather than midway through
+        if !batch.plan.elevated_steps().is_empty() {
+            let broker = Arc::clone(&self.broker);
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(move || broker.authorize()).await {
+                tracing::warn!("Batch {} continues without administrator privileges: {}", batch.id, e);
+            }
+        }
+
+        let semaphore = Arc::new(Semaphore::new(batch.max_parallel));
+        let mut failed: Vec<String> = Vec::new();
+
+        for stage in &batch.plan.stages {
+            let mut handles = Vec::new();
+
+            for tool_id in stage {
+                let (step, job_id) = match (batch.plan.step(tool_id), batch.job_ids.get(tool_id)) {
+                    (Some(step), Some(job_id)) => (step.clone(), job_id.clone()),
+                    _ => continue,
+                };
+
+                // Never install on top of a dependency that did not make it
+                if let Some(dependency) = step.depends_on.iter().find(|dep| failed.contains(dep)) {
+                    self.finish_job(&job_id, "skipped", Some(format!("Dependency {} failed to install", dependency)));
+                    failed.push(step.tool_id.clone());
+                    continue;
                 }
+
+                let installer = self.clone();
+                let semaphore = Arc::clone(&semaphore);
+                handles.push(tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let tool_id = step.tool_id.clone();
+                    installer.execute_installation(job_id.clone(), step).await;
+                    let succeeded = installer
+                        .get_job(&job_id)
+                        .map(|job| job.status == "completed" || job.status == "completed_with_warnings")
+                        .unwrap_or(false);
+                    (tool_id, succeeded)
+                }));
             }
-            "Docker" => {
-                log.push("Installing Docker...".to_string());
-                
-                let output = Command::new("brew")
-                    .args(&["install", "--cask", "docker"])
-                    .output()
-                    .map_err(|e| format!("Failed to run brew: {}", e))?;
 
-                if output.status.success() {
-                    log.push("Docker installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Docker installation failed: {}", error));
+            for handle in handles {
+                match handle.await {
+                    Ok((tool_id, false)) => failed.push(tool_id),
+                    Ok((_, true)) => {}
+                    Err(e) => tracing::error!("Installation task panicked: {}", e),
                 }
             }
-            _ => {
-                return Err(format!("Unsupported CLI tool: {}", tool.name));
+        }
+
+        let required_failed = failed.iter().any(|tool_id| {
+            batch.plan.step(tool_id).map(|s| s.tool.required).unwrap_or(false)
+        });
+
+        {
+            let mut batches_guard = self.batches.lock().unwrap();
+            if let Some(stored) = batches_guard.get_mut(&batch.id) {
+                stored.status = if failed.is_empty() {
+                    "completed"
+                } else if required_failed {
+                    "failed"
+                } else {
+                    "completed_with_errors"
+                }
+                .to_string();
+                stored.completed_at = Some(chrono::Utc::now());
+
+                if !failed.is_empty() && stored.rollback_policy == "confirm" {
+                    stored.rollback_status = Some("awaiting_confirmation".to_string());
+                }
             }
         }
 
-        Ok(log)
+        if !failed.is_empty() && batch.rollback_policy == "automatic" {
+            if let Err(e) = self.rollback_batch(&batch.id).await {
+                tracing::error!("Automatic rollback of batch {} failed: {}", batch.id, e);
+            }
+        }
     }
 
-    async fn install_package_manager(tool: &ToolInstallRequest) -> Result<Vec<String>, String> {
-        let mut log = Vec::new();
-        
-        match tool.name.as_str() {
-            "Homebrew" => {
-                log.push("Installing Homebrew...".to_string());
-                
-                let output = Command::new("bash")
-                    .args(&["-c", r#"/bin/bash -c "$(curl -fsSL https://raw.githubusercontent.com/Homebrew/install/HEAD/install.sh)""#])
-                    .output()
-                    .map_err(|e| format!("Failed to install Homebrew: {}", e))?;
-
-                if output.status.success() {
-                    log.push("Homebrew installed successfully".to_string());
-                } else {
-                    let error = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Homebrew installation failed: {}", error));
+    // Undoes every job of a batch, newest stage first
+    pub async fn rollback_batch(&self, batch_id: &str) -> Result<RollbackReport, String> {
+        let batch = {
+            let mut batches = self.batches.lock().unwrap();
+            let batch = batches
+                .get_mut(batch_id)
+                .ok_or_else(|| format!("Installation batch not found: {}", batch_id))?;
+
+            if batch.completed_at.is_none() {
+                return Err("Cannot roll back a batch that is still installing".to_string());
+            }
+            if matches!(batch.rollback_status.as_deref(), Some("rolling_back") | Some("rolled_back")) {
+                return Err("Batch has already been rolled back".to_string());
+            }
+            batch.rollback_status = Some("rolling_back".to_string());
+            batch.clone()
+        };
+
+        let mut report = RollbackReport::default();
+        for step in batch.plan.steps.iter().rev() {
+            let job = match batch.job_ids.get(&step.tool_id).and_then(|id| self.get_job(id)) {
+                Some(job) => job,
+                None => continue,
+            };
+            if job.journal.is_empty() {
+                continue;
+            }
+
+            let journal = job.journal.clone();
+            let broker = Arc::clone(&self.broker);
+            let job_report = tokio::task::spawn_blocking(move || journal.rollback(&broker))
+                .await
+                .map_err(|e| format!("Rollback task failed: {}", e))?;
+
+            {
+                let mut jobs_guard = self.jobs.lock().unwrap();
+                if let Some(job) = jobs_guard.get_mut(&job.id) {
+                    job.rollback_status = Some(if job_report.succeeded() { "rolled_back" } else { "rollback_failed" }.to_string());
+                    job.log.extend(job_report.undone.iter().map(|u| format!("Rolled back {}", u)));
+                    job.log.extend(job_report.failed.iter().map(|f| format!("Rollback failed for {}", f)));
                 }
             }
-            _ => {
-                return Err(format!("Unsupported package manager: {}", tool.name));
+            self.persist_job_by_id(&job.id);
+            report.merge(job_report);
+        }
+
+        let mut batches = self.batches.lock().unwrap();
+        if let Some(stored) = batches.get_mut(batch_id) {
+            stored.rollback_status = Some(if report.succeeded() { "rolled_back" } else { "rollback_failed" }.to_string());
+            stored.rollback_report = Some(report.clone());
+        }
+
+        Ok(report)
+    }
+
+    fn finish_job(&self, job_id: &str, status: &str, error: Option<String>) {
+        {
+            let mut jobs_guard = self.jobs.lock().unwrap();
+            if let Some(job) = jobs_guard.get_mut(job_id) {
+                job.status = status.to_string();
+                if let Some(error) = &error {
+                    job.log.push(error.clone());
+                }
+                job.error = error;
+                job.completed_at = Some(chrono::Utc::now());
             }
         }
+        self.persist_job_by_id(job_id);
+    }
 
-        Ok(log)
+    pub fn get_batch(&self, batch_id: &str) -> Option<InstallationBatch> {
+        let batches = self.batches.lock().unwrap();
+        batches.get(batch_id).cloned()
     }
 
     pub fn get_job(&self, job_id: &str) -> Option<InstallationJob> {
@@ -357,21 +1040,396 @@ impl UniversalInstaller {
     }
 }
 
+impl From<crate::scanner::DetectedTool> for InstalledTool {
+    fn from(tool: crate::scanner::DetectedTool) -> Self {
+        Self {
+            name: tool.name,
+            tool_type: tool.tool_type,
+            version: tool.version,
+            path: tool.path,
+            size: tool.size,
+            status: tool.status,
+            dependencies: Vec::new(),
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+// Profile entries are tagged with the tool id, which must not contain whitespace or commas
+fn profile_owner(step: &PlanStep) -> String {
+    step.tool_id.replace(|c: char| c.is_whitespace() || c == ',', "-").to_lowercase()
+}
+
+fn check_staged(path: &std::path::Path, digest: &str) -> Result<(), String> {
+    match hash_file(path) {
+        Ok((actual, _)) if actual == digest => Ok(()),
+        Ok((actual, _)) => Err(format!("{} changed after it was verified (sha256:{})", path.display(), actual)),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn detect_installed_tools() -> Vec<InstalledTool> {
+    SystemScanner::new()
+        .scan_system()
+        .map(|scan| scan.detected_tools.into_iter().map(InstalledTool::from).collect())
+        .unwrap_or_default()
+}
+
 // Tauri commands
 #[command]
-pub async fn install_tool(workspace_id: String, tool: ToolInstallRequest) -> Result<String, String> {
-    let installer = UniversalInstaller::new();
+pub async fn install_tool(
+    installer: State<'_, UniversalInstaller>,
+    workspace_id: String,
+    tool: ToolInstallRequest,
+) -> Result<String, String> {
     installer.install_tool(workspace_id, tool)
 }
 
 #[command]
-pub async fn get_installation_job(job_id: String) -> Result<Option<InstallationJob>, String> {
-    let installer = UniversalInstaller::new();
+pub async fn uninstall_tool(
+    installer: State<'_, UniversalInstaller>,
+    workspace_id: String,
+    tool: ToolInstallRequest,
+    force: Option<bool>,
+) -> Result<String, String> {
+    installer.uninstall_tool(workspace_id, tool, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn upgrade_tool(
+    installer: State<'_, UniversalInstaller>,
+    workspace_id: String,
+    tool: ToolInstallRequest,
+    force: Option<bool>,
+) -> Result<String, String> {
+    installer.upgrade_tool(workspace_id, tool, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn verify_tool_installation(
+    installer: State<'_, UniversalInstaller>,
+    workspace_id: String,
+    tool: ToolInstallRequest,
+) -> Result<VerificationReport, String> {
+    installer.verify_tool(&workspace_id, tool).await
+}
+
+#[command]
+pub async fn get_installation_job(installer: State<'_, UniversalInstaller>, job_id: String) -> Result<Option<InstallationJob>, String> {
     Ok(installer.get_job(&job_id))
 }
 
 #[command]
-pub async fn get_all_installation_jobs() -> Result<Vec<InstallationJob>, String> {
-    let installer = UniversalInstaller::new();
+pub async fn get_all_installation_jobs(installer: State<'_, UniversalInstaller>) -> Result<Vec<InstallationJob>, String> {
     Ok(installer.get_all_jobs())
-}
\ No newline at end of file
+}
+
+#[command]
+pub async fn plan_installation(
+    installer: State<'_, UniversalInstaller>,
+    tools: Vec<ToolInstallRequest>,
+    template_id: Option<String>,
+) -> Result<InstallPlan, String> {
+    installer.plan(tools, template_id.as_deref(), detect_installed_tools())
+}
+
+#[command]
+pub async fn preview_installation(
+    installer: State<'_, UniversalInstaller>,
+    tools: Vec<ToolInstallRequest>,
+    template_id: Option<String>,
+) -> Result<InstallPreview, String> {
+    installer.preview(tools, template_id.as_deref(), detect_installed_tools())
+}
+
+#[command]
+pub async fn install_batch(
+    installer: State<'_, UniversalInstaller>,
+    workspace_id: String,
+    tools: Vec<ToolInstallRequest>,
+    template_id: Option<String>,
+    max_parallel: Option<usize>,
+    rollback_policy: Option<String>,
+    force: Option<bool>,
+) -> Result<String, String> {
+    let plan = installer.plan(tools, template_id.as_deref(), detect_installed_tools())?;
+    installer.install_plan(workspace_id, plan, max_parallel, rollback_policy, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn get_installation_batch(installer: State<'_, UniversalInstaller>, batch_id: String) -> Result<Option<InstallationBatch>, String> {
+    Ok(installer.get_batch(&batch_id))
+}
+
+#[command]
+pub async fn rollback_installation_batch(installer: State<'_, UniversalInstaller>, batch_id: String) -> Result<RollbackReport, String> {
+    installer.rollback_batch(&batch_id).await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn shell_step(tool_id: &str, depends_on: Vec<&str>, stage: usize, commands: Vec<String>, files_touched: Vec<String>) -> PlanStep {
+        PlanStep {
+            depends_on: depends_on.into_iter().map(|d| d.to_string()).collect(),
+            stage,
+            commands: commands.iter().map(|c| InstallCommand::shell(c)).collect(),
+            files_touched,
+            ..PlanStep::for_tool(tool_id)
+        }
+    }
+
+    fn test_plan(steps: Vec<PlanStep>) -> InstallPlan {
+        let mut stages: Vec<Vec<String>> = Vec::new();
+        for step in &steps {
+            if stages.len() <= step.stage {
+                stages.resize(step.stage + 1, Vec::new());
+            }
+            stages[step.stage].push(step.tool_id.clone());
+        }
+
+        InstallPlan {
+            id: "plan-1".to_string(),
+            platform: Platform::current(),
+            steps,
+            stages,
+            skipped: Vec::new(),
+            unresolved: Vec::new(),
+            conflicts: Vec::new(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    async fn wait_for_batch(installer: &UniversalInstaller, batch_id: &str, done: impl Fn(&InstallationBatch) -> bool) -> InstallationBatch {
+        for _ in 0..200 {
+            if let Some(batch) = installer.get_batch(batch_id) {
+                if done(&batch) {
+                    return batch;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        }
+        panic!("batch {} did not finish", batch_id);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_rolls_back_automatically() {
+        let dir = tempdir().unwrap();
+        let created = dir.path().join("tool-a.bin").display().to_string();
+        let database = Database::open(&dir.path().join("nuffi.db")).unwrap();
+        let installer = UniversalInstaller::new().with_database(Arc::new(Mutex::new(database)));
+
+        let plan = test_plan(vec![
+            shell_step("tool-a", vec![], 0, vec![format!("touch {}", created)], vec![created.clone()]),
+            shell_step("tool-b", vec!["tool-a"], 1, vec!["exit 3".to_string()], vec![]),
+            shell_step("tool-c", vec!["tool-b"], 2, vec!["true".to_string()], vec![]),
+        ]);
+
+        let batch_id = installer
+            .install_plan("workspace-1".to_string(), plan, None, Some("automatic".to_string()), false)
+            .unwrap();
+        let batch = wait_for_batch(&installer, &batch_id, |b| {
+            matches!(b.rollback_status.as_deref(), Some("rolled_back") | Some("rollback_failed"))
+        }).await;
+
+        assert_eq!(batch.status, "failed");
+        assert_eq!(batch.rollback_status.as_deref(), Some("rolled_back"));
+        assert!(!std::path::Path::new(&created).exists());
+
+        let job_c = installer.get_job(&batch.job_ids["tool-c"]).unwrap();
+        assert_eq!(job_c.status, "skipped");
+
+        let records = installer.database.as_ref().unwrap().lock().unwrap().get_installation_jobs(&batch_id).unwrap();
+        let record_a = records.iter().find(|r| r.tool_name == "tool-a").unwrap();
+        assert_eq!(record_a.status, "completed");
+        assert_eq!(record_a.rollback_status.as_deref(), Some("rolled_back"));
+    }
+
+    #[tokio::test]
+    async fn test_downloads_run_from_a_private_copy() {
+        let dir = tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let script = dir.path().join("tool-a.sh");
+        std::fs::write(&script, b"installer script").unwrap();
+        let url = "https://x.test/tool-a.sh";
+        let artifact = cache.insert_file(url, &script, None).unwrap();
+        let installer = UniversalInstaller::new().with_download_cache(cache);
+
+        let shared = "/shared/nuffi-downloads/tool-a.sh";
+        let mut step = shell_step("tool-a", vec![], 0, vec![format!("cat \"{}\"", shared)], vec![]);
+        step.download = Some(crate::install_planner::PlannedDownload {
+            url: url.to_string(),
+            checksum: Some(format!("sha256:{}", artifact.digest)),
+            signature: None,
+            destination: shared.to_string(),
+        });
+
+        let batch_id = installer.install_plan("workspace-1".to_string(), test_plan(vec![step]), None, None, false).unwrap();
+        let batch = wait_for_batch(&installer, &batch_id, |b| b.completed_at.is_some()).await;
+        let job = installer.get_job(&batch.job_ids["tool-a"]).unwrap();
+        assert_eq!(job.status, "completed");
+        assert!(job.log.iter().any(|line| line == "installer script"));
+        assert!(!job.log.iter().any(|line| line.contains(shared)));
+
+        std::fs::write(&script, b"swapped").unwrap();
+        assert!(check_staged(&script, &artifact.digest).unwrap_err().contains("changed after it was verified"));
+    }
+
+    #[test]
+    fn test_tool_aliases_are_unique() {
+        let installer = UniversalInstaller::new();
+        let mut step = shell_step("nodejs", vec![], 0, vec![], vec![]);
+        step.tool.name = "Node.js".to_string();
+        assert_eq!(installer.tool_aliases(&step), vec!["nodejs".to_string(), "Node.js".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_plan_needs_force() {
+        let installer = UniversalInstaller::new();
+        let mut plan = test_plan(vec![shell_step("tool-a", vec![], 0, vec!["true".to_string()], vec![])]);
+        plan.unresolved.push("ghost".to_string());
+        plan.conflicts.push("tool-a conflicts with tool-b".to_string());
+
+        let error = installer.install_plan("workspace-1".to_string(), plan.clone(), None, None, false).unwrap_err();
+        assert!(error.contains("unresolved ghost"));
+        assert!(error.contains("tool-a conflicts with tool-b"));
+
+        let batch_id = installer.install_plan("workspace-1".to_string(), plan, None, None, true).unwrap();
+        let batch = wait_for_batch(&installer, &batch_id, |b| b.completed_at.is_some()).await;
+        assert_eq!(installer.get_job(&batch.job_ids["tool-a"]).unwrap().status, "completed");
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_waits_for_confirmation() {
+        let dir = tempdir().unwrap();
+        let created = dir.path().join("tool-a.bin").display().to_string();
+        let installer = UniversalInstaller::new();
+
+        let plan = test_plan(vec![
+            shell_step("tool-a", vec![], 0, vec![format!("touch {}", created)], vec![created.clone()]),
+            shell_step("tool-b", vec![], 0, vec!["exit 1".to_string()], vec![]),
+        ]);
+
+        let batch_id = installer.install_plan("workspace-1".to_string(), plan, Some(1), None, false).unwrap();
+        let batch = wait_for_batch(&installer, &batch_id, |b| b.completed_at.is_some()).await;
+        assert_eq!(batch.rollback_status.as_deref(), Some("awaiting_confirmation"));
+        assert!(std::path::Path::new(&created).exists());
+
+        let report = installer.rollback_batch(&batch_id).await.unwrap();
+        assert!(report.succeeded());
+        assert!(!std::path::Path::new(&created).exists());
+        assert!(installer.rollback_batch(&batch_id).await.is_err());
+    }
+
+    async fn wait_for_job(installer: &UniversalInstaller, job_id: &str) -> InstallationJob {
+        for _ in 0..200 {
+            if let Some(job) = installer.get_job(job_id).filter(|job| job.completed_at.is_some()) {
+                return job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        }
+        panic!("job {} did not finish", job_id);
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_and_upgrade_update_workspaces() {
+        let workspaces = Arc::new(Mutex::new(WorkspaceManager::new()));
+        let mut ids = Vec::new();
+        for name in ["Backend", "Frontend"] {
+            let mut manager = workspaces.lock().unwrap();
+            let id = manager
+                .create_workspace(crate::workspace_manager::CreateWorkspaceRequest {
+                    name: name.to_string(),
+                    workspace_type: "development".to_string(),
+                    tools: Vec::new(),
+                    config: None,
+                })
+                .unwrap()
+                .id;
+            manager.record_tool(&id, InstalledTool::from(crate::scanner::DetectedTool {
+                name: "tool-a".to_string(),
+                tool_type: "cli".to_string(),
+                version: "1.0.0".to_string(),
+                path: String::new(),
+                size: 0,
+                status: "installed".to_string(),
+            })).unwrap();
+            ids.push(id);
+        }
+        let installer = UniversalInstaller::new().with_workspaces(Arc::clone(&workspaces));
+
+        let mut upgrade = shell_step("tool-a", vec![], 0, vec!["true".to_string()], vec![]);
+        upgrade.verify_commands = vec![InstallCommand::shell("echo tool-a version 2.5.1")];
+        assert!(installer.start_operation(ids[0].clone(), "upgrade", upgrade.clone(), false).unwrap_err().contains("Frontend uses tool-a"));
+
+        let job_id = installer.start_operation(ids[0].clone(), "upgrade", upgrade, true).unwrap();
+        assert_eq!(wait_for_job(&installer, &job_id).await.status, "completed");
+        assert_eq!(workspaces.lock().unwrap().get_workspace(&ids[1]).unwrap().tools[0].version, "2.5.1");
+
+        // A tool that still responds after uninstall fails verification
+        let mut uninstall = shell_step("tool-a", vec![], 0, vec!["true".to_string()], vec![]);
+        uninstall.verify_commands = vec![InstallCommand::shell("true")];
+        let job_id = installer.start_operation(ids[0].clone(), "uninstall", uninstall.clone(), true).unwrap();
+        let job = wait_for_job(&installer, &job_id).await;
+        assert_eq!(job.status, "failed");
+        assert!(job.error.unwrap().contains("still available"));
+
+        uninstall.verify_commands = vec![InstallCommand::shell("exit 127")];
+        let job_id = installer.start_operation(ids[0].clone(), "uninstall", uninstall, true).unwrap();
+        assert_eq!(wait_for_job(&installer, &job_id).await.status, "completed");
+        for id in &ids {
+            assert!(workspaces.lock().unwrap().get_workspace(id).unwrap().tools.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verification_sets_job_status() {
+        let installer = UniversalInstaller::new();
+
+        let mut mismatched = shell_step("tool-a", vec![], 0, vec!["true".to_string()], vec![]);
+        mismatched.tool.version = Some("3".to_string());
+        mismatched.verify_commands = vec![InstallCommand::shell("echo tool-a 2.5.1")];
+        let job_id = installer.start_operation("workspace-1".to_string(), "install", mismatched, false).unwrap();
+        let job = wait_for_job(&installer, &job_id).await;
+        assert_eq!(job.status, "failed_verification");
+        assert!(job.error.unwrap().contains("3 was requested but 2.5.1 is installed"));
+
+        let mut partial = shell_step("tool-b", vec![], 0, vec!["true".to_string()], vec![]);
+        partial.tool.version = Some("2.5".to_string());
+        partial.verify_commands = vec![InstallCommand::shell("echo tool-b 2.5.1"), InstallCommand::shell("exit 1")];
+        let job_id = installer.start_operation("workspace-1".to_string(), "install", partial, false).unwrap();
+        let job = wait_for_job(&installer, &job_id).await;
+        assert_eq!(job.status, "completed_with_warnings");
+        assert_eq!(job.verification.unwrap().checks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_elevated_steps_need_authorization() {
+        let installer = UniversalInstaller::new()
+            .with_broker(PrivilegeBroker::new(crate::privilege_broker::ElevationMethod::Unavailable));
+
+        let mut elevated = shell_step("tool-a", vec![], 0, vec!["sudo true".to_string()], vec![]);
+        elevated.requires_sudo = true;
+        elevated.tool.required = false;
+        let mut plain = shell_step("tool-b", vec![], 0, vec!["true".to_string()], vec![]);
+        plain.tool.required = false;
+
+        let batch_id = installer
+            .install_plan("workspace-1".to_string(), test_plan(vec![elevated, plain]), None, Some("never".to_string()), false)
+            .unwrap();
+        let batch = wait_for_batch(&installer, &batch_id, |b| b.completed_at.is_some()).await;
+        assert_eq!(batch.status, "completed_with_errors");
+
+        let job_a = installer.get_job(&batch.job_ids["tool-a"]).unwrap();
+        assert_eq!(job_a.status, "failed");
+        assert!(job_a.error.unwrap().contains("Authorization"));
+        assert_eq!(installer.get_job(&batch.job_ids["tool-b"]).unwrap().status, "completed");
+
+        // One prompt for the batch, then the elevated command itself
+        let audit = installer.broker().audit_trail(10);
+        assert_eq!(audit.iter().filter(|entry| entry.command == "authorize").count(), 1);
+        assert!(audit.iter().any(|entry| entry.command == "true" && entry.tool == "tool-a"));
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::command_runner::{CommandOutput, CommandSpec};
+use crate::privilege_broker::find_program;
+
+#[derive(Debug, Error)]
+pub enum IntegrityError {
+    #[error("Invalid checksum `{0}`: expected sha256:<64 hex digits>")]
+    InvalidChecksum(String),
+    #[error("Checksum mismatch for {url}: expected sha256:{expected}, got sha256:{actual}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Signature verification failed for {url}: {reason}")]
+    SignatureMismatch { url: String, reason: String },
+    #[error("Cannot verify the signature of {url}: {program} is not installed")]
+    SignatureUnavailable { url: String, program: String },
+    #[error("{url} has no checksum or signature; it is only installed if you allow unverified downloads for it")]
+    Unverified { url: String },
+    #[error("Failed to download {url}: {reason}")]
+    Download { url: String, reason: String },
+    #[error("{0}")]
+    Io(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureKind {
+    Minisign,
+    Gpg,
+}
+
+// A detached signature published next to a download
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureSpec {
+    pub kind: SignatureKind,
+    pub url: String,
+    // minisign: the base64 public key; gpg: an ASCII-armored public key block
+    pub public_key: String,
+    // gpg only: the fingerprint the signature has to be made with
+    pub fingerprint: Option<String>,
+}
+
+impl SignatureKind {
+    pub fn program(&self) -> &'static str {
+        match self {
+            SignatureKind::Minisign => "minisign",
+            SignatureKind::Gpg => "gpg",
+        }
+    }
+}
+
+pub fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut size = 0u64;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+// Accepts "sha256:<hex>" or a bare hex digest
+pub fn parse_checksum(checksum: &str) -> Option<String> {
+    let digest = checksum.trim().strip_prefix("sha256:").unwrap_or(checksum.trim());
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then(|| digest.to_ascii_lowercase())
+}
+
+// A checksum that is present but unusable is an error, not a reason to skip verification
+pub fn checksum_digest(checksum: Option<&str>) -> Result<Option<String>, IntegrityError> {
+    match checksum {
+        Some(checksum) => parse_checksum(checksum)
+            .map(Some)
+            .ok_or_else(|| IntegrityError::InvalidChecksum(checksum.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub fn verify_checksum(url: &str, actual: &str, expected: Option<&str>) -> Result<(), IntegrityError> {
+    match checksum_digest(expected)? {
+        Some(expected) if expected != actual => Err(IntegrityError::ChecksumMismatch {
+            url: url.to_string(),
+            expected,
+            actual: actual.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Checks a detached signature with the minisign or gpg command line tools
+pub fn verify_signature(url: &str, file: &Path, signature_file: &Path, spec: &SignatureSpec) -> Result<(), IntegrityError> {
+    let program = spec.kind.program();
+    if find_program(program).is_none() {
+        return Err(IntegrityError::SignatureUnavailable {
+            url: url.to_string(),
+            program: program.to_string(),
+        });
+    }
+    let mismatch = |reason: String| IntegrityError::SignatureMismatch { url: url.to_string(), reason };
+    let (file, signature_file) = (file.display().to_string(), signature_file.display().to_string());
+
+    let output = match spec.kind {
+        SignatureKind::Minisign => {
+            CommandSpec::new("minisign", &["-V", "-q", "-P", spec.public_key.trim(), "-m", &file, "-x", &signature_file])
+                .run()
+                .map_err(|e| mismatch(e.to_string()))?
+        }
+        SignatureKind::Gpg => {
+            // A throwaway keyring, so only the manifest's key can vouch for the file
+            let home = std::env::temp_dir().join(format!("nuffi-gpg-{}", uuid::Uuid::new_v4()));
+            let output = run_gpg(url, &home, &file, &signature_file, spec);
+            let _ = std::fs::remove_dir_all(&home);
+            output?
+        }
+    };
+
+    if output.success {
+        Ok(())
+    } else {
+        Err(mismatch(format!("{} rejected the signature: {}", program, output.stderr.trim())))
+    }
+}
+
+fn run_gpg(url: &str, home: &Path, file: &str, signature_file: &str, spec: &SignatureSpec) -> Result<CommandOutput, IntegrityError> {
+    let mismatch = |reason: String| IntegrityError::SignatureMismatch { url: url.to_string(), reason };
+    std::fs::create_dir_all(home).map_err(|e| IntegrityError::Io(format!("Failed to create {}: {}", home.display(), e)))?;
+    let key = home.join("key.asc");
+    std::fs::write(&key, &spec.public_key).map_err(|e| IntegrityError::Io(format!("Failed to write {}: {}", key.display(), e)))?;
+    let homedir = home.display().to_string();
+
+    let import = CommandSpec::new("gpg", &["--homedir", &homedir, "--batch", "--import", &key.display().to_string()])
+        .run()
+        .map_err(|e| mismatch(e.to_string()))?;
+    if !import.success {
+        return Err(mismatch(format!("could not import the public key: {}", import.stderr.trim())));
+    }
+
+    let output = CommandSpec::new("gpg", &["--homedir", &homedir, "--batch", "--status-fd", "1", "--verify", signature_file, file])
+        .run()
+        .map_err(|e| mismatch(e.to_string()))?;
+    if let (true, Some(fingerprint)) = (output.success, &spec.fingerprint) {
+        let expected = fingerprint.replace(' ', "").to_ascii_uppercase();
+        let signed_by_key = output
+            .stdout_lines()
+            .iter()
+            .any(|line| line.starts_with("[GNUPG:] VALIDSIG") && line.split_whitespace().nth(2) == Some(expected.as_str()));
+        if !signed_by_key {
+            return Err(mismatch(format!("not signed by {}", fingerprint)));
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        let digest = "A".repeat(64);
+        assert_eq!(parse_checksum(&format!("sha256:{}", digest)), Some("a".repeat(64)));
+        assert!(matches!(checksum_digest(Some("sha256:abc123def456...")), Err(IntegrityError::InvalidChecksum(_))));
+        assert!(checksum_digest(None).unwrap().is_none());
+
+        assert!(verify_checksum("https://x.test/a", &"a".repeat(64), Some(&digest)).is_ok());
+        assert!(matches!(
+            verify_checksum("https://x.test/a", &"b".repeat(64), Some(&digest)),
+            Err(IntegrityError::ChecksumMismatch { .. })
+        ));
+    }
+}
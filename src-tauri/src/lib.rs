@@ -3,9 +3,55 @@ pub mod database;
 pub mod workspace_manager;
 pub mod scanner;
 pub mod installer;
+pub mod manifest;
+pub mod install_planner;
+pub mod install_journal;
+pub mod install_verifier;
+pub mod download_cache;
+pub mod integrity;
+pub mod offline_bundle;
+pub mod shell_profile;
+pub mod version_manager;
+pub mod dotfile_template;
+pub mod dotfiles;
+pub mod package_command;
+pub mod command_runner;
+pub mod privilege_broker;
+pub mod command_policy;
+pub mod metrics_collector;
+pub mod metrics_rollup;
+pub mod alerting;
+pub mod metrics_exporter;
+pub mod process_inspector;
+pub mod logging;
+pub mod diagnostics;
+pub mod workflow_runner;
 
 pub use models::*;
 pub use database::*;
 pub use workspace_manager::*;
 pub use scanner::*;
-pub use installer::*;
\ No newline at end of file
+pub use installer::*;
+pub use manifest::*;
+pub use install_planner::*;
+pub use install_journal::*;
+pub use install_verifier::*;
+pub use download_cache::*;
+pub use integrity::*;
+pub use offline_bundle::*;
+pub use shell_profile::*;
+pub use version_manager::*;
+pub use dotfile_template::*;
+pub use dotfiles::*;
+pub use package_command::*;
+pub use command_runner::*;
+pub use privilege_broker::*;
+pub use command_policy::*;
+pub use metrics_collector::*;
+pub use metrics_rollup::*;
+pub use alerting::*;
+pub use metrics_exporter::*;
+pub use process_inspector::*;
+pub use logging::*;
+pub use diagnostics::*;
+pub use workflow_runner::*;
\ No newline at end of file
@@ -0,0 +1,302 @@
+use chrono::{Duration, Utc};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, State};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::database::ConfigStore;
+use crate::models::{ApplicationLog, LogQuery, LoggingConfig};
+
+// Events waiting for the config store; once full, new events only reach the console and files
+const LOG_BUFFER_SIZE: usize = 10_000;
+
+// Events are written to the database in batches of up to this many
+const LOG_BATCH_SIZE: usize = 200;
+
+// Stored entries older than this are deleted when the writer starts
+const LOG_RETENTION_DAYS: i64 = 30;
+
+// sqlx logs its own statements, which would otherwise be written back through it
+const IGNORED_TARGET_PREFIX: &str = "sqlx";
+
+// Appends to `path`, moving it to `path.1`, `path.1` to `path.2` and so on once it would exceed
+// `max_bytes`. At most `max_files` files are kept, counting the active one.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64, max_files: u32) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files: max_files.max(1),
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 1 {
+            let _ = fs::remove_file(self.rotated_path(self.max_files - 1));
+            for index in (1..self.max_files - 1).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        } else {
+            self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    // The fmt layer writes each event in one call, so entries are never split across files
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Collects an event's message, with its other fields appended as `key=value`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+// Forwards events to a LogWriter, which stores them in `application_logs`
+pub struct DatabaseLayer {
+    sender: mpsc::Sender<ApplicationLog>,
+}
+
+impl DatabaseLayer {
+    pub fn new() -> (Self, LogWriter) {
+        let (sender, receiver) = mpsc::channel(LOG_BUFFER_SIZE);
+        (Self { sender }, LogWriter { receiver })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for DatabaseLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target().starts_with(IGNORED_TARGET_PREFIX) {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let log = ApplicationLog {
+            id: None,
+            level: metadata.level().as_str().to_lowercase(),
+            message: visitor.message + &visitor.fields,
+            module: metadata.module_path().map(str::to_string),
+            file: metadata.file().map(str::to_string),
+            line: metadata.line(),
+            timestamp: Utc::now(),
+        };
+        // Dropped rather than blocking the caller when the writer can't keep up
+        let _ = self.sender.try_send(log);
+    }
+}
+
+// Drains the DatabaseLayer's buffer into the config store
+pub struct LogWriter {
+    receiver: mpsc::Receiver<ApplicationLog>,
+}
+
+impl LogWriter {
+    pub fn start(self, store: ConfigStore) -> tauri::async_runtime::JoinHandle<()> {
+        tauri::async_runtime::spawn(self.run(store))
+    }
+
+    // Errors go to stderr, since logging them would feed them back into this writer
+    pub async fn run(mut self, store: ConfigStore) {
+        if let Err(e) = store
+            .delete_application_logs_before(Utc::now() - Duration::days(LOG_RETENTION_DAYS))
+            .await
+        {
+            eprintln!("Failed to delete old application logs: {}", e);
+        }
+        while let Some(log) = self.receiver.recv().await {
+            let mut batch = vec![log];
+            while batch.len() < LOG_BATCH_SIZE {
+                match self.receiver.try_recv() {
+                    Ok(log) => batch.push(log),
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = store.store_application_logs(&batch).await {
+                eprintln!("Failed to store {} application logs: {}", batch.len(), e);
+            }
+        }
+    }
+}
+
+// Relative log paths are resolved against the app's data directory
+pub fn log_file_path(config: &LoggingConfig, data_dir: &Path) -> Option<PathBuf> {
+    config.file_path.as_ref().map(|path| data_dir.join(path))
+}
+
+// Installs the global subscriber: the console when enabled, rotating files when `file_path` is set,
+// and the database through the returned writer. RUST_LOG overrides the configured level.
+pub fn init_logging(config: &LoggingConfig, data_dir: &Path) -> Result<LogWriter, String> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))
+        .map_err(|e| format!("Invalid log level {}: {}", config.level, e))?;
+
+    let console = config
+        .enable_console
+        .then(|| tracing_subscriber::fmt::layer().with_writer(io::stderr));
+
+    // Nothing is logged to a file that can't be opened, and the other outputs still work
+    let file = log_file_path(config, data_dir).and_then(|path| {
+        match RotatingFileWriter::open(&path, config.max_file_size_mb * 1024 * 1024, config.max_files) {
+            Ok(writer) => Some(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(Mutex::new(writer))),
+            Err(e) => {
+                eprintln!("Failed to open log file {}: {}", path.display(), e);
+                None
+            }
+        }
+    });
+
+    let (database, writer) = DatabaseLayer::new();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .with(database)
+        .try_init()
+        .map_err(|e| format!("Failed to initialise logging: {}", e))?;
+    Ok(writer)
+}
+
+#[command]
+pub async fn get_application_logs(store: State<'_, ConfigStore>, query: LogQuery) -> Result<Vec<ApplicationLog>, String> {
+    store.query_application_logs(&query).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rotating_file_writer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("logs").join("app.log");
+        let mut writer = RotatingFileWriter::open(&path, 10, 3).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        // Each line is over half the limit, so every write after the first rotates
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(dir.path().join("logs/app.log.1")).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(dir.path().join("logs/app.log.2")).unwrap(), "second\n");
+        assert!(!dir.path().join("logs/app.log.3").exists());
+
+        // Reopening appends to the active file
+        let mut writer = RotatingFileWriter::open(&path, 100, 1).unwrap();
+        writer.write_all(b"fifth\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\nfifth\n");
+    }
+
+    #[tokio::test]
+    async fn test_events_are_stored_and_queried() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+
+        let (layer, writer) = DatabaseLayer::new();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(job = "abc", "Installation started");
+            tracing::warn!("Retrying download");
+            tracing::error!(target: "sqlx::query", "ignored");
+            tracing::error!(target: "nuffi::installer::batch", "Rollback failed");
+        });
+        // The layer is gone, so the writer stops once the buffer is drained
+        writer.run(store.clone()).await;
+
+        let all = store.query_application_logs(&LogQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        let started = all.iter().find(|log| log.level == "info").unwrap();
+        assert_eq!(started.message, "Installation started job=abc");
+        assert!(started.module.as_deref().unwrap().ends_with("logging::tests"));
+        assert!(started.line.is_some());
+
+        let query = LogQuery { level: Some("warn".to_string()), ..LogQuery::default() };
+        assert_eq!(store.query_application_logs(&query).await.unwrap().len(), 2);
+
+        let module = started.module.clone().unwrap();
+        let parent = module.rsplit_once("::").unwrap().0.to_string();
+        let query = LogQuery { module: Some(parent), ..LogQuery::default() };
+        assert_eq!(store.query_application_logs(&query).await.unwrap().len(), 3);
+        // A module name is not a prefix match on its own
+        let query = LogQuery { module: Some(module[..module.len() - 1].to_string()), ..LogQuery::default() };
+        assert!(store.query_application_logs(&query).await.unwrap().is_empty());
+
+        let query = LogQuery { since: Some(Utc::now()), ..LogQuery::default() };
+        assert!(store.query_application_logs(&query).await.unwrap().is_empty());
+        let query = LogQuery { until: Some(Utc::now()), limit: Some(1), ..LogQuery::default() };
+        assert_eq!(store.query_application_logs(&query).await.unwrap().len(), 1);
+        let query = LogQuery { level: Some("loud".to_string()), ..LogQuery::default() };
+        assert!(store.query_application_logs(&query).await.is_err());
+    }
+}
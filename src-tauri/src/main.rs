@@ -1,15 +1,40 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
-use sysinfo::{System, SystemExt, CpuExt, ProcessExt, DiskExt};
+use tauri::{Manager, State};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use command_runner::{CommandOutput, CommandSpec};
+
 mod models;
 mod database;
 mod workspace_manager;
 mod scanner;
 mod installer;
+mod manifest;
+mod install_planner;
+mod install_journal;
+mod install_verifier;
+mod download_cache;
+mod integrity;
+mod offline_bundle;
+mod shell_profile;
+mod version_manager;
+mod dotfile_template;
+mod dotfiles;
+mod package_command;
+mod command_runner;
+mod privilege_broker;
+mod command_policy;
+mod metrics_collector;
+mod metrics_rollup;
+mod alerting;
+mod metrics_exporter;
+mod process_inspector;
+mod logging;
+mod diagnostics;
+mod workflow_runner;
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
@@ -21,6 +46,80 @@ fn greet(name: &str) -> String {
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
+            // Settings come from config.toml in the app config directory, defaults apply without one
+            let config_path = app
+                .path()
+                .app_config_dir()
+                .map(|dir| dir.join(models::APP_CONFIG_FILE_NAME))
+                .unwrap_or_else(|_| std::path::PathBuf::from(models::APP_CONFIG_FILE_NAME));
+            let loaded_config = models::AppConfig::load_from_file(&config_path);
+            let app_config = loaded_config.as_ref().cloned().unwrap_or_default();
+            app.manage(app_config.clone());
+
+            // Logs go to the console and rotating files right away, and to the config store once it opens
+            let data_dir = app.path().app_data_dir().unwrap_or_else(|_| std::path::PathBuf::from("data"));
+            let log_writer = match logging::init_logging(&app_config.logging, &data_dir) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            };
+            match loaded_config {
+                Ok(_) | Err(models::ConfigError::FileNotFound(_)) => {}
+                Err(e) => tracing::warn!("Failed to load {}, using defaults: {}", config_path.display(), e),
+            }
+
+            let workspaces = Arc::new(Mutex::new(workspace_manager::WorkspaceManager::new()));
+            let installer = installer::UniversalInstaller::new().with_workspaces(Arc::clone(&workspaces));
+
+            // Commands sent from the webview are checked against the user's policy file
+            let policy_path = app
+                .path()
+                .app_config_dir()
+                .map(|dir| dir.join(command_policy::POLICY_FILE_NAME))
+                .unwrap_or_else(|_| std::path::PathBuf::from(command_policy::POLICY_FILE_NAME));
+            let policy = command_policy::PolicyEngine::load(policy_path);
+
+            // Installation jobs are persisted when the database is available
+            let (installer, policy) = match database::Database::new(app.handle()) {
+                Ok(db) => {
+                    let db = Arc::new(Mutex::new(db));
+                    (installer.with_database(Arc::clone(&db)), policy.with_database(db))
+                }
+                Err(e) => {
+                    tracing::error!("Failed to open database, installation history will not be saved: {}", e);
+                    (installer, policy)
+                }
+            };
+            app.manage(Arc::clone(&workspaces));
+            app.manage(installer);
+            app.manage(policy);
+
+            // One sampler serves the metrics commands, the background collector and the exporter
+            let monitor = metrics_collector::SystemMonitor::new();
+            app.manage(monitor.clone());
+
+            // Metrics are collected, alert rules evaluated and logs stored in the background when the config store is available
+            match tauri::async_runtime::block_on(database::ConfigStore::new(&database::ConfigStore::url_for(&data_dir))) {
+                Ok(store) => {
+                    let alerts = alerting::AlertEvaluator::new(store.clone())
+                        .with_workspaces(workspaces)
+                        .with_sink(alerting::AppAlertSink::new(app.handle().clone(), app_config.ui.show_notifications));
+                    metrics_collector::MetricsCollector::new(store.clone(), app_config.monitoring.clone(), monitor.clone())
+                        .with_alerts(alerts)
+                        .start();
+                    if let Some(writer) = log_writer {
+                        writer.start(store.clone());
+                    }
+                    app.manage(store);
+                }
+                Err(e) => tracing::error!("Failed to open config store, metrics and logs will not be stored: {}", e),
+            }
+
+            // Scrapers can read the same samples over loopback HTTP when the exporter is enabled
+            metrics_exporter::MetricsExporter::new(app_config.monitoring.clone(), monitor).start();
+
             // Developer tools disabled for better UX
             // #[cfg(debug_assertions)]
             // {
@@ -44,18 +143,69 @@ pub fn run() {
             scanner::analyze_repository,
             // Tool installation
             installer::install_tool,
+            installer::uninstall_tool,
+            installer::upgrade_tool,
             installer::get_installation_job,
             installer::get_all_installation_jobs,
+            installer::plan_installation,
+            installer::preview_installation,
+            installer::install_batch,
+            installer::get_installation_batch,
+            installer::rollback_installation_batch,
+            installer::verify_tool_installation,
+            // Download cache and offline bundles
+            offline_bundle::export_offline_bundle,
+            offline_bundle::import_offline_bundle,
+            offline_bundle::get_download_cache,
+            // Shell profiles and workspace activation
+            shell_profile::get_shell_profiles,
+            shell_profile::write_workspace_activation,
+            shell_profile::remove_workspace_activation,
+            // Language version managers
+            version_manager::get_managed_versions,
+            version_manager::set_project_version,
+            // Dotfiles
+            dotfiles::preview_dotfiles,
+            dotfiles::install_dotfiles,
+            dotfiles::uninstall_dotfiles,
+            dotfiles::get_dotfiles_status,
+            dotfiles::sync_dotfiles,
+            dotfiles::get_dotfiles_manifest,
+            // Administrator privileges
+            privilege_broker::get_privilege_status,
+            privilege_broker::authorize_privileges,
+            privilege_broker::get_privileged_audit,
+            // Command policy
+            command_policy::get_command_policy,
+            command_policy::reload_command_policy,
+            command_policy::get_policy_denials,
             // System monitoring
-            get_system_metrics,
+            metrics_collector::get_system_metrics,
+            metrics_collector::get_historical_metrics,
+            metrics_collector::get_cpu_breakdown,
+            metrics_rollup::get_metric_series,
+            // Alerting
+            alerting::get_alert_rules,
+            alerting::save_alert_rule,
+            alerting::delete_alert_rule,
+            alerting::get_alert_history,
+            // Processes
+            process_inspector::get_processes,
+            process_inspector::send_process_signal,
+            process_inspector::kill_process,
+            process_inspector::find_orphaned_processes,
+            // Application logs
+            logging::get_application_logs,
+            // Diagnostics
+            diagnostics::export_diagnostics_bundle,
+            // Workflows
+            workflow_runner::execute_workflow,
             // Real installation commands
             check_tool_installed,
             get_platform,
             check_package_manager,
             execute_installation,
             clone_dotfiles,
-            install_dotfiles,
-            get_historical_metrics,
             // Setup system
             check_dependency,
             install_dependency
@@ -64,81 +214,16 @@ pub fn run() {
         .expect("error while running tauri application");
 }
 
-#[derive(serde::Serialize)]
-struct SystemMetrics {
-    cpu_usage: f32,
-    memory_usage: f32,
-    disk_usage: u64,
-    network_io: u64,
-    active_processes: usize,
-    uptime_seconds: u64,
-}
-
+// Only the setup dependencies the app knows how to install can be checked, by id
 #[tauri::command]
-async fn get_system_metrics() -> Result<SystemMetrics, String> {
-    let mut system = System::new_all();
-    system.refresh_all();
-    
-    // Calculate CPU usage (average across all cores)
-    let cpu_usage = system.cpus().iter()
-        .map(|cpu| cpu.cpu_usage())
-        .sum::<f32>() / system.cpus().len() as f32;
-    
-    // Calculate memory usage percentage
-    let total_memory = system.total_memory();
-    let used_memory = system.used_memory();
-    let memory_usage = if total_memory > 0 {
-        (used_memory as f32 / total_memory as f32) * 100.0
-    } else {
-        0.0
+async fn check_dependency(dependency: String) -> Result<bool, String> {
+    let spec = match dependency.as_str() {
+        "tauri-cli" => CommandSpec::new("cargo", &["tauri", "--version"]),
+        _ => return Err(format!("Unknown dependency: {}", dependency)),
     };
-    
-    // Get disk usage (first disk)
-    let disk_usage = system.disks().first()
-        .map(|disk| disk.total_space() - disk.available_space())
-        .unwrap_or(0);
-    
-    // Count active processes
-    let active_processes = system.processes().len();
-    
-    // Get system uptime
-    let uptime_seconds = system.uptime();
-    
-    // Network I/O (simplified - would need more complex implementation for real network stats)
-    let network_io = 0; // Placeholder
-    
-    Ok(SystemMetrics {
-        cpu_usage,
-        memory_usage,
-        disk_usage,
-        network_io,
-        active_processes,
-        uptime_seconds,
-    })
-}
-
-#[tauri::command]
-async fn get_historical_metrics(limit: usize) -> Result<Vec<SystemMetrics>, String> {
-    // In a real implementation, this would fetch from database
-    // For now, return empty array - the frontend will use mock data
-    Ok(vec![])
-}
 
-#[tauri::command]
-async fn check_dependency(command: String) -> Result<bool, String> {
-    use std::process::Command;
-    
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    if parts.is_empty() {
-        return Err("Invalid command".to_string());
-    }
-    
-    let result = Command::new(parts[0])
-        .args(&parts[1..])
-        .output();
-    
-    match result {
-        Ok(output) => Ok(output.status.success()),
+    match spec.run_async().await {
+        Ok(output) => Ok(output.success),
         Err(_) => Ok(false),
     }
 }
@@ -165,36 +250,49 @@ async fn install_dependency(dependency: String) -> Result<bool, String> {
 fn main() {
     run();
 }
+#[derive(serde::Serialize)]
+struct ToolCheck {
+    success: bool,
+    path: Option<String>,
+    version: Option<String>,
+}
+
 // Real installation commands
+// Runs the manifest's own verification commands for `tool_name`, each authorized by the command policy
 #[tauri::command]
-async fn check_tool_installed(tool_name: String, check_commands: Vec<String>) -> Result<serde_json::Value, String> {
-    use std::process::Command;
-    
+async fn check_tool_installed(
+    installer: State<'_, installer::UniversalInstaller>,
+    policy: State<'_, command_policy::PolicyEngine>,
+    tool_name: String,
+) -> Result<ToolCheck, String> {
+    let platform = manifest::Platform::current();
+    let check_commands = installer
+        .registry()
+        .get(&tool_name)
+        .and_then(|manifest| manifest.platform_spec(&platform))
+        .and_then(|spec| spec.verification)
+        .map(|verification| verification.all_commands())
+        .unwrap_or_default();
+
     for cmd in check_commands {
-        let parts: Vec<&str> = cmd.split_whitespace().collect();
-        if parts.is_empty() { continue; }
-        
-        let mut command = Command::new(parts[0]);
-        if parts.len() > 1 {
-            command.args(&parts[1..]);
-        }
-        
-        match command.output() {
-            Ok(output) if output.status.success() => {
-                let version = String::from_utf8_lossy(&output.stdout);
-                return Ok(serde_json::json!({
-                    "success": true,
-                    "path": parts[0],
-                    "version": version.trim()
-                }));
+        let spec = match policy.authorize(&tool_name, &cmd, installer.registry(), &platform) {
+            Ok(spec) => spec,
+            Err(_) => continue,
+        };
+
+        match spec.run_async().await {
+            Ok(output) if output.success => {
+                return Ok(ToolCheck {
+                    success: true,
+                    path: Some(spec.program().to_string()),
+                    version: Some(output.stdout.trim().to_string()),
+                });
             }
             _ => continue,
         }
     }
-    
-    Ok(serde_json::json!({
-        "success": false
-    }))
+
+    Ok(ToolCheck { success: false, path: None, version: None })
 }
 
 #[tauri::command]
@@ -204,85 +302,53 @@ async fn get_platform() -> Result<String, String> {
 
 #[tauri::command]
 async fn check_package_manager(manager: String) -> Result<bool, String> {
-    use std::process::Command;
-    
-    let check_cmd = match manager.as_str() {
-        "brew" => "brew --version",
-        "apt" => "apt --version",
-        "yum" => "yum --version",
-        "dnf" => "dnf --version",
-        "pacman" => "pacman --version",
-        "chocolatey" => "choco --version",
-        "winget" => "winget --version",
-        "scoop" => "scoop --version",
-        "npm" => "npm --version",
-        "pip" => "pip --version",
+    let program = match manager.as_str() {
+        "brew" => "brew",
+        "apt" => "apt",
+        "yum" => "yum",
+        "dnf" => "dnf",
+        "pacman" => "pacman",
+        "chocolatey" => "choco",
+        "winget" => "winget",
+        "scoop" => "scoop",
+        "npm" => "npm",
+        "pip" => "pip",
         _ => return Ok(false),
     };
-    
-    let parts: Vec<&str> = check_cmd.split_whitespace().collect();
-    if parts.is_empty() { return Ok(false); }
-    
-    let mut command = Command::new(parts[0]);
-    if parts.len() > 1 {
-        command.args(&parts[1..]);
-    }
-    
-    match command.output() {
-        Ok(output) => Ok(output.status.success()),
+
+    match CommandSpec::new(program, &["--version"]).run_async().await {
+        Ok(output) => Ok(output.success),
         Err(_) => Ok(false),
     }
 }
 
+// Runs a single install command, if the command policy allows it for `tool_name`
 #[tauri::command]
-async fn execute_installation(command: String, tool_name: String) -> Result<serde_json::Value, String> {
-    use std::process::Command;
-    use std::time::Duration;
-    
-    println!("Installing {}: {}", tool_name, command);
-    
-    // Parse command
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    if parts.is_empty() {
-        return Err("Empty command".to_string());
-    }
-    
-    let mut cmd = Command::new(parts[0]);
-    if parts.len() > 1 {
-        cmd.args(&parts[1..]);
-    }
-    
-    // Execute with timeout
-    match cmd.output() {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let combined_output = format!("{}\n{}", stdout, stderr);
-            
-            if output.status.success() {
-                Ok(serde_json::json!({
-                    "success": true,
-                    "output": combined_output
-                }))
-            } else {
-                Ok(serde_json::json!({
-                    "success": false,
-                    "output": combined_output,
-                    "error": format!("Command failed with exit code: {:?}", output.status.code())
-                }))
-            }
-        }
-        Err(e) => {
-            Err(format!("Failed to execute command: {}", e))
-        }
+async fn execute_installation(
+    installer: State<'_, installer::UniversalInstaller>,
+    policy: State<'_, command_policy::PolicyEngine>,
+    command: String,
+    tool_name: String,
+) -> Result<CommandOutput, String> {
+    tracing::info!("Installing {}: {}", tool_name, command);
+
+    // The webview only picks the command; its environment and directory are never taken from it
+    let spec = policy.authorize(&tool_name, &command, installer.registry(), &manifest::Platform::current())?;
+
+    if installer::InstallCommand::from(spec.clone()).needs_sudo() {
+        let broker = installer.broker();
+        return tokio::task::spawn_blocking(move || broker.run_elevated(&spec, None, &tool_name))
+            .await
+            .map_err(|e| format!("Installation task failed: {}", e))?;
     }
+    spec.run_async().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn clone_dotfiles(repo_url: String, target_dir: String) -> Result<serde_json::Value, String> {
     use std::process::Command;
     
-    println!("Cloning dotfiles from {} to {}", repo_url, target_dir);
+    tracing::info!("Cloning dotfiles from {} to {}", repo_url, target_dir);
     
     // Expand ~ in target_dir
     let expanded_dir = if target_dir.starts_with("~/") {
@@ -320,59 +386,4 @@ async fn clone_dotfiles(repo_url: String, target_dir: String) -> Result<serde_js
             Err(format!("Failed to clone dotfiles: {}", e))
         }
     }
-}
-
-#[tauri::command]
-async fn install_dotfiles(dotfiles_dir: String) -> Result<serde_json::Value, String> {
-    use std::process::Command;
-    use std::path::Path;
-    
-    println!("Installing dotfiles from {}", dotfiles_dir);
-    
-    // Expand ~ in dotfiles_dir
-    let expanded_dir = if dotfiles_dir.starts_with("~/") {
-        match std::env::var("HOME") {
-            Ok(home) => dotfiles_dir.replace("~", &home),
-            Err(_) => dotfiles_dir,
-        }
-    } else {
-        dotfiles_dir
-    };
-    
-    // Check if install script exists
-    let install_script = Path::new(&expanded_dir).join("install.sh");
-    let makefile = Path::new(&expanded_dir).join("Makefile");
-    
-    let mut cmd = if install_script.exists() {
-        let mut c = Command::new("bash");
-        c.arg(install_script);
-        c
-    } else if makefile.exists() {
-        let mut c = Command::new("make");
-        c.current_dir(&expanded_dir);
-        c.arg("install");
-        c
-    } else {
-        // Fallback: try to symlink common dotfiles
-        let mut c = Command::new("bash");
-        c.arg("-c");
-        c.arg(format!("cd {} && for file in .*; do [ -f \"$file\" ] && ln -sf \"$(pwd)/$file\" \"$HOME/$file\"; done", expanded_dir));
-        c
-    };
-    
-    match cmd.output() {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let combined_output = format!("{}\n{}", stdout, stderr);
-            
-            Ok(serde_json::json!({
-                "success": true,
-                "output": combined_output
-            }))
-        }
-        Err(e) => {
-            Err(format!("Failed to install dotfiles: {}", e))
-        }
-    }
 }
\ No newline at end of file
@@ -0,0 +1,583 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::integrity::{parse_checksum, SignatureSpec};
+
+// Manifests and templates shipped with the app. Extra manifests can be loaded from disk.
+const BUNDLED_MANIFESTS: &[&str] = &[
+    include_str!("../../backend/app/tools/manifests/cli/nodejs.json"),
+    include_str!("../../backend/app/tools/manifests/cli/homebrew.json"),
+    include_str!("../../backend/app/tools/manifests/gui/vscode.json"),
+    include_str!("../../backend/app/tools/manifests/gui/docker-desktop.json"),
+];
+
+const BUNDLED_TEMPLATES: &[&str] = &[
+    include_str!("../../backend/app/tools/templates/backend-complete.json"),
+    include_str!("../../backend/app/tools/templates/frontend-complete.json"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub manifest_type: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub description: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub platforms: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformSpec {
+    pub package_manager: Option<String>,
+    pub package_name: Option<String>,
+    pub version: Option<String>,
+    pub install_command: Option<String>,
+    #[serde(default)]
+    pub install_commands: Vec<String>,
+    pub installer: Option<InstallerSpec>,
+    #[serde(default)]
+    pub path_updates: Vec<String>,
+    #[serde(default)]
+    pub post_install: Vec<serde_json::Value>,
+    pub verification: Option<VerificationSpec>,
+    pub size: Option<String>,
+    #[serde(default)]
+    pub uninstall_commands: Vec<String>,
+    #[serde(default)]
+    pub upgrade_commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallerSpec {
+    #[serde(rename = "type")]
+    pub installer_type: String,
+    pub url: String,
+    pub checksum: Option<String>,
+    pub signature: Option<SignatureSpec>,
+    pub install_command: Option<String>,
+    pub install_path: Option<String>,
+    pub silent_args: Option<String>,
+    #[serde(default)]
+    pub requires_admin: bool,
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationSpec {
+    #[serde(default)]
+    pub commands: Vec<String>,
+    pub command: Option<String>,
+    pub expected_output: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallTemplate {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub gui_tools: Vec<String>,
+    #[serde(default)]
+    pub cli_tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Platform {
+    pub os: String,
+    pub distro: Option<String>,
+    pub distro_like: Vec<String>,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        let os = std::env::consts::OS.to_string();
+        let (distro, distro_like) = if os == "linux" {
+            Self::read_os_release()
+        } else {
+            (None, Vec::new())
+        };
+
+        Self { os, distro, distro_like }
+    }
+
+    fn read_os_release() -> (Option<String>, Vec<String>) {
+        let content = match std::fs::read_to_string("/etc/os-release") {
+            Ok(content) => content,
+            Err(_) => return (None, Vec::new()),
+        };
+
+        let mut distro = None;
+        let mut distro_like = Vec::new();
+        for line in content.lines() {
+            if let Some(value) = line.strip_prefix("ID=") {
+                distro = Some(value.trim_matches('"').to_lowercase());
+            } else if let Some(value) = line.strip_prefix("ID_LIKE=") {
+                distro_like = value
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .map(|s| s.to_lowercase())
+                    .collect();
+            }
+        }
+
+        (distro, distro_like)
+    }
+}
+
+impl ToolManifest {
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let manifest: Self = serde_json::from_str(content).map_err(|e| format!("Invalid tool manifest: {}", e))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    // Downloads are only as trustworthy as their checksums, so malformed ones reject the whole manifest
+    fn validate(&self) -> Result<(), String> {
+        for (os, value) in &self.platforms {
+            let specs: Vec<&serde_json::Value> = if Self::is_platform_spec(value) {
+                vec![value]
+            } else {
+                value.as_object().map(|distros| distros.values().collect()).unwrap_or_default()
+            };
+
+            for spec in specs {
+                let spec: PlatformSpec = serde_json::from_value(spec.clone())
+                    .map_err(|e| format!("Invalid tool manifest {}: {} platform: {}", self.id, os, e))?;
+                let installer = match spec.installer {
+                    Some(installer) => installer,
+                    None => continue,
+                };
+                if let Some(checksum) = installer.checksum.as_deref().filter(|c| parse_checksum(c).is_none()) {
+                    return Err(format!("Invalid tool manifest {}: checksum `{}` for {} is not a SHA-256 digest", self.id, checksum, os));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn platform_spec(&self, platform: &Platform) -> Option<PlatformSpec> {
+        let value = self.platforms.get(&platform.os)?;
+
+        // Linux entries are usually keyed by distribution; fall back to ID_LIKE matches
+        if platform.os == "linux" && !Self::is_platform_spec(value) {
+            let candidates = platform.distro.iter().chain(platform.distro_like.iter());
+            for distro in candidates {
+                if let Some(spec) = value.get(distro) {
+                    return serde_json::from_value(spec.clone()).ok();
+                }
+            }
+            return None;
+        }
+
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    fn is_platform_spec(value: &serde_json::Value) -> bool {
+        ["installer", "installCommand", "installCommands", "packageManager"]
+            .iter()
+            .any(|key| value.get(key).is_some())
+    }
+
+    // Maps the manifest category onto the tool types understood by the installer
+    pub fn tool_type(&self) -> String {
+        match self.category.as_str() {
+            "runtime" | "language" => "language",
+            "database" => "database",
+            "editor" | "ide" => "ide",
+            "package-manager" => "package",
+            _ => "cli",
+        }
+        .to_string()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.id.eq_ignore_ascii_case(name) || self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl PlatformSpec {
+    pub fn commands(&self, tool_id: &str) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(command) = &self.install_command {
+            commands.push(command.clone());
+        }
+        commands.extend(self.install_commands.iter().cloned());
+        if let Some(installer) = &self.installer {
+            commands.extend(installer.commands(tool_id));
+        }
+        commands.extend(self.post_install_commands());
+        commands
+    }
+
+    // Post-install entries are either plain commands or structured actions
+    pub fn post_install_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+
+        for entry in &self.post_install {
+            if let Some(command) = entry.as_str() {
+                commands.push(command.to_string());
+                continue;
+            }
+
+            if entry.get("action").and_then(|a| a.as_str()) == Some("install_extensions") {
+                let extensions = entry.get("extensions").and_then(|e| e.as_array());
+                for extension in extensions.into_iter().flatten().filter_map(|e| e.as_str()) {
+                    commands.push(format!("code --install-extension {}", extension));
+                }
+            }
+        }
+
+        commands
+    }
+
+    // Settings files written by `configure_settings` post-install actions
+    pub fn settings_files(&self) -> Vec<String> {
+        self.post_install
+            .iter()
+            .filter(|entry| entry.get("action").and_then(|a| a.as_str()) == Some("configure_settings"))
+            .filter_map(|entry| entry.get("settingsPath").and_then(|p| p.as_str()))
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    pub fn download_size(&self) -> Option<u64> {
+        self.installer
+            .as_ref()
+            .and_then(|installer| installer.size.as_deref())
+            .or(self.size.as_deref())
+            .and_then(parse_size)
+    }
+
+    pub fn requires_admin(&self) -> bool {
+        self.installer.as_ref().map(|i| i.requires_admin).unwrap_or(false)
+    }
+}
+
+impl VerificationSpec {
+    pub fn all_commands(&self) -> Vec<String> {
+        self.command.iter().chain(self.commands.iter()).cloned().collect()
+    }
+}
+
+impl InstallerSpec {
+    pub fn download_path(&self, tool_id: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join("nuffi-downloads")
+            .join(format!("{}.{}", tool_id, self.installer_type))
+    }
+
+    pub fn download_command(&self, tool_id: &str) -> String {
+        format!("curl --create-dirs -fsSL -o \"{}\" \"{}\"", self.download_path(tool_id).display(), self.url)
+    }
+
+    // Download the installer, then run it the way its package type expects
+    pub fn commands(&self, tool_id: &str) -> Vec<String> {
+        let file = self.download_path(tool_id).display().to_string();
+        let mut commands = vec![self.download_command(tool_id)];
+
+        if let Some(install_command) = &self.install_command {
+            commands.push(install_command.replace("$INSTALLER", &format!("\"{}\"", file)));
+            return commands;
+        }
+
+        match self.installer_type.as_str() {
+            "dmg" => {
+                let mount = format!("/Volumes/nuffi-{}", tool_id);
+                commands.push(format!("hdiutil attach -nobrowse -mountpoint \"{}\" \"{}\"", mount, file));
+                if let Some(app) = self.install_path.as_deref().and_then(|p| Path::new(p).file_name()) {
+                    commands.push(format!("cp -R \"{}/{}\" /Applications/", mount, app.to_string_lossy()));
+                }
+                commands.push(format!("hdiutil detach \"{}\"", mount));
+            }
+            "pkg" => commands.push(format!("sudo installer -pkg \"{}\" -target /", file)),
+            "deb" => commands.push(format!("sudo dpkg -i \"{}\"", file)),
+            "rpm" => commands.push(format!("sudo rpm -i \"{}\"", file)),
+            "exe" | "msi" => commands.push(format!("\"{}\" {}", file, self.silent_args.as_deref().unwrap_or("")).trim().to_string()),
+            _ => {}
+        }
+
+        commands
+    }
+}
+
+// Parses manifest sizes such as "50 MB" into bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let value: f64 = size[..split].trim().parse().ok()?;
+
+    let multiplier = match size[split..].trim().to_uppercase().as_str() {
+        "" | "B" => 1u64,
+        "KB" | "K" => 1024,
+        "MB" | "M" => 1024 * 1024,
+        "GB" | "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    Some((value * multiplier as f64) as u64)
+}
+
+impl InstallTemplate {
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        serde_json::from_str(content).map_err(|e| format!("Invalid template: {}", e))
+    }
+
+    pub fn tool_ids(&self) -> Vec<String> {
+        self.cli_tools.iter().chain(self.gui_tools.iter()).cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ManifestRegistry {
+    manifests: HashMap<String, ToolManifest>,
+    templates: HashMap<String, InstallTemplate>,
+    // Manifests whose commands are trusted verbatim, shell lines included
+    trusted_manifests: HashSet<String>,
+}
+
+impl ManifestRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bundled() -> Self {
+        let mut registry = Self::new();
+
+        for content in BUNDLED_MANIFESTS {
+            if let Ok(manifest) = ToolManifest::from_json(content) {
+                registry.trust_manifest(&manifest.id);
+                registry.add_manifest(manifest);
+            }
+        }
+
+        for content in BUNDLED_TEMPLATES {
+            if let Ok(template) = InstallTemplate::from_json(content) {
+                registry.add_template(template);
+            }
+        }
+
+        registry
+    }
+
+    pub fn load_dir(&mut self, dir: &Path) -> Result<usize, String> {
+        let mut loaded = 0;
+
+        for entry in walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+            self.add_manifest(ToolManifest::from_json(&content)?);
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
+    pub fn add_manifest(&mut self, manifest: ToolManifest) {
+        self.manifests.insert(manifest.id.clone(), manifest);
+    }
+
+    pub fn add_template(&mut self, template: InstallTemplate) {
+        self.templates.insert(template.id.clone(), template);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ToolManifest> {
+        self.manifests
+            .get(name)
+            .or_else(|| self.manifests.values().find(|m| m.matches(name)))
+    }
+
+    pub fn trust_manifest(&mut self, manifest_id: &str) {
+        self.trusted_manifests.insert(manifest_id.to_string());
+    }
+
+    // True when `command_line` is written verbatim in a trusted manifest for this platform.
+    // Installer lines are left out: they fetch to and run from a shared temp path, so installers
+    // only run through `UniversalInstaller`, which stages a verified copy instead.
+    pub fn trusts_command(&self, tool: &str, command_line: &str, platform: &Platform) -> bool {
+        let manifest = match self.get(tool) {
+            Some(manifest) if self.trusted_manifests.contains(&manifest.id) => manifest,
+            _ => return false,
+        };
+        let spec = match manifest.platform_spec(platform) {
+            Some(spec) => spec,
+            None => return false,
+        };
+
+        let mut commands: Vec<String> = spec.install_command.iter().chain(&spec.install_commands).cloned().collect();
+        commands.extend(spec.post_install_commands());
+        commands.extend(spec.uninstall_commands.iter().cloned());
+        commands.extend(spec.upgrade_commands.iter().cloned());
+        commands.extend(spec.verification.iter().flat_map(|v| v.all_commands()));
+        commands.iter().any(|command| command.trim() == command_line.trim())
+    }
+
+    pub fn template(&self, id: &str) -> Option<&InstallTemplate> {
+        self.templates.get(id)
+    }
+
+    pub fn manifests(&self) -> Vec<&ToolManifest> {
+        self.manifests.values().collect()
+    }
+
+    pub fn templates(&self) -> Vec<&InstallTemplate> {
+        self.templates.values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux(distro: &str) -> Platform {
+        Platform {
+            os: "linux".to_string(),
+            distro: Some(distro.to_string()),
+            distro_like: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bundled_registry() {
+        let registry = ManifestRegistry::bundled();
+        assert!(registry.get("nodejs").is_some());
+        assert!(registry.get("Node.js").is_some());
+        assert!(registry.get("vscode").is_some());
+        assert!(registry.template("backend-complete").is_some());
+        assert!(registry.template("frontend-complete").is_some());
+    }
+
+    #[test]
+    fn test_manifest_checksums_are_validated() {
+        let manifest = |checksum: &str| {
+            serde_json::json!({
+                "id": "tool",
+                "name": "Tool",
+                "type": "CLI",
+                "platforms": { "linux": { "ubuntu": { "installer": {
+                    "type": "deb",
+                    "url": "https://x.test/tool.deb",
+                    "checksum": checksum,
+                    "signature": { "kind": "minisign", "url": "https://x.test/tool.deb.minisig", "publicKey": "RWQ..." }
+                } } } },
+            })
+            .to_string()
+        };
+
+        assert!(ToolManifest::from_json(&manifest("sha256:abc123def456...")).unwrap_err().contains("not a SHA-256 digest"));
+        let tool = ToolManifest::from_json(&manifest(&format!("sha256:{}", "ab".repeat(32)))).unwrap();
+        let ubuntu = Platform { os: "linux".to_string(), distro: Some("ubuntu".to_string()), distro_like: Vec::new() };
+        let installer = tool.platform_spec(&ubuntu).unwrap().installer.unwrap();
+        assert_eq!(installer.signature.unwrap().kind, crate::integrity::SignatureKind::Minisign);
+    }
+
+    #[test]
+    fn test_platform_spec_resolution() {
+        let registry = ManifestRegistry::bundled();
+        let node = registry.get("nodejs").unwrap();
+
+        let macos = Platform { os: "macos".to_string(), distro: None, distro_like: Vec::new() };
+        let spec = node.platform_spec(&macos).unwrap();
+        assert_eq!(spec.package_manager, Some("homebrew".to_string()));
+        assert_eq!(spec.commands("nodejs")[0], "brew install node@20");
+        assert_eq!(spec.path_updates.len(), 1);
+
+        // Distro-keyed Linux entries
+        let spec = node.platform_spec(&linux("ubuntu")).unwrap();
+        assert_eq!(spec.install_commands.len(), 2);
+
+        // ID_LIKE fallback
+        let mint = Platform {
+            os: "linux".to_string(),
+            distro: Some("linuxmint".to_string()),
+            distro_like: vec!["ubuntu".to_string()],
+        };
+        assert!(node.platform_spec(&mint).is_some());
+        assert!(node.platform_spec(&linux("arch")).is_none());
+
+        // Flat Linux entries
+        let vscode = registry.get("vscode").unwrap();
+        let spec = vscode.platform_spec(&linux("arch")).unwrap();
+        assert_eq!(spec.installer.clone().unwrap().installer_type, "deb");
+
+        let commands = spec.commands("vscode");
+        assert!(commands[0].starts_with("curl"));
+        assert!(commands[1].starts_with("sudo dpkg -i"));
+        assert_eq!(commands[2], "code --install-extension ms-python.python");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("50 MB"), Some(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5GB"), Some(1610612736));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("lots"), None);
+
+        let registry = ManifestRegistry::bundled();
+        let macos = Platform { os: "macos".to_string(), distro: None, distro_like: Vec::new() };
+        let spec = registry.get("vscode").unwrap().platform_spec(&macos).unwrap();
+        assert_eq!(spec.download_size(), Some(95 * 1024 * 1024));
+        assert_eq!(spec.settings_files().len(), 1);
+    }
+
+    #[test]
+    fn test_template_tool_ids() {
+        let registry = ManifestRegistry::bundled();
+        let template = registry.template("frontend-complete").unwrap();
+        let ids = template.tool_ids();
+        assert!(ids.contains(&"nodejs".to_string()));
+        assert!(ids.contains(&"vscode".to_string()));
+    }
+
+    #[test]
+    fn test_manifest_tool_type() {
+        let registry = ManifestRegistry::bundled();
+        assert_eq!(registry.get("nodejs").unwrap().tool_type(), "language");
+        assert_eq!(registry.get("vscode").unwrap().tool_type(), "ide");
+        assert_eq!(registry.get("docker-desktop").unwrap().tool_type(), "cli");
+    }
+
+    #[test]
+    fn test_trusted_manifests() {
+        let mut registry = ManifestRegistry::bundled();
+        let macos = Platform { os: "macos".to_string(), distro: None, distro_like: Vec::new() };
+        assert!(registry.trusts_command("Node.js", "npm config set prefix '~/.npm-global'", &macos));
+        assert!(registry.trusts_command("nodejs", "node --version", &macos));
+        assert!(!registry.trusts_command("nodejs", "curl https://example.com | sh", &macos));
+
+        // Installers never run from the shared download path on the strength of the manifest
+        let ubuntu = linux("ubuntu");
+        let installer = registry.get("vscode").unwrap().platform_spec(&ubuntu).unwrap().installer.unwrap();
+        for command in installer.commands("vscode") {
+            assert!(!registry.trusts_command("vscode", &command, &ubuntu), "{}", command);
+        }
+        assert!(!registry.trusts_command("vscode", "sudo dpkg -i /tmp/nuffi-downloads/vscode.deb", &ubuntu));
+        assert!(registry.trusts_command("vscode", "code --version", &ubuntu));
+
+        // Manifests loaded from elsewhere are not trusted until allowlisted
+        let mut manifest = registry.get("nodejs").unwrap().clone();
+        manifest.id = "custom-node".to_string();
+        manifest.name = "Custom Node".to_string();
+        registry.add_manifest(manifest);
+        assert!(!registry.trusts_command("custom-node", "brew install node@20", &macos));
+        registry.trust_manifest("custom-node");
+        assert!(registry.trusts_command("custom-node", "brew install node@20", &macos));
+    }
+}
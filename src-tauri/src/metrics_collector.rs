@@ -0,0 +1,811 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use sysinfo::{ComponentExt, CpuExt, DiskExt, NetworkExt, NetworksExt, PidExt, Process, ProcessExt, System, SystemExt};
+use tauri::{command, State};
+
+use crate::alerting::AlertEvaluator;
+use crate::database::ConfigStore;
+use crate::models::{CoreError, MonitoringConfig};
+use crate::shell_profile::WORKSPACE_ENV_VAR;
+
+// Metrics the collector samples itself; other names in `metrics_to_collect` are recorded by the features that produce them
+pub const SYSTEM_METRICS: &[&str] = &[
+    "cpu_usage",
+    "memory_usage",
+    "disk_usage",
+    "network_io",
+    "active_processes",
+    "uptime_seconds",
+    "swap_used",
+    "load_average",
+];
+
+// Old rows are deleted at most this often
+const RETENTION_INTERVAL_MINUTES: i64 = 60;
+
+// Rollups are written once their bucket is complete, so the finest resolution sets the pace
+const COMPACTION_INTERVAL_MINUTES: i64 = 1;
+
+// CPU usage and rates are measured between two refreshes at least this far apart; samples
+// requested sooner get the latest one. Must stay above sysinfo's MINIMUM_CPU_UPDATE_INTERVAL.
+const SAMPLE_WINDOW: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Disks, interfaces and sensors come and go rarely, so their lists are reloaded less often
+const LIST_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+const DEFAULT_TOP_PROCESSES: usize = 20;
+
+// Upper bound on the samples a history request reads back (about a day at the default interval)
+const MAX_HISTORY_SAMPLES: usize = 1440;
+
+// Linux reports disk I/O in 512-byte sectors regardless of the device's sector size
+const DISKSTATS_SECTOR_SIZE: u64 = 512;
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct NetworkInterfaceMetrics {
+    pub name: String,
+    pub received_per_second: f64,
+    pub transmitted_per_second: f64,
+    pub total_received: u64,
+    pub total_transmitted: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct DiskMetrics {
+    pub name: String,
+    pub mount_point: String,
+    pub file_system: String,
+    pub total_space: u64,
+    pub used_space: u64,
+    pub usage_percent: f32,
+    // Only known where the OS exposes per-device counters (Linux)
+    pub read_per_second: Option<f64>,
+    pub written_per_second: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct TemperatureReading {
+    pub label: String,
+    pub celsius: f32,
+    pub critical: Option<f32>,
+}
+
+// Usage of the processes started from a workspace's activated shell
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct WorkspaceMetrics {
+    // The workspace id, as exported by its activation script
+    pub workspace: String,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub memory_usage: f32,
+    pub processes: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct SystemMetrics {
+    pub cpu_usage: f32,
+    pub memory_usage: f32,
+    // Used bytes across all mounted devices
+    pub disk_usage: u64,
+    // Bytes per second received and transmitted across all interfaces
+    pub network_io: u64,
+    pub active_processes: usize,
+    pub uptime_seconds: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+    // Not available on Windows
+    pub load_average: Option<LoadAverage>,
+    pub network_interfaces: Vec<NetworkInterfaceMetrics>,
+    pub disks: Vec<DiskMetrics>,
+    // Empty where sensors can't be read
+    pub temperatures: Vec<TemperatureReading>,
+    pub workspaces: Vec<WorkspaceMetrics>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SystemMetrics {
+    pub fn value(&self, name: &str) -> Option<f64> {
+        match name {
+            "cpu_usage" => Some(self.cpu_usage as f64),
+            "memory_usage" => Some(self.memory_usage as f64),
+            "disk_usage" => Some(self.disk_usage as f64),
+            "network_io" => Some(self.network_io as f64),
+            "active_processes" => Some(self.active_processes as f64),
+            "uptime_seconds" => Some(self.uptime_seconds as f64),
+            "swap_used" => Some(self.swap_used as f64),
+            "load_average" => self.load_average.as_ref().map(|load| load.one),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, name: &str, value: f64) {
+        match name {
+            "cpu_usage" => self.cpu_usage = value as f32,
+            "memory_usage" => self.memory_usage = value as f32,
+            "disk_usage" => self.disk_usage = value as u64,
+            "network_io" => self.network_io = value as u64,
+            "active_processes" => self.active_processes = value as usize,
+            "uptime_seconds" => self.uptime_seconds = value as u64,
+            "swap_used" => self.swap_used = value as u64,
+            "load_average" => self.load_average.get_or_insert_with(LoadAverage::default).one = value,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CoreUsage {
+    pub name: String,
+    pub usage: f32,
+    pub frequency_mhz: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    // Percent of one core, so a busy multi-threaded process can exceed 100
+    pub cpu_usage: f32,
+    pub memory: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CpuBreakdown {
+    pub total: f32,
+    pub cores: Vec<CoreUsage>,
+    // Busiest first
+    pub processes: Vec<ProcessUsage>,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Cumulative byte counters from one reading
+struct Counters {
+    at: Instant,
+    network: HashMap<String, (u64, u64)>,
+    disk_io: HashMap<String, (u64, u64)>,
+}
+
+// Keeps the previous reading so network and disk I/O are reported as rates over the interval
+// between two samples rather than as cumulative counters
+pub struct MetricsSampler {
+    system: System,
+    previous: Counters,
+    lists_refreshed: Instant,
+    latest: Option<SystemMetrics>,
+}
+
+impl Default for MetricsSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSampler {
+    pub fn new() -> Self {
+        let system = System::new_all();
+        let previous = Self::counters(&system);
+        Self {
+            system,
+            previous,
+            lists_refreshed: Instant::now(),
+            latest: None,
+        }
+    }
+
+    // How long until a new sample would cover a full window
+    fn ready_in(&self) -> std::time::Duration {
+        SAMPLE_WINDOW.saturating_sub(self.previous.at.elapsed())
+    }
+
+    // Refreshes only what the metrics read, instead of everything sysinfo knows
+    fn refresh(&mut self) {
+        self.system.refresh_cpu();
+        self.system.refresh_memory();
+        self.system.refresh_processes();
+        self.system.refresh_networks();
+        if self.lists_refreshed.elapsed() >= LIST_REFRESH_INTERVAL {
+            self.system.refresh_disks_list();
+            self.system.refresh_networks_list();
+            self.system.refresh_components_list();
+            self.lists_refreshed = Instant::now();
+        } else {
+            self.system.refresh_disks();
+            self.system.refresh_components();
+        }
+    }
+
+    fn counters(system: &System) -> Counters {
+        Counters {
+            at: Instant::now(),
+            network: system
+                .networks()
+                .iter()
+                .map(|(name, data)| (name.clone(), (data.total_received(), data.total_transmitted())))
+                .collect(),
+            disk_io: std::fs::read_to_string("/proc/diskstats")
+                .map(|text| parse_diskstats(&text))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn sample(&mut self) -> SystemMetrics {
+        self.refresh();
+        let system = &self.system;
+        let current = Self::counters(system);
+        let previous = &self.previous;
+        let elapsed = current.at.duration_since(self.previous.at).as_secs_f64();
+
+        // Average across all cores
+        let cpus = system.cpus();
+        let cpu_usage = if cpus.is_empty() {
+            0.0
+        } else {
+            cpus.iter().map(|cpu| cpu.cpu_usage()).sum::<f32>() / cpus.len() as f32
+        };
+
+        let total_memory = system.total_memory();
+        let memory_usage = if total_memory > 0 {
+            (system.used_memory() as f32 / total_memory as f32) * 100.0
+        } else {
+            0.0
+        };
+
+        let mut network_interfaces: Vec<NetworkInterfaceMetrics> = current
+            .network
+            .iter()
+            .map(|(name, &(received, transmitted))| {
+                let before = previous.network.get(name);
+                NetworkInterfaceMetrics {
+                    name: name.clone(),
+                    received_per_second: per_second(received, before.map(|b| b.0), elapsed),
+                    transmitted_per_second: per_second(transmitted, before.map(|b| b.1), elapsed),
+                    total_received: received,
+                    total_transmitted: transmitted,
+                }
+            })
+            .collect();
+        network_interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        let network_io = network_interfaces
+            .iter()
+            .map(|interface| interface.received_per_second + interface.transmitted_per_second)
+            .sum::<f64>() as u64;
+
+        let disks: Vec<DiskMetrics> = system
+            .disks()
+            .iter()
+            .map(|disk| {
+                let name = disk.name().to_string_lossy().to_string();
+                let total_space = disk.total_space();
+                let used_space = total_space.saturating_sub(disk.available_space());
+                let device = name.strip_prefix("/dev/").unwrap_or(&name);
+                let io = current.disk_io.get(device);
+                let before = previous.disk_io.get(device);
+                DiskMetrics {
+                    mount_point: disk.mount_point().display().to_string(),
+                    file_system: String::from_utf8_lossy(disk.file_system()).to_string(),
+                    total_space,
+                    used_space,
+                    usage_percent: if total_space > 0 {
+                        (used_space as f32 / total_space as f32) * 100.0
+                    } else {
+                        0.0
+                    },
+                    read_per_second: io.map(|io| per_second(io.0, before.map(|b| b.0), elapsed)),
+                    written_per_second: io.map(|io| per_second(io.1, before.map(|b| b.1), elapsed)),
+                    name,
+                }
+            })
+            .collect();
+        // A device mounted more than once is counted once
+        let mut seen = HashSet::new();
+        let disk_usage = disks
+            .iter()
+            .filter(|disk| seen.insert(disk.name.clone()))
+            .map(|disk| disk.used_space)
+            .sum();
+
+        let load_average = if cfg!(windows) {
+            None
+        } else {
+            let load = system.load_average();
+            Some(LoadAverage {
+                one: load.one,
+                five: load.five,
+                fifteen: load.fifteen,
+            })
+        };
+
+        let temperatures = system
+            .components()
+            .iter()
+            .map(|component| TemperatureReading {
+                label: component.label().to_string(),
+                celsius: component.temperature(),
+                critical: component.critical(),
+            })
+            .collect();
+
+        let mut workspaces: BTreeMap<String, WorkspaceMetrics> = BTreeMap::new();
+        for process in system.processes().values() {
+            let Some(workspace) = process_workspace(process) else {
+                continue;
+            };
+            let usage = workspaces.entry(workspace.to_string()).or_insert_with(|| WorkspaceMetrics {
+                workspace: workspace.to_string(),
+                ..WorkspaceMetrics::default()
+            });
+            usage.cpu_usage += process.cpu_usage();
+            usage.memory += process.memory();
+            usage.processes += 1;
+        }
+        for usage in workspaces.values_mut() {
+            if total_memory > 0 {
+                usage.memory_usage = (usage.memory as f32 / total_memory as f32) * 100.0;
+            }
+        }
+
+        let metrics = SystemMetrics {
+            cpu_usage,
+            memory_usage,
+            disk_usage,
+            network_io,
+            active_processes: system.processes().len(),
+            uptime_seconds: system.uptime(),
+            swap_total: system.total_swap(),
+            swap_used: system.used_swap(),
+            load_average,
+            network_interfaces,
+            disks,
+            temperatures,
+            workspaces: workspaces.into_values().collect(),
+            timestamp: Utc::now(),
+        };
+        self.previous = current;
+        self.latest = Some(metrics.clone());
+        metrics
+    }
+
+    // Reads the values of the last refresh
+    pub fn cpu_breakdown(&self, top: usize) -> CpuBreakdown {
+        let cores = self
+            .system
+            .cpus()
+            .iter()
+            .map(|cpu| CoreUsage {
+                name: cpu.name().to_string(),
+                usage: cpu.cpu_usage(),
+                frequency_mhz: cpu.frequency(),
+            })
+            .collect();
+
+        let mut processes: Vec<ProcessUsage> = self
+            .system
+            .processes()
+            .values()
+            .map(|process| ProcessUsage {
+                pid: process.pid().as_u32(),
+                name: process.name().to_string(),
+                cpu_usage: process.cpu_usage(),
+                memory: process.memory(),
+            })
+            .collect();
+        processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+        processes.truncate(top);
+
+        CpuBreakdown {
+            total: self.system.global_cpu_info().cpu_usage(),
+            cores,
+            processes,
+            timestamp: self.latest.as_ref().map(|m| m.timestamp).unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+// One long-lived sampler shared by the collector and the commands, so every reading is measured
+// against the previous refresh rather than against a freshly created `System`
+#[derive(Clone, Default)]
+pub struct SystemMonitor {
+    sampler: Arc<Mutex<MetricsSampler>>,
+}
+
+impl SystemMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The latest sample while it is within the window, otherwise a new one, waiting first if the
+    // last refresh is too recent for CPU usage to be meaningful
+    pub async fn current(&self) -> SystemMetrics {
+        let wait = {
+            let sampler = self.sampler.lock().unwrap();
+            match &sampler.latest {
+                Some(latest) if sampler.ready_in() > std::time::Duration::ZERO => return latest.clone(),
+                _ => sampler.ready_in(),
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        // Refreshing walks every process, so it runs on the blocking pool and the lock is never
+        // held by an async task
+        let sampler = Arc::clone(&self.sampler);
+        tokio::task::spawn_blocking(move || {
+            let mut sampler = sampler.lock().unwrap();
+            match &sampler.latest {
+                // Another caller sampled while this one waited
+                Some(latest) if sampler.ready_in() > std::time::Duration::ZERO => latest.clone(),
+                _ => sampler.sample(),
+            }
+        })
+        .await
+        .expect("metrics sampler panicked")
+    }
+
+    pub async fn cpu_breakdown(&self, top: usize) -> CpuBreakdown {
+        self.current().await;
+        self.sampler.lock().unwrap().cpu_breakdown(top)
+    }
+
+    // Runs `f` against the shared `System` once it holds a current sample
+    pub async fn inspect<R>(&self, f: impl FnOnce(&System) -> R) -> R {
+        self.current().await;
+        f(&self.sampler.lock().unwrap().system)
+    }
+}
+
+// The workspace whose activated shell started the process; only readable for the user's own processes
+pub fn process_workspace(process: &Process) -> Option<&str> {
+    process
+        .environ()
+        .iter()
+        .find_map(|var| var.strip_prefix(WORKSPACE_ENV_VAR)?.strip_prefix('='))
+}
+
+// Usage of the processes working inside each environment's directory, keyed by environment id.
+// A process inside nested environments counts towards the innermost one; an idle environment reads zero.
+fn environment_usage(
+    processes: &[(&Path, f32, u64)],
+    environments: &[(String, PathBuf)],
+    total_memory: u64,
+) -> BTreeMap<String, [(&'static str, f64); 4]> {
+    let mut usage: BTreeMap<&str, (f32, u64, usize)> =
+        environments.iter().map(|(id, _)| (id.as_str(), (0.0, 0, 0))).collect();
+    for (cwd, cpu_usage, memory) in processes {
+        let innermost = environments
+            .iter()
+            .filter(|(_, path)| cwd.starts_with(path))
+            .max_by_key(|(_, path)| path.components().count());
+        if let Some((id, _)) = innermost {
+            let totals = usage.get_mut(id.as_str()).unwrap();
+            totals.0 += cpu_usage;
+            totals.1 += memory;
+            totals.2 += 1;
+        }
+    }
+    usage
+        .into_iter()
+        .map(|(id, (cpu_usage, memory, processes))| {
+            let memory_usage = if total_memory > 0 { memory as f64 / total_memory as f64 * 100.0 } else { 0.0 };
+            let values = [
+                ("cpu_usage", cpu_usage as f64),
+                ("memory", memory as f64),
+                ("memory_usage", memory_usage),
+                ("processes", processes as f64),
+            ];
+            (id.to_string(), values)
+        })
+        .collect()
+}
+
+// Counters can go backwards when an interface is reset; that interval counts as idle
+fn per_second(current: u64, previous: Option<u64>, elapsed: f64) -> f64 {
+    match previous {
+        Some(previous) if elapsed > 0.0 => current.saturating_sub(previous) as f64 / elapsed,
+        _ => 0.0,
+    }
+}
+
+// Bytes read and written per device, from the sector counts in /proc/diskstats
+fn parse_diskstats(text: &str) -> HashMap<String, (u64, u64)> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let sectors_read: u64 = fields.get(5)?.parse().ok()?;
+            let sectors_written: u64 = fields.get(9)?.parse().ok()?;
+            Some((
+                fields[2].to_string(),
+                (sectors_read * DISKSTATS_SECTOR_SIZE, sectors_written * DISKSTATS_SECTOR_SIZE),
+            ))
+        })
+        .collect()
+}
+
+// Samples system metrics into `monitoring_data` on the configured interval and deletes rows past retention
+pub struct MetricsCollector {
+    store: ConfigStore,
+    config: MonitoringConfig,
+    monitor: SystemMonitor,
+    last_retention: Option<DateTime<Utc>>,
+    last_compaction: Option<DateTime<Utc>>,
+    alerts: Option<AlertEvaluator>,
+}
+
+impl MetricsCollector {
+    pub fn new(store: ConfigStore, config: MonitoringConfig, monitor: SystemMonitor) -> Self {
+        Self {
+            store,
+            config,
+            monitor,
+            last_retention: None,
+            last_compaction: None,
+            alerts: None,
+        }
+    }
+
+    // Alert rules are evaluated against every sample
+    pub fn with_alerts(mut self, alerts: AlertEvaluator) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    // Returns None when monitoring is disabled
+    pub fn start(mut self) -> Option<tauri::async_runtime::JoinHandle<()>> {
+        if !self.config.enabled {
+            return None;
+        }
+        let period = std::time::Duration::from_secs(self.config.collection_interval_seconds).max(SAMPLE_WINDOW);
+
+        Some(tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                // A command may have sampled just before the tick; going through `current` keeps the
+                // CPU delta a full window long
+                let metrics = self.monitor.current().await;
+                if let Err(e) = self.record(&metrics).await {
+                    tracing::warn!("Failed to store metrics: {}", e);
+                }
+                if let Err(e) = self.record_environments(&metrics).await {
+                    tracing::warn!("Failed to store environment metrics: {}", e);
+                }
+                if let Err(e) = self.apply_retention(metrics.timestamp).await {
+                    tracing::warn!("Failed to apply metrics retention: {}", e);
+                }
+                if let Err(e) = self.compact(metrics.timestamp).await {
+                    tracing::warn!("Failed to compact metrics: {}", e);
+                }
+                if let Some(alerts) = self.alerts.as_mut() {
+                    if let Err(e) = alerts.evaluate(&metrics).await {
+                        tracing::warn!("Failed to evaluate alert rules: {}", e);
+                    }
+                }
+            }
+        }))
+    }
+
+    pub fn collected_metrics(&self) -> Vec<&'static str> {
+        SYSTEM_METRICS
+            .iter()
+            .copied()
+            .filter(|name| self.config.metrics_to_collect.iter().any(|metric| metric == name))
+            .collect()
+    }
+
+    pub async fn record(&self, metrics: &SystemMetrics) -> Result<usize, CoreError> {
+        let names = self.collected_metrics();
+        for name in &names {
+            if let Some(value) = metrics.value(name) {
+                self.store
+                    .store_monitoring_data_at(None, name, value, None, metrics.timestamp)
+                    .await?;
+            }
+        }
+        Ok(names.len())
+    }
+
+    // Stores the usage of each environment's processes, which environment-scoped alert rules read.
+    // Returns the number of environments recorded.
+    pub async fn record_environments(&self, metrics: &SystemMetrics) -> Result<usize, CoreError> {
+        let environments: Vec<(String, PathBuf)> = self
+            .store
+            .list_environments()
+            .await?
+            .into_iter()
+            .map(|environment| (environment.id, environment.path))
+            .collect();
+        if environments.is_empty() {
+            return Ok(0);
+        }
+
+        let usage = self
+            .monitor
+            .inspect(|system| {
+                let processes: Vec<(&Path, f32, u64)> = system
+                    .processes()
+                    .values()
+                    .map(|process| (process.cwd(), process.cpu_usage(), process.memory()))
+                    .collect();
+                environment_usage(&processes, &environments, system.total_memory())
+            })
+            .await;
+        for (id, values) in &usage {
+            for (name, value) in values {
+                self.store
+                    .store_monitoring_data_at(Some(id), name, *value, None, metrics.timestamp)
+                    .await?;
+            }
+        }
+        Ok(usage.len())
+    }
+
+    // Deletes rows older than `retention_days`, at most once per RETENTION_INTERVAL_MINUTES
+    pub async fn apply_retention(&mut self, now: DateTime<Utc>) -> Result<u64, CoreError> {
+        if let Some(last) = self.last_retention {
+            if now - last < Duration::minutes(RETENTION_INTERVAL_MINUTES) {
+                return Ok(0);
+            }
+        }
+        self.last_retention = Some(now);
+        let cutoff = now - Duration::days(self.config.retention_days as i64);
+        self.store.delete_monitoring_data_before(cutoff).await
+    }
+
+    // Rolls raw rows up into 1-minute, 1-hour and 1-day buckets, at most once per COMPACTION_INTERVAL_MINUTES
+    pub async fn compact(&mut self, now: DateTime<Utc>) -> Result<usize, CoreError> {
+        if let Some(last) = self.last_compaction {
+            if now - last < Duration::minutes(COMPACTION_INTERVAL_MINUTES) {
+                return Ok(0);
+            }
+        }
+        self.last_compaction = Some(now);
+        crate::metrics_rollup::compact(&self.store, now, self.config.retention_days).await
+    }
+}
+
+// The latest `limit` samples, oldest first
+pub async fn metrics_history(store: &ConfigStore, limit: usize) -> Result<Vec<SystemMetrics>, CoreError> {
+    let rows = store
+        .get_system_monitoring_data(Some((limit.min(MAX_HISTORY_SAMPLES) * SYSTEM_METRICS.len()) as i64))
+        .await?;
+
+    let mut samples: BTreeMap<DateTime<Utc>, SystemMetrics> = BTreeMap::new();
+    for (name, value, timestamp) in rows {
+        samples
+            .entry(timestamp)
+            .or_insert_with(|| SystemMetrics {
+                timestamp,
+                ..SystemMetrics::default()
+            })
+            .set(&name, value);
+    }
+
+    let skip = samples.len().saturating_sub(limit);
+    Ok(samples.into_values().skip(skip).collect())
+}
+
+#[command]
+pub async fn get_system_metrics(monitor: State<'_, SystemMonitor>) -> Result<SystemMetrics, String> {
+    Ok(monitor.current().await)
+}
+
+// Per-core usage and the `top` busiest processes (20 by default)
+#[command]
+pub async fn get_cpu_breakdown(monitor: State<'_, SystemMonitor>, top: Option<usize>) -> Result<CpuBreakdown, String> {
+    Ok(monitor.cpu_breakdown(top.unwrap_or(DEFAULT_TOP_PROCESSES)).await)
+}
+
+#[command]
+pub async fn get_historical_metrics(store: State<'_, ConfigStore>, limit: usize) -> Result<Vec<SystemMetrics>, String> {
+    metrics_history(&store, limit).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_collector_records_history_and_applies_retention() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+        let config = MonitoringConfig {
+            retention_days: 7,
+            metrics_to_collect: vec!["cpu_usage".to_string(), "memory_usage".to_string(), "build_times".to_string()],
+            ..MonitoringConfig::default()
+        };
+        let mut collector = MetricsCollector::new(store.clone(), config, SystemMonitor::new());
+        assert_eq!(collector.collected_metrics(), vec!["cpu_usage", "memory_usage"]);
+
+        let now = Utc::now();
+        for (age, cpu) in [(10, 90.0), (2, 40.0), (1, 50.0), (0, 60.0)] {
+            let metrics = SystemMetrics {
+                cpu_usage: cpu,
+                memory_usage: 25.0,
+                active_processes: 100,
+                timestamp: now - Duration::days(age),
+                ..SystemMetrics::default()
+            };
+            assert_eq!(collector.record(&metrics).await.unwrap(), 2);
+        }
+
+        // Metrics outside `metrics_to_collect` are not stored
+        let history = metrics_history(&store, 3).await.unwrap();
+        let cpu: Vec<f32> = history.iter().map(|m| m.cpu_usage).collect();
+        assert_eq!(cpu, vec![40.0, 50.0, 60.0]);
+        assert!(history.iter().all(|m| m.memory_usage == 25.0 && m.active_processes == 0));
+
+        assert_eq!(metrics_history(&store, 10).await.unwrap().len(), 4);
+        assert_eq!(metrics_history(&store, usize::MAX).await.unwrap().len(), 4);
+
+        assert_eq!(collector.apply_retention(now).await.unwrap(), 2);
+        assert_eq!(metrics_history(&store, 10).await.unwrap().len(), 3);
+        // Not due again until the retention interval has passed
+        assert_eq!(collector.apply_retention(now + Duration::minutes(1)).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_environment_usage_counts_the_innermost_environment() {
+        let environments = vec![
+            ("app".to_string(), PathBuf::from("/src/app")),
+            ("web".to_string(), PathBuf::from("/src/app/web")),
+            ("idle".to_string(), PathBuf::from("/src/idle")),
+        ];
+        let processes = [
+            (Path::new("/src/app"), 10.0, 100),
+            (Path::new("/src/app/web/src"), 20.0, 300),
+            (Path::new("/src/application"), 40.0, 600),
+        ];
+        let usage = environment_usage(&processes, &environments, 1000);
+        assert_eq!(
+            usage["app"],
+            [("cpu_usage", 10.0), ("memory", 100.0), ("memory_usage", 10.0), ("processes", 1.0)]
+        );
+        assert_eq!(usage["web"][3], ("processes", 1.0));
+        assert_eq!(usage["idle"][3], ("processes", 0.0));
+    }
+
+    #[test]
+    fn test_rates_come_from_two_readings() {
+        let stats = "   8       0 sda 1000 0 2048 500 300 0 4096 200 0 600 700\n 259       1 nvme0n1p1 10 0 8 1 2 0 16 1 0 2 2\n";
+        let io = parse_diskstats(stats);
+        assert_eq!(io["sda"], (2048 * 512, 4096 * 512));
+        assert_eq!(io["nvme0n1p1"], (8 * 512, 16 * 512));
+
+        assert_eq!(per_second(3_000, Some(1_000), 2.0), 1_000.0);
+        // No previous reading, or a counter reset, is not reported as traffic
+        assert_eq!(per_second(3_000, None, 2.0), 0.0);
+        assert_eq!(per_second(500, Some(1_000), 2.0), 0.0);
+
+        let mut sampler = MetricsSampler::new();
+        let metrics = sampler.sample();
+        assert!(metrics.disks.iter().all(|disk| disk.used_space <= disk.total_space));
+        assert!(metrics.swap_used <= metrics.swap_total);
+    }
+
+    #[tokio::test]
+    async fn test_monitor_reuses_samples_within_the_window() {
+        let monitor = SystemMonitor::new();
+        let started = Instant::now();
+        let first = monitor.current().await;
+        // The first reading waits for a full window after the baseline refresh
+        assert!(started.elapsed() >= SAMPLE_WINDOW - std::time::Duration::from_millis(50));
+        assert_eq!(monitor.current().await.timestamp, first.timestamp);
+
+        let breakdown = monitor.cpu_breakdown(5).await;
+        assert!(breakdown.processes.len() <= 5);
+        assert!(breakdown.processes.windows(2).all(|pair| pair[0].cpu_usage >= pair[1].cpu_usage));
+        assert!(breakdown.cores.iter().all(|core| (0.0..=100.0).contains(&core.usage)));
+    }
+}
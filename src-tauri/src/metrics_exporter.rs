@@ -0,0 +1,316 @@
+use std::fmt::Write as _;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::metrics_collector::{SystemMetrics, SystemMonitor};
+use crate::models::MonitoringConfig;
+
+pub const METRICS_PATH: &str = "/metrics";
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+// Scrapers send small requests; anything longer, or slower, is dropped
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Serves the shared sampler's metrics on 127.0.0.1 for Prometheus-compatible scrapers
+pub struct MetricsExporter {
+    config: MonitoringConfig,
+    monitor: SystemMonitor,
+}
+
+impl MetricsExporter {
+    pub fn new(config: MonitoringConfig, monitor: SystemMonitor) -> Self {
+        Self { config, monitor }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.config.exporter_port))
+    }
+
+    // Returns None unless both monitoring and the exporter are enabled
+    pub fn start(self) -> Option<tauri::async_runtime::JoinHandle<()>> {
+        if !self.config.enabled || !self.config.exporter_enabled {
+            return None;
+        }
+        Some(tauri::async_runtime::spawn(async move {
+            match TcpListener::bind(self.address()).await {
+                Ok(listener) => self.serve(listener).await,
+                Err(e) => tracing::error!("Failed to start metrics exporter on {}: {}", self.address(), e),
+            }
+        }))
+    }
+
+    pub async fn serve(self, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Metrics exporter failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let monitor = self.monitor.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(REQUEST_TIMEOUT, respond(stream, monitor)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::warn!("Metrics exporter request failed: {}", e),
+                    Err(_) => tracing::warn!("Metrics exporter request timed out"),
+                }
+            });
+        }
+    }
+}
+
+async fn respond(stream: TcpStream, monitor: SystemMonitor) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Headers are read and ignored; the exporter only ever serves one format
+    let mut head_bytes = request_line.len();
+    loop {
+        let mut header = String::new();
+        let read = reader.read_line(&mut header).await?;
+        head_bytes += read;
+        if read == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if head_bytes > MAX_REQUEST_HEAD_BYTES {
+            return write_response(reader.get_mut(), "431 Request Header Fields Too Large", "text/plain", "").await;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    let stream = reader.get_mut();
+    match (method, path) {
+        ("GET", METRICS_PATH) => {
+            let body = render_openmetrics(&monitor.current().await);
+            write_response(stream, "200 OK", OPENMETRICS_CONTENT_TYPE, &body).await
+        }
+        ("GET", _) => write_response(stream, "404 Not Found", "text/plain", "Metrics are served at /metrics\n").await,
+        _ => write_response(stream, "405 Method Not Allowed", "text/plain", "").await,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// One metric family in OpenMetrics text format
+struct Family<'a> {
+    out: &'a mut String,
+    name: &'a str,
+}
+
+impl<'a> Family<'a> {
+    fn new(out: &'a mut String, name: &'a str, kind: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        Self { out, name }
+    }
+
+    fn sample(&mut self, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.sample_named("", labels, value)
+    }
+
+    // Counters are exposed as `<family>_total`
+    fn sample_named(&mut self, suffix: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        let _ = write!(self.out, "{}{}", self.name, suffix);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+        self
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Renders a sample, including per-workspace usage, as an OpenMetrics exposition
+pub fn render_openmetrics(metrics: &SystemMetrics) -> String {
+    let mut out = String::new();
+
+    Family::new(&mut out, "nuffi_cpu_usage_percent", "gauge", "CPU usage across all cores.")
+        .sample(&[], metrics.cpu_usage as f64);
+    Family::new(&mut out, "nuffi_memory_usage_percent", "gauge", "Share of physical memory in use.")
+        .sample(&[], metrics.memory_usage as f64);
+    Family::new(&mut out, "nuffi_swap_total_bytes", "gauge", "Swap space.")
+        .sample(&[], metrics.swap_total as f64);
+    Family::new(&mut out, "nuffi_swap_used_bytes", "gauge", "Swap space in use.")
+        .sample(&[], metrics.swap_used as f64);
+    Family::new(&mut out, "nuffi_processes", "gauge", "Running processes.")
+        .sample(&[], metrics.active_processes as f64);
+    Family::new(&mut out, "nuffi_uptime_seconds", "gauge", "Time since boot.")
+        .sample(&[], metrics.uptime_seconds as f64);
+
+    if let Some(load) = &metrics.load_average {
+        Family::new(&mut out, "nuffi_load_average", "gauge", "System load average.")
+            .sample(&[("window", "1m")], load.one)
+            .sample(&[("window", "5m")], load.five)
+            .sample(&[("window", "15m")], load.fifteen);
+    }
+
+    let mut family = Family::new(&mut out, "nuffi_network_receive_bytes_per_second", "gauge", "Receive rate per interface.");
+    for interface in &metrics.network_interfaces {
+        family.sample(&[("interface", &interface.name)], interface.received_per_second);
+    }
+    let mut family = Family::new(&mut out, "nuffi_network_transmit_bytes_per_second", "gauge", "Transmit rate per interface.");
+    for interface in &metrics.network_interfaces {
+        family.sample(&[("interface", &interface.name)], interface.transmitted_per_second);
+    }
+    let mut family = Family::new(&mut out, "nuffi_network_received_bytes", "counter", "Bytes received per interface.");
+    for interface in &metrics.network_interfaces {
+        family.sample_named("_total", &[("interface", &interface.name)], interface.total_received as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_network_transmitted_bytes", "counter", "Bytes transmitted per interface.");
+    for interface in &metrics.network_interfaces {
+        family.sample_named("_total", &[("interface", &interface.name)], interface.total_transmitted as f64);
+    }
+
+    let mut family = Family::new(&mut out, "nuffi_filesystem_size_bytes", "gauge", "Size of each mounted filesystem.");
+    for disk in &metrics.disks {
+        family.sample(&disk_labels(disk), disk.total_space as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_filesystem_used_bytes", "gauge", "Used space of each mounted filesystem.");
+    for disk in &metrics.disks {
+        family.sample(&disk_labels(disk), disk.used_space as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_disk_read_bytes_per_second", "gauge", "Read rate per mounted device.");
+    for disk in &metrics.disks {
+        if let Some(rate) = disk.read_per_second {
+            family.sample(&disk_labels(disk), rate);
+        }
+    }
+    let mut family = Family::new(&mut out, "nuffi_disk_written_bytes_per_second", "gauge", "Write rate per mounted device.");
+    for disk in &metrics.disks {
+        if let Some(rate) = disk.written_per_second {
+            family.sample(&disk_labels(disk), rate);
+        }
+    }
+
+    let mut family = Family::new(&mut out, "nuffi_temperature_celsius", "gauge", "Sensor temperatures.");
+    for reading in &metrics.temperatures {
+        family.sample(&[("sensor", &reading.label)], reading.celsius as f64);
+    }
+
+    // Processes started from a workspace's activated shell
+    let mut family = Family::new(&mut out, "nuffi_workspace_cpu_usage_percent", "gauge", "CPU usage per workspace, in percent of one core.");
+    for usage in &metrics.workspaces {
+        family.sample(&[("workspace", &usage.workspace)], usage.cpu_usage as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_workspace_memory_bytes", "gauge", "Memory used per workspace.");
+    for usage in &metrics.workspaces {
+        family.sample(&[("workspace", &usage.workspace)], usage.memory as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_workspace_memory_usage_percent", "gauge", "Share of physical memory used per workspace.");
+    for usage in &metrics.workspaces {
+        family.sample(&[("workspace", &usage.workspace)], usage.memory_usage as f64);
+    }
+    let mut family = Family::new(&mut out, "nuffi_workspace_processes", "gauge", "Running processes per workspace.");
+    for usage in &metrics.workspaces {
+        family.sample(&[("workspace", &usage.workspace)], usage.processes as f64);
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn disk_labels(disk: &crate::metrics_collector::DiskMetrics) -> [(&str, &str); 3] {
+    [
+        ("device", disk.name.as_str()),
+        ("mount_point", disk.mount_point.as_str()),
+        ("fs_type", disk.file_system.as_str()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics_collector::{LoadAverage, NetworkInterfaceMetrics, WorkspaceMetrics};
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_render_openmetrics() {
+        let metrics = SystemMetrics {
+            cpu_usage: 12.5,
+            load_average: Some(LoadAverage { one: 1.5, five: 1.0, fifteen: 0.5 }),
+            network_interfaces: vec![NetworkInterfaceMetrics {
+                name: "eth0".to_string(),
+                total_received: 2048,
+                ..NetworkInterfaceMetrics::default()
+            }],
+            workspaces: vec![WorkspaceMetrics {
+                workspace: "say \"hi\"".to_string(),
+                memory: 1024,
+                processes: 2,
+                ..WorkspaceMetrics::default()
+            }],
+            ..SystemMetrics::default()
+        };
+        let text = render_openmetrics(&metrics);
+
+        assert!(text.contains("# TYPE nuffi_cpu_usage_percent gauge\n"));
+        assert!(text.contains("\nnuffi_cpu_usage_percent 12.5\n"));
+        assert!(text.contains("\nnuffi_load_average{window=\"5m\"} 1\n"));
+        assert!(text.contains("# TYPE nuffi_network_received_bytes counter\n"));
+        assert!(text.contains("\nnuffi_network_received_bytes_total{interface=\"eth0\"} 2048\n"));
+        assert!(text.contains("\nnuffi_workspace_processes{workspace=\"say \\\"hi\\\"\"} 2\n"));
+        assert!(text.ends_with("# EOF\n"));
+
+        // Every family is declared once
+        let types: Vec<&str> = text.lines().filter(|line| line.starts_with("# TYPE")).collect();
+        let mut unique = types.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(types.len(), unique.len());
+        assert!(!render_openmetrics(&SystemMetrics::default()).contains("nuffi_load_average"));
+    }
+
+    #[tokio::test]
+    async fn test_serves_metrics_on_loopback() {
+        let config = MonitoringConfig {
+            exporter_enabled: false,
+            ..MonitoringConfig::default()
+        };
+        assert!(MetricsExporter::new(config.clone(), SystemMonitor::new()).start().is_none());
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(MetricsExporter::new(config, SystemMonitor::new()).serve(listener));
+
+        let get = |request: &'static str| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = get("GET /metrics HTTP/1.1\r\nHost: localhost\r\nAccept: application/openmetrics-text\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(OPENMETRICS_CONTENT_TYPE));
+        assert!(response.contains("\nnuffi_memory_usage_percent "));
+        assert!(response.ends_with("# EOF\n"));
+
+        assert!(get("GET / HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 404"));
+        assert!(get("POST /metrics HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 405"));
+    }
+}
@@ -0,0 +1,307 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::{command, State};
+
+use crate::database::ConfigStore;
+use crate::models::{bucket_start_for, ConfigError, CoreError, MetricRollup, RollupResolution};
+
+// A series never has more points than this; longer ranges get a wider step
+const MAX_POINTS: i64 = 1_000;
+
+// Raw rows are read a day at a time while compacting
+const COMPACTION_CHUNK_DAYS: i64 = 1;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MetricPoint {
+    pub timestamp: DateTime<Utc>,
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub p95: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MetricSeries {
+    pub metric_name: String,
+    pub environment_id: Option<String>,
+    // None when the points were computed from raw rows
+    pub resolution: Option<RollupResolution>,
+    pub step_seconds: i64,
+    pub points: Vec<MetricPoint>,
+}
+
+// Coarser rollups are kept longer; daily rollups are never deleted
+pub fn rollup_retention(resolution: RollupResolution, retention_days: u32) -> Option<Duration> {
+    match resolution {
+        RollupResolution::Minute => Some(Duration::days(retention_days as i64)),
+        RollupResolution::Hour => Some(Duration::days(retention_days as i64 * 12)),
+        RollupResolution::Day => None,
+    }
+}
+
+// Writes rollups for every bucket completed since the last compaction, then drops rollups past
+// their retention. Returns how many rollups were written.
+pub async fn compact(store: &ConfigStore, now: DateTime<Utc>, retention_days: u32) -> Result<usize, CoreError> {
+    let mut written = 0;
+    for resolution in RollupResolution::ALL {
+        // The current bucket is still filling up
+        let until = resolution.bucket_start(now);
+        let from = match store.latest_rollup_start(resolution).await? {
+            Some(latest) => latest + resolution.duration(),
+            None => match store.earliest_monitoring_timestamp().await? {
+                Some(earliest) => resolution.bucket_start(earliest),
+                None => continue,
+            },
+        };
+
+        let mut chunk_start = from;
+        while chunk_start < until {
+            let chunk_end = (chunk_start + Duration::days(COMPACTION_CHUNK_DAYS)).min(until);
+            let rows = store.get_monitoring_rows_between(chunk_start, chunk_end).await?;
+            let rollups = summarize(resolution, rows);
+            store.replace_rollups(resolution, chunk_start, chunk_end, &rollups).await?;
+            written += rollups.len();
+            chunk_start = chunk_end;
+        }
+
+        if let Some(keep) = rollup_retention(resolution, retention_days) {
+            store.delete_rollups_before(resolution, now - keep).await?;
+        }
+    }
+    Ok(written)
+}
+
+fn summarize(
+    resolution: RollupResolution,
+    rows: Vec<(Option<String>, String, f64, DateTime<Utc>)>,
+) -> Vec<MetricRollup> {
+    let mut groups: BTreeMap<(Option<String>, String, DateTime<Utc>), Vec<f64>> = BTreeMap::new();
+    for (environment_id, metric_name, value, timestamp) in rows {
+        groups
+            .entry((environment_id, metric_name, resolution.bucket_start(timestamp)))
+            .or_default()
+            .push(value);
+    }
+
+    groups
+        .into_iter()
+        .map(|((environment_id, metric_name, bucket_start), mut values)| MetricRollup {
+            environment_id,
+            metric_name,
+            resolution,
+            bucket_start,
+            count: values.len() as u64,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            avg: values.iter().sum::<f64>() / values.len() as f64,
+            p95: percentile(&mut values, 0.95),
+        })
+        .collect()
+}
+
+// Nearest-rank percentile
+fn percentile(values: &mut [f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let rank = (p * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+// The step actually used, widened so the range fits in MAX_POINTS, and the coarsest resolution
+// that still fits in one step; None means raw rows
+pub fn choose_resolution(range: Duration, step_seconds: i64) -> (Option<RollupResolution>, i64) {
+    let step = step_seconds
+        .max((range.num_seconds() + MAX_POINTS - 1) / MAX_POINTS)
+        .max(1);
+    let resolution = RollupResolution::ALL
+        .into_iter()
+        .rev()
+        .find(|resolution| resolution.seconds() <= step);
+    (resolution, step)
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    values: Vec<f64>,
+    rollup_p95: Option<f64>,
+}
+
+impl Accumulator {
+    fn add(&mut self, count: u64, min: f64, max: f64, sum: f64) {
+        if self.count == 0 {
+            self.min = min;
+            self.max = max;
+        } else {
+            self.min = self.min.min(min);
+            self.max = self.max.max(max);
+        }
+        self.count += count;
+        self.sum += sum;
+    }
+
+    fn add_value(&mut self, value: f64) {
+        self.add(1, value, value, value);
+        self.values.push(value);
+    }
+
+    // p95 can't be merged exactly, so a step spanning several rollups reports the highest of their p95s
+    fn add_rollup(&mut self, rollup: &MetricRollup) {
+        self.add(rollup.count, rollup.min, rollup.max, rollup.avg * rollup.count as f64);
+        self.rollup_p95 = Some(self.rollup_p95.map_or(rollup.p95, |p95| p95.max(rollup.p95)));
+    }
+
+    fn point(mut self, timestamp: DateTime<Utc>) -> MetricPoint {
+        let p95 = match self.rollup_p95 {
+            Some(p95) if self.values.is_empty() => p95,
+            Some(p95) => p95.max(percentile(&mut self.values, 0.95)),
+            None => percentile(&mut self.values, 0.95),
+        };
+        MetricPoint {
+            timestamp,
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            avg: self.sum / self.count.max(1) as f64,
+            p95,
+        }
+    }
+}
+
+// Reads rollups at the chosen resolution and fills the buckets compaction hasn't reached yet from raw rows
+pub async fn metric_series(
+    store: &ConfigStore,
+    environment_id: Option<&str>,
+    metric_name: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    step_seconds: i64,
+) -> Result<MetricSeries, CoreError> {
+    if end <= start {
+        return Err(CoreError::Config(ConfigError::ValidationFailed(
+            "Series end must be after its start".to_string(),
+        )));
+    }
+    let (resolution, step) = choose_resolution(end - start, step_seconds);
+
+    let mut buckets: BTreeMap<DateTime<Utc>, Accumulator> = BTreeMap::new();
+    let mut raw_from = start;
+    if let Some(resolution) = resolution {
+        let rollups = store.get_rollups(environment_id, metric_name, resolution, start, end).await?;
+        if let Some(last) = rollups.last() {
+            raw_from = last.bucket_start + resolution.duration();
+        }
+        for rollup in &rollups {
+            buckets
+                .entry(bucket_start_for(rollup.bucket_start, step))
+                .or_default()
+                .add_rollup(rollup);
+        }
+    }
+    if raw_from < end {
+        for (value, timestamp) in store.get_monitoring_values(environment_id, metric_name, raw_from, end).await? {
+            buckets.entry(bucket_start_for(timestamp, step)).or_default().add_value(value);
+        }
+    }
+
+    Ok(MetricSeries {
+        metric_name: metric_name.to_string(),
+        environment_id: environment_id.map(str::to_string),
+        resolution,
+        step_seconds: step,
+        points: buckets
+            .into_iter()
+            .map(|(timestamp, accumulator)| accumulator.point(timestamp))
+            .collect(),
+    })
+}
+
+// Without a step, the range is split into at most MAX_POINTS points
+#[command]
+pub async fn get_metric_series(
+    store: State<'_, ConfigStore>,
+    metric_name: String,
+    environment_id: Option<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    step_seconds: Option<i64>,
+) -> Result<MetricSeries, String> {
+    metric_series(&store, environment_id.as_deref(), &metric_name, start, end, step_seconds.unwrap_or(0))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_compaction_and_series_resolution() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+        let base = DateTime::parse_from_rfc3339("2024-03-10T00:00:00Z").unwrap().with_timezone(&Utc);
+
+        // Three hours of samples every 30 seconds, valued by their index
+        for i in 0..360 {
+            store
+                .store_monitoring_data_at(None, "cpu_usage", i as f64, None, base + Duration::seconds(i * 30))
+                .await
+                .unwrap();
+        }
+        let now = base + Duration::minutes(190);
+        assert_eq!(compact(&store, now, 30).await.unwrap(), 180 + 3);
+        // Nothing new to compact, and the unfinished day has no rollup yet
+        assert_eq!(compact(&store, now, 30).await.unwrap(), 0);
+        assert!(store.latest_rollup_start(RollupResolution::Day).await.unwrap().is_none());
+
+        let hours = store
+            .get_rollups(None, "cpu_usage", RollupResolution::Hour, base, now)
+            .await
+            .unwrap();
+        assert_eq!(hours.len(), 3);
+        assert_eq!((hours[0].count, hours[0].min, hours[0].max, hours[0].avg, hours[0].p95), (120, 0.0, 119.0, 59.5, 113.0));
+
+        // Samples after the last compacted hour come from raw rows
+        store
+            .store_monitoring_data_at(None, "cpu_usage", 1000.0, None, base + Duration::minutes(185))
+            .await
+            .unwrap();
+        let series = metric_series(&store, None, "cpu_usage", base, now, 3600).await.unwrap();
+        assert_eq!(series.resolution, Some(RollupResolution::Hour));
+        let counts: Vec<u64> = series.points.iter().map(|p| p.count).collect();
+        assert_eq!(counts, vec![120, 120, 120, 1]);
+        assert_eq!(series.points[3].max, 1000.0);
+
+        let series = metric_series(&store, None, "cpu_usage", base, base + Duration::hours(1), 60).await.unwrap();
+        assert_eq!(series.resolution, Some(RollupResolution::Minute));
+        assert_eq!(series.points.len(), 60);
+        assert_eq!((series.points[0].avg, series.points[0].p95), (0.5, 1.0));
+
+        // Steps spanning several rollups merge them
+        let series = metric_series(&store, None, "cpu_usage", base, base + Duration::hours(1), 600).await.unwrap();
+        assert_eq!(series.points.len(), 6);
+        assert_eq!((series.points[0].count, series.points[0].min, series.points[0].max), (20, 0.0, 19.0));
+
+        let series = metric_series(&store, None, "cpu_usage", base, base + Duration::minutes(5), 30).await.unwrap();
+        assert_eq!(series.resolution, None);
+        assert_eq!(series.points.len(), 10);
+
+        assert!(metric_series(&store, None, "cpu_usage", now, base, 60).await.is_err());
+    }
+
+    #[test]
+    fn test_choose_resolution() {
+        assert_eq!(choose_resolution(Duration::hours(1), 10), (None, 10));
+        assert_eq!(choose_resolution(Duration::days(30), 60), (Some(RollupResolution::Minute), 2592));
+        assert_eq!(choose_resolution(Duration::days(365), 0), (Some(RollupResolution::Hour), 31536));
+        assert_eq!(choose_resolution(Duration::days(30), 86_400), (Some(RollupResolution::Day), 86_400));
+    }
+}
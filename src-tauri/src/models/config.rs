@@ -5,6 +5,24 @@ use chrono::{DateTime, Utc};
 
 use super::error::ConfigError;
 
+// Read from the app's config directory by the desktop app
+pub const APP_CONFIG_FILE_NAME: &str = "config.toml";
+
+// Must match `identifier` in tauri.conf.json, so tools running outside the app find its files
+pub const APP_IDENTIFIER: &str = "com.nuffi.workspace-manager";
+
+// The directories Tauri resolves for the app, for callers without an app handle
+pub fn default_app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+pub fn default_app_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+// Port of the metrics exporter unless `exporter_port` says otherwise
+pub const DEFAULT_EXPORTER_PORT: u16 = 9464;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub url: String,
@@ -41,6 +59,15 @@ pub struct MonitoringConfig {
     pub collection_interval_seconds: u64,
     pub retention_days: u32,
     pub metrics_to_collect: Vec<String>,
+    // Serves metrics in OpenMetrics text format on 127.0.0.1; off unless enabled
+    #[serde(default)]
+    pub exporter_enabled: bool,
+    #[serde(default = "default_exporter_port")]
+    pub exporter_port: u16,
+}
+
+fn default_exporter_port() -> u16 {
+    DEFAULT_EXPORTER_PORT
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +139,8 @@ impl Default for MonitoringConfig {
                 "build_times".to_string(),
                 "test_results".to_string(),
             ],
+            exporter_enabled: false,
+            exporter_port: DEFAULT_EXPORTER_PORT,
         }
     }
 }
@@ -323,6 +352,10 @@ impl AppConfig {
             return Err(ConfigError::ValidationFailed("Monitoring collection interval must be greater than 0".to_string()));
         }
 
+        if self.monitoring.exporter_enabled && self.monitoring.exporter_port == 0 {
+            return Err(ConfigError::ValidationFailed("Metrics exporter port must be greater than 0".to_string()));
+        }
+
         // Validate workflow config
         if self.workflow.max_concurrent_executions == 0 {
             return Err(ConfigError::ValidationFailed("Max concurrent executions must be greater than 0".to_string()));
@@ -462,6 +495,16 @@ mod tests {
         assert_eq!(monitoring_config.retention_days, 30);
         assert_eq!(monitoring_config.metrics_to_collect.len(), 5);
         assert!(monitoring_config.metrics_to_collect.contains(&"cpu_usage".to_string()));
+        assert!(!monitoring_config.exporter_enabled);
+        assert_eq!(monitoring_config.exporter_port, DEFAULT_EXPORTER_PORT);
+
+        // Configs written before the exporter existed still load
+        let monitoring_config: MonitoringConfig = toml::from_str(
+            "enabled = true\ncollection_interval_seconds = 30\nretention_days = 7\nmetrics_to_collect = []\n",
+        )
+        .unwrap();
+        assert!(!monitoring_config.exporter_enabled);
+        assert_eq!(monitoring_config.exporter_port, DEFAULT_EXPORTER_PORT);
     }
 
     #[test]
@@ -680,7 +723,13 @@ mod tests {
         config = AppConfig::default();
         config.monitoring.collection_interval_seconds = 0;
         assert!(config.validate().is_err());
-        
+
+        // Reset and test an enabled exporter without a port
+        config = AppConfig::default();
+        config.monitoring.exporter_enabled = true;
+        config.monitoring.exporter_port = 0;
+        assert!(config.validate().is_err());
+
         // Reset and test invalid workflow config
         config = AppConfig::default();
         config.workflow.max_concurrent_executions = 0;
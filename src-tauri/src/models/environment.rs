@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
@@ -97,6 +97,26 @@ pub struct ValidationResult {
     pub errors: Vec<String>,
 }
 
+impl ProjectType {
+    // Guessed from the manifests in `path`; Mixed when there are several kinds or none
+    pub fn detect(path: &Path) -> Self {
+        let mut found = Vec::new();
+        if path.join("package.json").exists() {
+            found.push(ProjectType::NodeJS);
+        }
+        if path.join("Cargo.toml").exists() {
+            found.push(ProjectType::Rust);
+        }
+        if ["pyproject.toml", "requirements.txt", "setup.py"].iter().any(|name| path.join(name).exists()) {
+            found.push(ProjectType::Python);
+        }
+        match found.as_slice() {
+            [only] => only.clone(),
+            _ => ProjectType::Mixed,
+        }
+    }
+}
+
 impl EnvironmentConfig {
     pub fn new(name: String, path: PathBuf, project_type: ProjectType) -> Self {
         Self {
@@ -284,6 +304,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_project_type_detect() {
+        let dir = tempdir().unwrap();
+        assert_eq!(ProjectType::detect(dir.path()), ProjectType::Mixed);
+
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        assert_eq!(ProjectType::detect(dir.path()), ProjectType::Rust);
+
+        std::fs::write(dir.path().join("requirements.txt"), "requests").unwrap();
+        assert_eq!(ProjectType::detect(dir.path()), ProjectType::Mixed);
+    }
+
     #[test]
     fn test_dependency_type_serialization() {
         let types = vec![
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use super::error::ConfigError;
+
+// Least to most severe, as stored in `application_logs.level`
+pub const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApplicationLog {
+    // None until the entry is stored
+    pub id: Option<i64>,
+    pub level: String,
+    pub message: String,
+    pub module: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LogQuery {
+    // Entries at this level or more severe
+    pub level: Option<String>,
+    // Entries from this module or any module nested in it
+    pub module: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+impl LogQuery {
+    // The levels the query matches, None when it doesn't filter by level
+    pub fn levels(&self) -> Result<Option<&'static [&'static str]>, ConfigError> {
+        let Some(level) = &self.level else {
+            return Ok(None);
+        };
+        let level = level.to_lowercase();
+        LOG_LEVELS
+            .iter()
+            .position(|candidate| *candidate == level)
+            .map(|index| Some(&LOG_LEVELS[index..]))
+            .ok_or_else(|| ConfigError::ValidationFailed(format!("Unknown log level: {}", level)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_query_levels() {
+        let query = LogQuery { level: Some("WARN".to_string()), ..LogQuery::default() };
+        assert_eq!(query.levels().unwrap(), Some(&["warn", "error"][..]));
+        assert_eq!(LogQuery::default().levels().unwrap(), None);
+        assert!(LogQuery { level: Some("loud".to_string()), ..LogQuery::default() }.levels().is_err());
+    }
+}
@@ -3,9 +3,13 @@ pub mod workflow;
 pub mod ai_analysis;
 pub mod config;
 pub mod error;
+pub mod monitoring;
+pub mod logging;
 
 pub use environment::*;
 pub use workflow::*;
 pub use ai_analysis::*;
 pub use config::*;
-pub use error::*;
\ No newline at end of file
+pub use error::*;
+pub use monitoring::*;
+pub use logging::*;
\ No newline at end of file
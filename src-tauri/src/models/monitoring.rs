@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use super::error::ConfigError;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RollupResolution {
+    Minute,
+    Hour,
+    Day,
+}
+
+// Summary of one metric's raw samples within a bucket
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetricRollup {
+    pub environment_id: Option<String>,
+    pub metric_name: String,
+    pub resolution: RollupResolution,
+    pub bucket_start: DateTime<Utc>,
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub p95: f64,
+}
+
+impl RollupResolution {
+    // Finest first
+    pub const ALL: [RollupResolution; 3] = [Self::Minute, Self::Hour, Self::Day];
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 3_600,
+            Self::Day => 86_400,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.seconds())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minute => "1m",
+            Self::Hour => "1h",
+            Self::Day => "1d",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|resolution| resolution.as_str() == value)
+    }
+
+    pub fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        bucket_start_for(timestamp, self.seconds())
+    }
+}
+
+// Start of the `seconds`-long bucket, counted from the Unix epoch, that holds `timestamp`
+pub fn bucket_start_for(timestamp: DateTime<Utc>, seconds: i64) -> DateTime<Utc> {
+    let secs = timestamp.timestamp();
+    DateTime::from_timestamp(secs - secs.rem_euclid(seconds.max(1)), 0).unwrap_or(timestamp)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertComparator {
+    GreaterThan,
+    GreaterOrEqual,
+    LessThan,
+    LessOrEqual,
+}
+
+// What a rule watches: the whole machine, or the processes of one workspace or of one environment's directory
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertScope {
+    Global,
+    Workspace { id: String },
+    Environment { id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertRule {
+    pub id: String,
+    pub name: String,
+    pub metric: String,
+    pub comparator: AlertComparator,
+    pub threshold: f64,
+    // How long the condition has to hold before the alert fires
+    pub duration_seconds: u64,
+    pub scope: AlertScope,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertEvent {
+    pub id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub state: AlertState,
+    pub metric: String,
+    pub scope: AlertScope,
+    pub value: f64,
+    pub threshold: f64,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AlertComparator {
+    pub fn holds(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::GreaterThan => value > threshold,
+            Self::GreaterOrEqual => value >= threshold,
+            Self::LessThan => value < threshold,
+            Self::LessOrEqual => value <= threshold,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::GreaterThan => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::LessThan => "<",
+            Self::LessOrEqual => "<=",
+        }
+    }
+}
+
+impl AlertRule {
+    pub fn new(name: String, metric: String, comparator: AlertComparator, threshold: f64, scope: AlertScope) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            metric,
+            comparator,
+            threshold,
+            duration_seconds: 0,
+            scope,
+            enabled: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn with_duration(mut self, seconds: u64) -> Self {
+        self.duration_seconds = seconds;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.name.trim().is_empty() {
+            return Err(ConfigError::ValidationFailed("Alert rule name cannot be empty".to_string()));
+        }
+        if self.metric.trim().is_empty() {
+            return Err(ConfigError::ValidationFailed("Alert rule metric cannot be empty".to_string()));
+        }
+        if !self.threshold.is_finite() {
+            return Err(ConfigError::ValidationFailed("Alert rule threshold must be a number".to_string()));
+        }
+        match &self.scope {
+            AlertScope::Workspace { id } | AlertScope::Environment { id } if id.trim().is_empty() => {
+                Err(ConfigError::ValidationFailed("Alert rule scope needs an id".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn event(&self, state: AlertState, value: f64, timestamp: DateTime<Utc>) -> AlertEvent {
+        let message = match state {
+            AlertState::Firing => format!(
+                "{}: {} is {:.1} ({} {})",
+                self.name, self.metric, value, self.comparator.symbol(), self.threshold
+            ),
+            AlertState::Resolved => format!("{} resolved: {} is {:.1}", self.name, self.metric, value),
+        };
+        AlertEvent {
+            id: Uuid::new_v4().to_string(),
+            rule_id: self.id.clone(),
+            rule_name: self.name.clone(),
+            state,
+            metric: self.metric.clone(),
+            scope: self.scope.clone(),
+            value,
+            threshold: self.threshold,
+            message,
+            timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_buckets() {
+        let timestamp = DateTime::parse_from_rfc3339("2024-03-10T14:37:52.250Z").unwrap().with_timezone(&Utc);
+        assert_eq!(RollupResolution::Minute.bucket_start(timestamp).to_rfc3339(), "2024-03-10T14:37:00+00:00");
+        assert_eq!(RollupResolution::Hour.bucket_start(timestamp).to_rfc3339(), "2024-03-10T14:00:00+00:00");
+        assert_eq!(RollupResolution::Day.bucket_start(timestamp).to_rfc3339(), "2024-03-10T00:00:00+00:00");
+        assert_eq!(RollupResolution::parse("1h"), Some(RollupResolution::Hour));
+        assert_eq!(RollupResolution::parse("5m"), None);
+    }
+
+    #[test]
+    fn test_alert_rule() {
+        let rule = AlertRule::new(
+            "Memory pressure".to_string(),
+            "memory_usage".to_string(),
+            AlertComparator::GreaterThan,
+            80.0,
+            AlertScope::Workspace { id: "ws-1".to_string() },
+        )
+        .with_duration(300);
+        assert!(rule.validate().is_ok());
+        assert!(rule.comparator.holds(80.5, rule.threshold));
+        assert!(!rule.comparator.holds(80.0, rule.threshold));
+        assert_eq!(rule.event(AlertState::Firing, 91.34, Utc::now()).message, "Memory pressure: memory_usage is 91.3 (> 80)");
+
+        let scope: AlertScope = serde_json::from_str(r#"{"kind":"environment","id":"env-1"}"#).unwrap();
+        assert_eq!(scope, AlertScope::Environment { id: "env-1".to_string() });
+        let unscoped = AlertRule { scope: AlertScope::Workspace { id: " ".to_string() }, ..rule };
+        assert!(unscoped.validate().is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{command, State};
+
+use crate::download_cache::{CachedArtifact, DownloadCache};
+use crate::integrity::parse_checksum;
+use crate::install_planner::{InstallPlan, InstallPlanner, PlanStep};
+use crate::installer::UniversalInstaller;
+use crate::manifest::Platform;
+
+pub const BUNDLE_MANIFEST_NAME: &str = "bundle.json";
+
+// Programs that fetch what they install at install time, so a step running them can't be bundled
+const NETWORK_PROGRAMS: &[&str] = &[
+    "apt", "apt-get", "brew", "cargo", "choco", "code", "curl", "dnf", "gem", "git", "go", "npm", "nvm", "pip", "pip3",
+    "pyenv", "rustup", "snap", "wget", "winget", "yum",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleArtifact {
+    pub tool_id: String,
+    pub url: String,
+    pub digest: String,
+    pub size: u64,
+}
+
+// Describes an offline bundle: the installers it carries, and the tools that still need a network
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleManifest {
+    pub template_id: String,
+    pub platform: Platform,
+    pub artifacts: Vec<BundleArtifact>,
+    // Installed through a package manager or another download at install time
+    #[serde(default)]
+    pub network_steps: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Fetches every direct download of `plan` into the cache and packs them into a .tar.gz archive.
+// Downloads the manifest doesn't pin are only bundled with `allow_unverified`, pinned at the digest
+// they have now. Steps that fetch what they install are listed in the manifest instead.
+pub async fn export_bundle(
+    plan: &InstallPlan,
+    template_id: &str,
+    cache: &DownloadCache,
+    allow_unverified: bool,
+    destination: &Path,
+) -> Result<BundleManifest, String> {
+    let network_steps: Vec<String> = plan.steps.iter().filter(|step| needs_network(step)).map(|step| step.tool_id.clone()).collect();
+
+    let mut artifacts = Vec::new();
+    for step in &plan.steps {
+        if let Some(download) = &step.download {
+            // Signature files travel with the artifacts they sign
+            let artifact = if download.checksum.is_none() && download.signature.is_none() && allow_unverified {
+                cache.fetch_unverified(&download.url).await
+            } else {
+                cache
+                    .fetch(&download.url, download.checksum.as_deref(), download.signature.as_ref())
+                    .await
+                    .map(|fetched| fetched.artifact)
+            };
+            let mut bundled = vec![artifact.map_err(|e| e.to_string())?];
+            if let Some(signature) = &download.signature {
+                let signature_file = cache
+                    .lookup(&signature.url, None)
+                    .ok_or_else(|| format!("{} is missing from the download cache", signature.url))?;
+                bundled.push(signature_file);
+            }
+
+            for artifact in bundled {
+                artifacts.push(BundleArtifact {
+                    tool_id: step.tool_id.clone(),
+                    url: artifact.url,
+                    digest: artifact.digest,
+                    size: artifact.size,
+                });
+            }
+        }
+    }
+    if artifacts.is_empty() {
+        return Err(format!(
+            "{} has nothing to bundle for offline use: {} install through a package manager or the network",
+            template_id,
+            network_steps.join(", ")
+        ));
+    }
+
+    let manifest = BundleManifest {
+        template_id: template_id.to_string(),
+        platform: plan.platform.clone(),
+        artifacts,
+        network_steps,
+        created_at: chrono::Utc::now(),
+    };
+    write_archive(&manifest, cache, destination)?;
+    Ok(manifest)
+}
+
+// Steps without a bundled installer, or that run a package manager or downloader, resolve what they
+// install at install time
+fn needs_network(step: &PlanStep) -> bool {
+    (step.download.is_none() && !step.commands.is_empty())
+        || step.commands.iter().any(|command| {
+            command
+                .display()
+                .split(|c: char| c.is_whitespace() || matches!(c, '|' | ';' | '&' | '(' | ')'))
+                .any(|token| NETWORK_PROGRAMS.contains(&token))
+        })
+}
+
+fn write_archive(manifest: &BundleManifest, cache: &DownloadCache, destination: &Path) -> Result<(), String> {
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let file = std::fs::File::create(destination).map_err(|e| format!("Failed to create {}: {}", destination.display(), e))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let write_error = |e: std::io::Error| format!("Failed to write bundle {}: {}", destination.display(), e);
+
+    let content = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, BUNDLE_MANIFEST_NAME, content.as_slice()).map_err(write_error)?;
+
+    let mut written = Vec::new();
+    for artifact in &manifest.artifacts {
+        if written.contains(&artifact.digest) {
+            continue;
+        }
+        archive
+            .append_path_with_name(cache.object_path(&artifact.digest), format!("sha256/{}", artifact.digest))
+            .map_err(write_error)?;
+        written.push(artifact.digest.clone());
+    }
+
+    archive.into_inner().and_then(|encoder| encoder.finish()).map_err(write_error)?;
+    Ok(())
+}
+
+// Unpacks a bundle into the download cache so its installers are found without a network
+pub fn import_bundle(archive_path: &Path, cache: &DownloadCache) -> Result<BundleManifest, String> {
+    let staging = cache.root().join("partial").join(format!("bundle-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(staging.join("sha256")).map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let result = unpack(archive_path, &staging).and_then(|manifest| {
+        for artifact in &manifest.artifacts {
+            // Every object is hashed again, so a tampered bundle is rejected here
+            let object = staging.join("sha256").join(&artifact.digest);
+            if !object.exists() && cache.lookup(&artifact.url, Some(&artifact.digest)).is_some() {
+                continue;
+            }
+            cache.insert_file(&artifact.url, &object, Some(&artifact.digest)).map_err(|e| e.to_string())?;
+        }
+        Ok(manifest)
+    });
+
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+fn unpack(archive_path: &Path, staging: &Path) -> Result<BundleManifest, String> {
+    let file = std::fs::File::open(archive_path).map_err(|e| format!("Failed to open {}: {}", archive_path.display(), e))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let read_error = |e: std::io::Error| format!("Failed to read bundle {}: {}", archive_path.display(), e);
+    let mut manifest = None;
+
+    for entry in archive.entries().map_err(read_error)? {
+        let mut entry = entry.map_err(read_error)?;
+        let name = entry.path().map_err(read_error)?.to_string_lossy().to_string();
+
+        // Links and special files are refused outright, so nothing is written outside the staging directory
+        match entry.header().entry_type() {
+            tar::EntryType::Regular => {}
+            tar::EntryType::Directory => continue,
+            other => return Err(format!("Unexpected {:?} entry in bundle: {}", other, name)),
+        }
+
+        // Only the manifest and digest-named objects are extracted, never arbitrary paths
+        let target: PathBuf = if name == BUNDLE_MANIFEST_NAME {
+            staging.join(BUNDLE_MANIFEST_NAME)
+        } else if let Some(digest) = name.strip_prefix("sha256/").and_then(parse_checksum) {
+            staging.join("sha256").join(digest)
+        } else {
+            return Err(format!("Unexpected entry in bundle: {}", name));
+        };
+        entry.unpack(&target).map_err(read_error)?;
+
+        if name == BUNDLE_MANIFEST_NAME {
+            let content = std::fs::read_to_string(&target).map_err(read_error)?;
+            manifest = Some(serde_json::from_str(&content).map_err(|e| format!("Invalid bundle manifest: {}", e))?);
+        }
+    }
+
+    manifest.ok_or_else(|| format!("{} has no {}", archive_path.display(), BUNDLE_MANIFEST_NAME))
+}
+
+// Tauri commands
+#[command]
+pub async fn export_offline_bundle(
+    installer: State<'_, UniversalInstaller>,
+    template_id: String,
+    destination: String,
+    platform: Option<Platform>,
+    allow_unverified: Option<bool>,
+) -> Result<BundleManifest, String> {
+    let plan = InstallPlanner::new(installer.registry(), platform.unwrap_or_else(Platform::current)).plan_template(&template_id)?;
+    export_bundle(&plan, &template_id, &installer.download_cache(), allow_unverified.unwrap_or(false), Path::new(&destination)).await
+}
+
+#[command]
+pub async fn import_offline_bundle(installer: State<'_, UniversalInstaller>, path: String) -> Result<BundleManifest, String> {
+    let cache = installer.download_cache();
+    tokio::task::spawn_blocking(move || import_bundle(Path::new(&path), &cache))
+        .await
+        .map_err(|e| format!("Bundle import failed: {}", e))?
+}
+
+#[command]
+pub async fn get_download_cache(installer: State<'_, UniversalInstaller>) -> Result<Vec<CachedArtifact>, String> {
+    Ok(installer.download_cache().entries())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install_planner::PlannedDownload;
+    use crate::installer::InstallCommand;
+    use tempfile::tempdir;
+
+    fn step(tool_id: &str, download: Option<PlannedDownload>) -> PlanStep {
+        PlanStep { download, ..PlanStep::for_tool(tool_id) }
+    }
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let dir = tempdir().unwrap();
+        let online = DownloadCache::new(dir.path().join("online"));
+        let installer_file = dir.path().join("tool-a.deb");
+        std::fs::write(&installer_file, b"tool-a installer").unwrap();
+        let url = "https://x.test/tool-a.deb";
+        let artifact = online.insert_file(url, &installer_file, None).unwrap();
+        let checksum = format!("sha256:{}", artifact.digest);
+
+        let mut tool_a = step("tool-a", Some(PlannedDownload { url: url.to_string(), checksum: Some(checksum.clone()), signature: None, destination: String::new() }));
+        tool_a.commands = vec![InstallCommand::new("sudo", &["dpkg", "-i", "tool-a.deb"])];
+        let mut tool_b = step("tool-b", None);
+        tool_b.commands = vec![InstallCommand::new("sudo", &["apt-get", "install", "-y", "tool-b"])];
+        let mut plan = InstallPlan {
+            id: "plan-1".to_string(),
+            platform: Platform::current(),
+            steps: vec![tool_a, tool_b],
+            stages: vec![vec!["tool-a".to_string(), "tool-b".to_string()]],
+            skipped: Vec::new(),
+            unresolved: Vec::new(),
+            conflicts: Vec::new(),
+            created_at: chrono::Utc::now(),
+        };
+
+        // The artifact is already cached, so exporting needs no network; apt still does on the offline machine
+        let archive = dir.path().join("bundle.tar.gz");
+        let manifest = export_bundle(&plan, "backend", &online, false, &archive).await.unwrap();
+        assert_eq!(manifest.network_steps, vec!["tool-b".to_string()]);
+
+        // A bundle with nothing in it isn't made at all
+        plan.steps.remove(0);
+        plan.stages = vec![vec!["tool-b".to_string()]];
+        let empty = dir.path().join("empty.tar.gz");
+        let error = export_bundle(&plan, "backend", &online, false, &empty).await.unwrap_err();
+        assert!(error.contains("tool-b"));
+        assert!(!empty.exists());
+
+        let offline = DownloadCache::new(dir.path().join("offline"));
+        let imported = import_bundle(&archive, &offline).unwrap();
+        assert_eq!(imported.artifacts[0].digest, artifact.digest);
+
+        let destination = dir.path().join("downloads").join("tool-a.deb");
+        offline.materialize(url, Some(&checksum), None, false, &destination).await.unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"tool-a installer");
+    }
+
+    #[tokio::test]
+    async fn test_export_shipped_template() {
+        let dir = tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let registry = crate::manifest::ManifestRegistry::bundled();
+        let windows = Platform { os: "windows".to_string(), distro: None, distro_like: Vec::new() };
+        let plan = InstallPlanner::new(&registry, windows).plan_template("backend-complete").unwrap();
+
+        // The shipped installers aren't pinned, so a copy downloaded earlier stands in for the network
+        let mut urls = Vec::new();
+        for step in plan.steps.iter().filter(|step| step.download.is_some()) {
+            let file = dir.path().join(&step.tool_id);
+            std::fs::write(&file, format!("{} installer", step.tool_id)).unwrap();
+            let url = step.download.as_ref().unwrap().url.clone();
+            cache.insert_file(&url, &file, None).unwrap();
+            urls.push(url);
+        }
+
+        let archive = dir.path().join("backend.tar.gz");
+        assert!(export_bundle(&plan, "backend-complete", &cache, false, &archive).await.unwrap_err().contains("no checksum"));
+        let manifest = export_bundle(&plan, "backend-complete", &cache, true, &archive).await.unwrap();
+        let bundled: Vec<&str> = manifest.artifacts.iter().map(|artifact| artifact.tool_id.as_str()).collect();
+        assert_eq!(bundled, vec!["vscode", "docker-desktop"]);
+        assert!(manifest.network_steps.contains(&"git".to_string()));
+
+        // Offline, the opt-in installs the copy the bundle pinned
+        let offline = DownloadCache::new(dir.path().join("offline"));
+        import_bundle(&archive, &offline).unwrap();
+        let destination = dir.path().join("downloads").join("vscode.exe");
+        let fetched = offline.materialize(&urls[0], None, None, true, &destination).await.unwrap();
+        assert_eq!(fetched.artifact.digest, manifest.artifacts[0].digest);
+        assert_eq!(std::fs::read(&destination).unwrap(), b"vscode installer");
+    }
+
+    #[test]
+    fn test_import_rejects_links() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("bundle.tar.gz");
+        let file = std::fs::File::create(&archive_path).unwrap();
+        let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o644);
+        archive.append_link(&mut header, format!("sha256/{}", "a".repeat(64)), "/etc/passwd").unwrap();
+        archive.into_inner().unwrap().finish().unwrap();
+
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let error = import_bundle(&archive_path, &cache).unwrap_err();
+        assert!(error.contains("Symlink"));
+        assert!(cache.entries().is_empty());
+    }
+}
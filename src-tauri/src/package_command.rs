@@ -0,0 +1,179 @@
+use crate::installer::InstallCommand;
+use crate::version_manager::{nvm_args, nvm_command};
+
+// A package-manager install parsed out of an install command
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInstall {
+    pub manager: String,
+    pub packages: Vec<String>,
+    sudo: bool,
+    cask: bool,
+}
+
+impl PackageInstall {
+    pub fn parse(command: &InstallCommand) -> Option<Self> {
+        // nvm commands are wrapped in bash to source nvm.sh; the wrapper itself is not part of the install
+        let line = nvm_args(command).map(|args| args.join(" ")).unwrap_or_else(|| command.display());
+
+        // Pipelines and substitutions can't be reversed reliably
+        if line.contains(['|', ';', '&', '>', '$', '`']) {
+            return None;
+        }
+
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        let sudo = tokens.first() == Some(&"sudo");
+        if sudo {
+            tokens.remove(0);
+            tokens.retain(|t| *t != "-E");
+        }
+
+        let (manager, rest) = match tokens.as_slice() {
+            ["brew", "install", rest @ ..] => ("brew", rest),
+            ["apt-get" | "apt", "install", rest @ ..] => ("apt", rest),
+            ["dnf" | "yum", "install", rest @ ..] => ("dnf", rest),
+            ["choco", "install", rest @ ..] => ("choco", rest),
+            ["npm", "install", "-g", rest @ ..] | ["npm", "install", "--global", rest @ ..] => ("npm", rest),
+            ["pyenv", "install", rest @ ..] => ("pyenv", rest),
+            ["rustup", "toolchain", "install", rest @ ..] => ("rustup", rest),
+            ["nvm", "install", rest @ ..] => ("nvm", rest),
+            ["mise", "install", rest @ ..] => ("mise", rest),
+            ["code", "--install-extension", rest @ ..] => ("code", rest),
+            _ => return None,
+        };
+
+        let packages: Vec<String> = rest
+            .iter()
+            .filter(|t| !t.starts_with('-'))
+            .map(|t| t.to_string())
+            .collect();
+
+        if packages.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            manager: manager.to_string(),
+            packages,
+            sudo,
+            cask: rest.contains(&"--cask"),
+        })
+    }
+
+    // A command that succeeds when the package was already present before the install
+    pub fn probe(&self, package: &str) -> Option<InstallCommand> {
+        let name = self.package_name(package);
+        let probe = match self.manager.as_str() {
+            "brew" if self.cask => InstallCommand::new("brew", &["list", "--cask", name]),
+            "brew" => InstallCommand::new("brew", &["list", "--versions", name]),
+            "apt" => InstallCommand::new("dpkg", &["-s", name]),
+            "dnf" => InstallCommand::new("rpm", &["-q", name]),
+            "npm" => InstallCommand::new("npm", &["ls", "-g", "--depth=0", name]),
+            "pyenv" => InstallCommand::new("pyenv", &["prefix", name]),
+            "nvm" => nvm_command(&["which", name]),
+            "rustup" => InstallCommand::new("rustup", &["which", "--toolchain", name, "rustc"]),
+            "mise" => InstallCommand::new("mise", &["where", name]),
+            _ => return None,
+        };
+        Some(probe)
+    }
+
+    pub fn undo(&self, packages: &[String]) -> InstallCommand {
+        let packages: Vec<&str> = packages.iter().map(|p| self.package_name(p)).collect();
+
+        let mut args: Vec<&str> = match self.manager.as_str() {
+            "brew" if self.cask => vec!["brew", "uninstall", "--cask"],
+            "brew" => vec!["brew", "uninstall"],
+            "apt" => vec!["apt-get", "remove", "-y"],
+            "dnf" => vec!["dnf", "remove", "-y"],
+            "choco" => vec!["choco", "uninstall", "-y"],
+            "npm" => vec!["npm", "uninstall", "-g"],
+            "pyenv" => vec!["pyenv", "uninstall", "-f"],
+            "rustup" => vec!["rustup", "toolchain", "uninstall"],
+            "nvm" => vec!["nvm", "uninstall"],
+            "mise" => vec!["mise", "uninstall"],
+            _ => vec!["code", "--uninstall-extension"],
+        };
+        args.extend(packages);
+
+        if self.manager == "nvm" {
+            return nvm_command(&args[1..]);
+        }
+        if self.sudo {
+            InstallCommand::new("sudo", &args)
+        } else {
+            InstallCommand::new(args[0], &args[1..])
+        }
+    }
+
+    // Upgrades in place; version managers install side by side and have no upgrade
+    pub fn upgrade(&self, packages: &[String]) -> Option<InstallCommand> {
+        let packages: Vec<String> = packages
+            .iter()
+            .map(|p| match self.manager.as_str() {
+                "npm" => format!("{}@latest", self.package_name(p)),
+                _ => p.clone(),
+            })
+            .collect();
+
+        let mut args: Vec<&str> = match self.manager.as_str() {
+            "brew" if self.cask => vec!["brew", "upgrade", "--cask"],
+            "brew" => vec!["brew", "upgrade"],
+            "apt" => vec!["apt-get", "install", "--only-upgrade", "-y"],
+            "dnf" => vec!["dnf", "upgrade", "-y"],
+            "choco" => vec!["choco", "upgrade", "-y"],
+            "npm" => vec!["npm", "install", "-g"],
+            "rustup" => vec!["rustup", "update"],
+            "code" => vec!["code", "--force", "--install-extension"],
+            _ => return None,
+        };
+        args.extend(packages.iter().map(|p| p.as_str()));
+
+        Some(if self.sudo {
+            InstallCommand::new("sudo", &args)
+        } else {
+            InstallCommand::new(args[0], &args[1..])
+        })
+    }
+
+    // npm pins versions as `name@version`; brew formulae like `node@20` are real names
+    fn package_name<'a>(&self, package: &'a str) -> &'a str {
+        match package.rfind('@') {
+            Some(index) if self.manager == "npm" && index > 0 => &package[..index],
+            _ => package,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_package_installs() {
+        let install = PackageInstall::parse(&InstallCommand::shell("sudo apt-get install -y docker-ce containerd.io")).unwrap();
+        assert_eq!(install.manager, "apt");
+        assert_eq!(install.packages, vec!["docker-ce".to_string(), "containerd.io".to_string()]);
+        assert_eq!(install.undo(&install.packages).display(), "sudo apt-get remove -y docker-ce containerd.io");
+
+        let cask = PackageInstall::parse(&InstallCommand::new("brew", &["install", "--cask", "docker"])).unwrap();
+        assert_eq!(cask.undo(&cask.packages).display(), "brew uninstall --cask docker");
+        assert_eq!(cask.probe("docker").unwrap().display(), "brew list --cask docker");
+
+        let extension = PackageInstall::parse(&InstallCommand::shell("code --install-extension ms-python.python")).unwrap();
+        assert_eq!(extension.undo(&extension.packages).display(), "code --uninstall-extension ms-python.python");
+
+        let npm = PackageInstall::parse(&InstallCommand::shell("npm install -g npm@latest @types/node")).unwrap();
+        assert_eq!(npm.probe("npm@latest").unwrap().display(), "npm ls -g --depth=0 npm");
+        assert_eq!(npm.undo(&["@types/node".to_string()]).display(), "npm uninstall -g @types/node");
+
+        assert_eq!(cask.upgrade(&cask.packages).unwrap().display(), "brew upgrade --cask docker");
+        let apt = PackageInstall::parse(&InstallCommand::shell("sudo apt-get install -y nodejs")).unwrap();
+        assert_eq!(apt.upgrade(&apt.packages).unwrap().display(), "sudo apt-get install --only-upgrade -y nodejs");
+        let pyenv = PackageInstall::parse(&InstallCommand::new("pyenv", &["install", "3.12.0"])).unwrap();
+        assert!(pyenv.upgrade(&pyenv.packages).is_none());
+
+        // Pipelines are not reversible
+        assert!(PackageInstall::parse(&InstallCommand::shell("curl -fsSL https://example.com | sudo -E bash -")).is_none());
+        assert!(PackageInstall::parse(&InstallCommand::shell("sudo usermod -aG docker $USER")).is_none());
+    }
+}
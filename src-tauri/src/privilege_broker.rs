@@ -0,0 +1,762 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{command, State};
+
+use crate::command_runner::{split_command_line, CommandError, CommandOutput, CommandSpec};
+use crate::database::Database;
+use crate::installer::UniversalInstaller;
+
+// Common locations of graphical askpass helpers for `sudo -A`
+const ASKPASS_HELPERS: &[&str] = &[
+    "/usr/bin/ssh-askpass",
+    "/usr/lib/ssh/ssh-askpass",
+    "/usr/lib/openssh/gnome-ssh-askpass",
+    "/usr/libexec/openssh/gnome-ssh-askpass",
+    "/usr/bin/ksshaskpass",
+    "/usr/bin/lxqt-openssh-askpass",
+];
+
+// Flags that don't change who sudo runs as, so the wrapper can be dropped when we elevate ourselves
+const TRANSPARENT_SUDO_FLAGS: &[&str] = &["-E", "-H", "-n", "-S", "--preserve-env"];
+
+const SESSION_MARKER: &str = "__NUFFI_ELEVATED_DONE__";
+
+// How long one command in a root session may run; package installs can take a while
+const ELEVATED_COMMAND_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+// Exit codes `timeout` uses when it had to stop the command
+const TIMED_OUT_EXIT_CODES: &[i32] = &[124, 137];
+
+// Output of an unprivileged producer piped into an elevated command, such as a setup script
+const PIPED_INPUT_LIMIT: usize = 16 * 1024 * 1024;
+
+// The only variables an elevated command may be given. Anything that changes which binaries or
+// libraries load, such as PATH, LD_PRELOAD or BASH_ENV, never crosses the elevation boundary.
+const ELEVATED_ENV_ALLOWLIST: &[&str] = &[
+    "DEBIAN_FRONTEND",
+    "NEEDRESTART_MODE",
+    "NONINTERACTIVE",
+    "HOMEBREW_NO_AUTO_UPDATE",
+    "LANG",
+    "LC_ALL",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", content = "helper", rename_all = "snake_case")]
+pub enum ElevationMethod {
+    // The app already runs as root
+    Root,
+    // A root shell started once through polkit; elevated commands are piped into it
+    Pkexec,
+    // `sudo -A` with the given askpass helper; sudo caches the credentials between commands
+    SudoAskpass(String),
+    Unavailable,
+}
+
+// One line of the audit trail: everything that ran as root, and every authorisation attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivilegedCommandRecord {
+    pub id: String,
+    pub job_id: Option<String>,
+    pub tool: String,
+    pub command: String,
+    pub method: String,
+    pub user: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivilegeStatus {
+    pub method: ElevationMethod,
+    pub authorized: bool,
+    pub denied: Option<String>,
+}
+
+#[derive(Default)]
+struct BrokerState {
+    authorized: bool,
+    // A refused prompt is remembered so a batch doesn't prompt again for every step
+    denied: Option<String>,
+    session: Option<Arc<Mutex<RootSession>>>,
+}
+
+pub struct PrivilegeBroker {
+    method: ElevationMethod,
+    user: String,
+    state: Mutex<BrokerState>,
+    audit: Mutex<Vec<PrivilegedCommandRecord>>,
+    database: Option<Arc<Mutex<Database>>>,
+}
+
+impl PrivilegeBroker {
+    pub fn new(method: ElevationMethod) -> Self {
+        Self {
+            method,
+            user: current_user(),
+            state: Mutex::new(BrokerState::default()),
+            audit: Mutex::new(Vec::new()),
+            database: None,
+        }
+    }
+
+    pub fn detect() -> Self {
+        Self::new(detect_method(
+            cfg!(unix) && is_root(),
+            std::env::var("SUDO_ASKPASS").ok(),
+            std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some(),
+            |program| find_program(program).is_some(),
+        ))
+    }
+
+    pub fn with_database(mut self, database: Arc<Mutex<Database>>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    pub fn method(&self) -> &ElevationMethod {
+        &self.method
+    }
+
+    pub fn status(&self) -> PrivilegeStatus {
+        let state = self.state.lock().unwrap();
+        PrivilegeStatus {
+            method: self.method.clone(),
+            authorized: state.authorized,
+            denied: state.denied.clone(),
+        }
+    }
+
+    // Asks for authorisation at most once; later calls reuse the outcome until `reset` is called
+    pub fn authorize(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        self.authorize_locked(&mut state)
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = BrokerState::default();
+    }
+
+    fn authorize_locked(&self, state: &mut BrokerState) -> Result<(), String> {
+        if state.authorized {
+            return Ok(());
+        }
+        if let Some(denied) = &state.denied {
+            return Err(denied.clone());
+        }
+
+        let started = Instant::now();
+        let started_at = chrono::Utc::now();
+        let result = match &self.method {
+            ElevationMethod::Root => Ok(()),
+            ElevationMethod::Pkexec => RootSession::start(&["pkexec", "/bin/sh"]).map(|session| {
+                state.session = Some(Arc::new(Mutex::new(session)));
+            }),
+            ElevationMethod::SudoAskpass(helper) => CommandSpec::new("sudo", &["-A", "-v"])
+                .env("SUDO_ASKPASS", helper)
+                .run()
+                .map_err(|e| e.to_string())
+                .and_then(|output| {
+                    if output.success {
+                        Ok(())
+                    } else {
+                        Err(output.stderr.trim().to_string())
+                    }
+                }),
+            ElevationMethod::Unavailable => Err(
+                "Administrator privileges are required, but neither pkexec nor a sudo askpass helper is available".to_string(),
+            ),
+        };
+
+        self.record(PrivilegedCommandRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            job_id: None,
+            tool: String::new(),
+            command: "authorize".to_string(),
+            method: self.method_name(),
+            user: self.user.clone(),
+            success: result.is_ok(),
+            exit_code: None,
+            error: result.as_ref().err().cloned(),
+            started_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+
+        match result {
+            Ok(()) => {
+                state.authorized = true;
+                Ok(())
+            }
+            Err(error) => {
+                let error = format!("Authorization for administrator privileges failed: {}", error);
+                state.denied = Some(error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    // Runs `spec` as root and records it in the audit trail. For `producer | sudo command` lines
+    // only the sudo segment is elevated; the producer runs as the app user.
+    pub fn run_elevated(&self, spec: &CommandSpec, job_id: Option<&str>, tool: &str) -> Result<CommandOutput, String> {
+        let started = Instant::now();
+        let started_at = chrono::Utc::now();
+        let step = elevated_step(spec, &self.user);
+        let command = match &step {
+            Ok(step) => step.command.display(),
+            Err(_) => spec.display(),
+        };
+
+        let result = step.and_then(|step| {
+            check_elevated_env(&step.command)?;
+            // The broker state is only held while authorising, so a long install doesn't block status checks
+            let session = {
+                let mut state = self.state.lock().unwrap();
+                self.authorize_locked(&mut state)?;
+                state.session.clone()
+            };
+            let spec = match &step.input {
+                Some(producer) => step.command.clone().stdin(run_producer(producer)?),
+                None => step.command,
+            };
+            let output = match (&self.method, session) {
+                (ElevationMethod::Pkexec, Some(session)) => session.lock().unwrap().run(&spec, &self.user),
+                _ => self.elevated_spec(&spec).run(),
+            };
+            output.map_err(|e| e.to_string())
+        });
+
+        self.record(PrivilegedCommandRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            job_id: job_id.map(|id| id.to_string()),
+            tool: tool.to_string(),
+            command,
+            method: self.method_name(),
+            user: self.user.clone(),
+            success: matches!(&result, Ok(output) if output.success),
+            exit_code: result.as_ref().ok().and_then(|output| output.exit_code),
+            error: match &result {
+                Ok(output) if !output.success => Some(output.stderr.trim().to_string()),
+                Ok(_) => None,
+                Err(error) => Some(error.clone()),
+            },
+            started_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+
+        result
+    }
+
+    // Newest first; the database keeps entries from earlier sessions as well
+    pub fn audit_trail(&self, limit: usize) -> Vec<PrivilegedCommandRecord> {
+        if let Some(database) = &self.database {
+            match database.lock().unwrap().get_privileged_commands(limit as i32) {
+                Ok(entries) => return entries,
+                Err(e) => tracing::warn!("Failed to read privileged command audit trail: {}", e),
+            }
+        }
+        self.audit.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    // How a command is launched for the sudo based methods. Manifests refer to the invoking user as $USER.
+    fn elevated_spec(&self, spec: &CommandSpec) -> CommandSpec {
+        let mut argv: Vec<String> = Vec::new();
+        if let ElevationMethod::SudoAskpass(_) = &self.method {
+            argv.extend(["sudo", "-A", "--", "env"].iter().map(|a| a.to_string()));
+            argv.push(format!("USER={}", self.user));
+            argv.extend(elevated_env(spec).map(|(key, value)| format!("{}={}", key, value)));
+        }
+        if spec.shell {
+            argv.extend(["sh".to_string(), "-c".to_string()]);
+        }
+        argv.extend(spec.argv.iter().cloned());
+
+        let mut elevated = CommandSpec::from_argv(argv).output_limit(spec.output_limit);
+        elevated.working_dir = spec.working_dir.clone();
+        elevated.stdin = spec.stdin.clone();
+        if let ElevationMethod::SudoAskpass(helper) = &self.method {
+            elevated = elevated.env("SUDO_ASKPASS", helper);
+        } else {
+            elevated.env = elevated_env(spec).map(|(key, value)| (key.clone(), value.clone())).collect();
+        }
+        elevated
+    }
+
+    fn method_name(&self) -> String {
+        match &self.method {
+            ElevationMethod::Root => "root",
+            ElevationMethod::Pkexec => "pkexec",
+            ElevationMethod::SudoAskpass(_) => "sudo_askpass",
+            ElevationMethod::Unavailable => "unavailable",
+        }
+        .to_string()
+    }
+
+    fn record(&self, entry: PrivilegedCommandRecord) {
+        if let Some(database) = &self.database {
+            if let Err(e) = database.lock().unwrap().record_privileged_command(&entry) {
+                tracing::warn!("Failed to persist privileged command audit entry: {}", e);
+            }
+        }
+        self.audit.lock().unwrap().push(entry);
+    }
+}
+
+pub fn detect_method(
+    is_root: bool,
+    askpass: Option<String>,
+    has_display: bool,
+    has_program: impl Fn(&str) -> bool,
+) -> ElevationMethod {
+    if is_root {
+        return ElevationMethod::Root;
+    }
+    if !cfg!(unix) || (!has_program("sudo") && !has_program("pkexec")) {
+        return ElevationMethod::Unavailable;
+    }
+
+    // An explicitly configured helper wins over polkit
+    if let Some(askpass) = askpass.filter(|a| !a.is_empty() && has_program("sudo")) {
+        return ElevationMethod::SudoAskpass(askpass);
+    }
+    if has_display && has_program("pkexec") {
+        return ElevationMethod::Pkexec;
+    }
+    match ASKPASS_HELPERS.iter().find(|helper| has_program(helper)) {
+        Some(helper) if has_program("sudo") => ElevationMethod::SudoAskpass(helper.to_string()),
+        _ => ElevationMethod::Unavailable,
+    }
+}
+
+// Resolves a program name against PATH; paths are checked as they are
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        return Path::new(program).is_file().then(|| PathBuf::from(program));
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+fn is_root() -> bool {
+    CommandSpec::new("id", &["-u"])
+        .run()
+        .map(|output| output.success && output.stdout.trim() == "0")
+        .unwrap_or(false)
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+// What an elevated step runs: the argv that runs as root, and optionally an unprivileged shell
+// command whose output is piped into it
+#[derive(Debug, PartialEq)]
+struct ElevatedStep {
+    input: Option<CommandSpec>,
+    command: CommandSpec,
+}
+
+// Shell lines are only accepted as `sudo program args` or `producer | sudo program args`, so the
+// shell never runs as root. Manifests refer to the invoking user as $USER.
+fn elevated_step(spec: &CommandSpec, user: &str) -> Result<ElevatedStep, String> {
+    if !spec.shell {
+        return Ok(ElevatedStep { input: None, command: unwrap_sudo(spec) });
+    }
+
+    let line = spec.argv.first().map(|line| line.trim()).unwrap_or_default();
+    let rejected = || format!("`{}` can't run elevated: only `sudo program args`, optionally piped into, can", line);
+    let (producer, elevated) = match line.rsplit_once('|') {
+        Some((producer, elevated)) => (Some(producer.trim()), elevated.trim()),
+        None => (None, line),
+    };
+    if producer.map(|p| p.is_empty() || p.ends_with('|') || p.split_whitespace().any(|t| t == "sudo")).unwrap_or(false) {
+        return Err(rejected());
+    }
+
+    // Output of the elevated command is captured anyway
+    let elevated = elevated
+        .strip_suffix("> /dev/null")
+        .or_else(|| elevated.strip_suffix(">/dev/null"))
+        .unwrap_or(elevated)
+        .trim();
+    let user = shell_quote(user);
+    let elevated = elevated.replace("${USER}", &user).replace("$USER", &user);
+    let argv = match split_command_line(&elevated) {
+        Ok(Some(argv)) if argv.first().map(|p| p.as_str()) == Some("sudo") => argv,
+        _ => return Err(rejected()),
+    };
+
+    let command = CommandSpec {
+        argv,
+        shell: false,
+        ..spec.clone()
+    };
+    let input = producer.map(|producer| CommandSpec {
+        shell: true,
+        argv: vec![producer.to_string()],
+        ..spec.clone()
+    });
+    Ok(ElevatedStep { input, command: unwrap_sudo(&command) })
+}
+
+// Runs a producer as the app user; its whole output has to fit, since a cut-off script would still run
+fn run_producer(producer: &CommandSpec) -> Result<Vec<u8>, String> {
+    let output = producer.clone().output_limit(PIPED_INPUT_LIMIT).run().map_err(|e| e.to_string())?;
+    if !output.success {
+        return Err(format!("`{}` failed: {}", producer.display(), output.stderr.trim()));
+    }
+    if output.stdout_truncated {
+        return Err(format!("`{}` produced more than {} bytes", producer.display(), PIPED_INPUT_LIMIT));
+    }
+    Ok(output.stdout.into_bytes())
+}
+
+
+// `sudo apt-get install x` becomes `apt-get install x`; the broker decides how to elevate
+fn unwrap_sudo(spec: &CommandSpec) -> CommandSpec {
+    if spec.shell || spec.argv.first().map(|p| p.as_str()) != Some("sudo") {
+        return spec.clone();
+    }
+
+    let rest: Vec<String> = spec.argv[1..]
+        .iter()
+        .skip_while(|arg| TRANSPARENT_SUDO_FLAGS.contains(&arg.as_str()))
+        .cloned()
+        .collect();
+    if rest.is_empty() || rest[0].starts_with('-') {
+        return spec.clone();
+    }
+
+    CommandSpec { argv: rest, ..spec.clone() }
+}
+
+fn is_env_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_elevated_env(key: &str) -> bool {
+    is_env_name(key) && ELEVATED_ENV_ALLOWLIST.contains(&key)
+}
+
+// Refuses the command rather than running it without the variables it asked for
+fn check_elevated_env(spec: &CommandSpec) -> Result<(), String> {
+    match spec.env.keys().find(|key| !is_elevated_env(key)) {
+        Some(key) if !is_env_name(key) => Err(format!("`{}` is not a valid environment variable name", key)),
+        Some(key) => Err(format!("`{}` can't be passed to a command that runs as root", key)),
+        None => Ok(()),
+    }
+}
+
+// The variables that are passed on; keys outside the allowlist are dropped even if a caller skipped the check
+fn elevated_env(spec: &CommandSpec) -> impl Iterator<Item = (&String, &String)> {
+    spec.env.iter().filter(|(key, _)| is_elevated_env(key))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// A root shell kept open after one authorisation prompt. Each command's input and output go through
+// a 0700 directory the root shell creates for it, and are passed back over the session's own pipe.
+struct RootSession {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    timeout: Duration,
+}
+
+impl RootSession {
+    fn start(launcher: &[&str]) -> Result<Self, String> {
+        let (program, args) = launcher.split_first().ok_or("Empty launcher")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+        let stdin = child.stdin.take().ok_or("Root session has no stdin")?;
+        let stdout = BufReader::new(child.stdout.take().ok_or("Root session has no stdout")?);
+        let mut session = Self { _child: child, stdin, stdout, timeout: ELEVATED_COMMAND_TIMEOUT };
+
+        // The prompt happens while the launcher starts; a dismissed prompt closes the pipe
+        let check = session
+            .run(&CommandSpec::new("id", &["-u"]), "")
+            .map_err(|_| "the authorisation prompt was dismissed".to_string())?;
+        if check.stdout.trim() != "0" && launcher[0] == "pkexec" {
+            return Err("the elevated session is not running as root".to_string());
+        }
+        Ok(session)
+    }
+
+    fn run(&mut self, spec: &CommandSpec, user: &str) -> Result<CommandOutput, CommandError> {
+        let started = Instant::now();
+
+        // `timeout` stops the whole command, children included, from inside the root shell
+        let mut command = String::from("(");
+        if let Some(working_dir) = &spec.working_dir {
+            command.push_str(&format!("cd {} && ", shell_quote(&working_dir.display().to_string())));
+        }
+        command.push_str(&format!("exec timeout -k 10 {} env", self.timeout.as_secs().max(1)));
+        if !user.is_empty() {
+            command.push_str(&format!(" USER={}", shell_quote(user)));
+        }
+        for (key, value) in elevated_env(spec) {
+            command.push_str(&format!(" {}={}", key, shell_quote(value)));
+        }
+        if spec.shell {
+            command.push_str(" sh -c");
+        }
+        for arg in &spec.argv {
+            command.push(' ');
+            command.push_str(&shell_quote(arg));
+        }
+        command.push(')');
+
+        // Input is written out by the root shell itself, as octal escapes so any bytes survive
+        let mut input = String::from(": >\"$d/stdin\"\n");
+        for chunk in spec.stdin.as_deref().unwrap_or_default().chunks(4096) {
+            let escaped: String = chunk.iter().map(|byte| format!("\\{:03o}", byte)).collect();
+            input.push_str(&format!("printf '{}' >>\"$d/stdin\"\n", escaped));
+        }
+
+        let limit = spec.output_limit;
+        let script = format!(
+            "d=$(mktemp -d) || d=\n\
+             if [ -n \"$d\" ]; then\n\
+             {input}\
+             {command} >\"$d/stdout\" 2>\"$d/stderr\" <\"$d/stdin\"\n\
+             c=$?\n\
+             echo \"{marker} $c $(wc -c <\"$d/stdout\") $(wc -c <\"$d/stderr\")\"\n\
+             head -c {limit} \"$d/stdout\"; head -c {limit} \"$d/stderr\"\n\
+             rm -rf \"$d\"\n\
+             else echo \"{marker} 125 0 0\"; fi\n",
+            marker = SESSION_MARKER,
+        );
+
+        let closed = |source| CommandError::Spawn { program: "sh".to_string(), source };
+        self.stdin.write_all(script.as_bytes()).map_err(closed)?;
+        self.stdin.flush().map_err(closed)?;
+
+        let mut line = String::new();
+        let (exit_code, stdout_size, stderr_size) = loop {
+            line.clear();
+            let read = self.stdout.read_line(&mut line).map_err(closed)?;
+            if read == 0 {
+                return Err(CommandError::Task("elevated session closed".to_string()));
+            }
+            if let Some(rest) = line.trim().strip_prefix(SESSION_MARKER) {
+                let fields: Vec<usize> = rest.split_whitespace().skip(1).filter_map(|field| field.parse().ok()).collect();
+                let exit_code: Option<i32> = rest.split_whitespace().next().and_then(|code| code.parse().ok());
+                break (exit_code, fields.first().copied().unwrap_or(0), fields.get(1).copied().unwrap_or(0));
+            }
+        };
+
+        let mut read_output = |size: usize| -> Result<(String, bool), CommandError> {
+            let mut content = vec![0u8; size.min(limit)];
+            self.stdout.read_exact(&mut content).map_err(closed)?;
+            Ok((String::from_utf8_lossy(&content).into_owned(), size > limit))
+        };
+        let (stdout, stdout_truncated) = read_output(stdout_size)?;
+        let (mut stderr, stderr_truncated) = read_output(stderr_size)?;
+        if exit_code.map(|code| TIMED_OUT_EXIT_CODES.contains(&code)).unwrap_or(false) && started.elapsed() >= self.timeout {
+            stderr.push_str(&format!("\nTimed out after {} seconds", self.timeout.as_secs()));
+        }
+
+        Ok(CommandOutput {
+            success: exit_code == Some(0),
+            exit_code,
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+// Tauri commands
+#[command]
+pub async fn get_privilege_status(installer: State<'_, UniversalInstaller>) -> Result<PrivilegeStatus, String> {
+    Ok(installer.broker().status())
+}
+
+// Lets the UI prompt before a batch starts, or retry after a dismissed prompt
+#[command]
+pub async fn authorize_privileges(installer: State<'_, UniversalInstaller>) -> Result<PrivilegeStatus, String> {
+    let broker = installer.broker();
+    broker.reset();
+    tokio::task::spawn_blocking(move || broker.authorize().map(|()| broker.status()))
+        .await
+        .map_err(|e| format!("Authorization task failed: {}", e))?
+}
+
+#[command]
+pub async fn get_privileged_audit(
+    installer: State<'_, UniversalInstaller>,
+    limit: Option<usize>,
+) -> Result<Vec<PrivilegedCommandRecord>, String> {
+    Ok(installer.broker().audit_trail(limit.unwrap_or(100)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_method() {
+        let all = |_: &str| true;
+        assert_eq!(detect_method(true, None, false, all), ElevationMethod::Root);
+
+        if cfg!(unix) {
+            assert_eq!(detect_method(false, None, true, all), ElevationMethod::Pkexec);
+            assert_eq!(
+                detect_method(false, Some("/opt/askpass".to_string()), true, all),
+                ElevationMethod::SudoAskpass("/opt/askpass".to_string())
+            );
+
+            // Without a display polkit can't prompt, so a known helper is used instead
+            let no_display = detect_method(false, None, false, |p| p == "sudo" || p == ASKPASS_HELPERS[0]);
+            assert_eq!(no_display, ElevationMethod::SudoAskpass(ASKPASS_HELPERS[0].to_string()));
+            assert_eq!(detect_method(false, None, false, |p| p == "sudo"), ElevationMethod::Unavailable);
+        }
+    }
+
+    #[test]
+    fn test_unwrap_sudo() {
+        let spec = CommandSpec::parse("sudo -E apt-get install -y nodejs").unwrap();
+        assert_eq!(unwrap_sudo(&spec).display(), "apt-get install -y nodejs");
+
+        // Running as another user keeps the sudo wrapper
+        let spec = CommandSpec::parse("sudo -u postgres createdb app").unwrap();
+        assert_eq!(unwrap_sudo(&spec), spec);
+
+        let shell = CommandSpec::shell("curl -fsSL https://example.com | sudo -E bash -");
+        assert_eq!(unwrap_sudo(&shell), shell);
+    }
+
+    #[test]
+    fn test_elevated_step_only_elevates_sudo_segment() {
+        let step = elevated_step(&CommandSpec::shell("curl -fsSL https://example.com | sudo -E bash -"), "alice").unwrap();
+        assert_eq!(step.input.unwrap(), CommandSpec::shell("curl -fsSL https://example.com"));
+        assert_eq!(step.command, CommandSpec::new("bash", &["-"]));
+
+        let step = elevated_step(&CommandSpec::shell("echo \"deb stable\" | sudo tee /etc/apt/x.list > /dev/null"), "alice").unwrap();
+        assert_eq!(step.command.argv, vec!["tee", "/etc/apt/x.list"]);
+
+        let step = elevated_step(&CommandSpec::shell("sudo usermod -aG docker $USER"), "o'neil").unwrap();
+        assert_eq!(step.input, None);
+        assert_eq!(step.command.argv, vec!["usermod", "-aG", "docker", "o'neil"]);
+
+        let step = elevated_step(&CommandSpec::parse("sudo apt-get update").unwrap(), "alice").unwrap();
+        assert_eq!(step.command.argv, vec!["apt-get", "update"]);
+
+        for line in ["a && sudo b", "sudo sh -c 'x' | tee y", "x | sudo $(evil)", "sudo a | sudo b", "| sudo b", "apt-get update"] {
+            assert!(elevated_step(&CommandSpec::shell(line), "alice").is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_sudo_askpass_spec_preserves_user_and_env() {
+        let broker = PrivilegeBroker::new(ElevationMethod::SudoAskpass("/usr/bin/ssh-askpass".to_string()));
+        let spec = CommandSpec::shell("usermod -aG docker $USER").env("DEBIAN_FRONTEND", "noninteractive");
+        let elevated = broker.elevated_spec(&spec);
+
+        assert_eq!(&elevated.argv[..4], &["sudo", "-A", "--", "env"]);
+        assert_eq!(elevated.argv[4], format!("USER={}", current_user()));
+        assert!(elevated.argv.contains(&"DEBIAN_FRONTEND=noninteractive".to_string()));
+        assert_eq!(elevated.argv[elevated.argv.len() - 3..], ["sh", "-c", "usermod -aG docker $USER"]);
+        assert_eq!(elevated.env.get("SUDO_ASKPASS").map(|s| s.as_str()), Some("/usr/bin/ssh-askpass"));
+    }
+
+    #[test]
+    fn test_elevated_env_is_allowlisted() {
+        let broker = PrivilegeBroker::new(ElevationMethod::Root);
+        for (key, error) in [
+            ("LD_PRELOAD", "`LD_PRELOAD` can't be passed to a command that runs as root"),
+            ("PATH", "`PATH` can't be passed to a command that runs as root"),
+            ("X=1; rm -rf / #", "`X=1; rm -rf / #` is not a valid environment variable name"),
+        ] {
+            let spec = CommandSpec::new("true", &[]).env(key, "/tmp/x");
+            assert_eq!(broker.run_elevated(&spec, None, "Git").unwrap_err(), error);
+        }
+        assert!(broker.audit_trail(10).iter().all(|entry| !entry.success));
+
+        // Dropped as well when a spec is built without the check
+        let spec = CommandSpec::new("true", &[]).env("BASH_ENV", "/tmp/x").env("DEBIAN_FRONTEND", "noninteractive");
+        let elevated = broker.elevated_spec(&spec);
+        assert_eq!(elevated.env.len(), 1);
+        assert!(elevated.env.contains_key("DEBIAN_FRONTEND"));
+    }
+
+    #[test]
+    fn test_unavailable_method_is_denied_once_and_audited() {
+        let broker = PrivilegeBroker::new(ElevationMethod::Unavailable);
+        let spec = CommandSpec::parse("sudo apt-get install -y git").unwrap();
+
+        assert!(broker.run_elevated(&spec, Some("job-1"), "Git").is_err());
+        assert!(broker.run_elevated(&spec, Some("job-1"), "Git").is_err());
+
+        let audit = broker.audit_trail(10);
+        assert_eq!(audit.iter().filter(|entry| entry.command == "authorize").count(), 1);
+        let commands: Vec<&PrivilegedCommandRecord> = audit.iter().filter(|entry| entry.command != "authorize").collect();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].command, "apt-get install -y git");
+        assert_eq!(commands[0].job_id.as_deref(), Some("job-1"));
+        assert!(!commands[0].success);
+        assert!(broker.status().denied.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_root_session_protocol() {
+        // Any shell speaks the same protocol as the pkexec one
+        let mut session = RootSession::start(&["sh"]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let output = session
+            .run(
+                &CommandSpec::shell("echo \"$USER:$DEBIAN_FRONTEND:$BASH_ENV\"; pwd; echo 'it''s' >&2; exit 4")
+                    .env("DEBIAN_FRONTEND", "noninteractive")
+                    .env("BASH_ENV", "/tmp/x")
+                    .current_dir(dir.path()),
+                "alice",
+            )
+            .unwrap();
+        assert_eq!(output.exit_code, Some(4));
+        assert_eq!(output.stdout_lines()[0], "alice:noninteractive:");
+        assert!(output.stdout_lines()[1].ends_with(&*dir.path().file_name().unwrap().to_string_lossy()));
+        assert_eq!(output.stderr.trim(), "its");
+
+        let output = session.run(&CommandSpec::new("echo", &["it's", "quoted"]), "").unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout.trim(), "it's quoted");
+
+        let output = session.run(&CommandSpec::new("cat", &[]).stdin(b"piped 'in' \\ 100%\n\xff".to_vec()), "").unwrap();
+        assert_eq!(output.stdout, "piped 'in' \\ 100%\n\u{fffd}");
+
+        // Output is cut at the limit, and whatever follows still lines up
+        let output = session.run(&CommandSpec::shell("yes | head -c 100000").output_limit(10), "").unwrap();
+        assert_eq!((output.stdout.as_str(), output.stdout_truncated), ("y\ny\ny\ny\ny\n", true));
+        assert_eq!(session.run(&CommandSpec::new("echo", &["next"]), "").unwrap().stdout, "next\n");
+
+        // A command that runs too long is stopped, and the session stays usable
+        session.timeout = Duration::from_secs(1);
+        let output = session.run(&CommandSpec::shell("sleep 30"), "").unwrap();
+        assert!(!output.success);
+        assert!(output.stderr.contains("Timed out after 1 seconds"));
+        assert!(session.run(&CommandSpec::new("true", &[]), "").unwrap().success);
+    }
+}
@@ -0,0 +1,280 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use sysinfo::{Pid, PidExt, Process, ProcessExt, Signal, System, SystemExt};
+use tauri::{command, State};
+
+use crate::metrics_collector::{process_workspace, SystemMonitor};
+use crate::workspace_manager::WorkspaceManager;
+
+// Runtimes and tools that typically back a long-running dev server
+const DEV_SERVER_NAMES: &[&str] = &[
+    "node", "deno", "bun", "npm", "npx", "yarn", "pnpm", "vite", "next-server", "webpack", "python", "python3",
+    "ruby", "rails", "php", "java", "dotnet", "go", "cargo", "air", "nodemon", "uvicorn", "gunicorn", "flask",
+];
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub command: Vec<String>,
+    pub executable: String,
+    pub working_directory: String,
+    // Percent of one core, so a busy multi-threaded process can exceed 100
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
+    // The workspace id exported by the activation script the process was started under
+    pub workspace: Option<String>,
+}
+
+impl ProcessInfo {
+    fn from_process(process: &Process) -> Self {
+        Self {
+            pid: process.pid().as_u32(),
+            parent_pid: process.parent().map(|pid| pid.as_u32()),
+            name: process.name().to_string(),
+            command: process.cmd().to_vec(),
+            executable: process.exe().display().to_string(),
+            working_directory: process.cwd().display().to_string(),
+            cpu_usage: process.cpu_usage(),
+            memory: process.memory(),
+            status: process.status().to_string(),
+            started_at: DateTime::from_timestamp(process.start_time() as i64, 0),
+            workspace: process_workspace(process).map(str::to_string),
+        }
+    }
+
+    pub fn is_dev_server(&self) -> bool {
+        let name = self.name.to_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        DEV_SERVER_NAMES.contains(&name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessSignal {
+    Terminate,
+    Kill,
+    Interrupt,
+    Hangup,
+    Stop,
+    Continue,
+}
+
+impl ProcessSignal {
+    fn signal(&self) -> Signal {
+        match self {
+            Self::Terminate => Signal::Term,
+            Self::Kill => Signal::Kill,
+            Self::Interrupt => Signal::Interrupt,
+            Self::Hangup => Signal::Hangup,
+            Self::Stop => Signal::Stop,
+            Self::Continue => Signal::Continue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanReason {
+    Inactive,
+    Deleted,
+    // Not a workspace this session knows about, such as one from before a restart
+    Unknown,
+}
+
+// A process still running for a workspace that is no longer active
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OrphanedProcess {
+    pub process: ProcessInfo,
+    pub reason: OrphanReason,
+    // Its parent has exited and it was re-parented, typically to init
+    pub detached: bool,
+    pub dev_server: bool,
+}
+
+// Every process, or only those started from `workspace`'s shells, busiest first
+pub fn list_processes(system: &System, workspace: Option<&str>) -> Vec<ProcessInfo> {
+    let mut processes: Vec<ProcessInfo> = system
+        .processes()
+        .values()
+        .filter(|process| workspace.is_none() || process_workspace(process) == workspace)
+        .map(ProcessInfo::from_process)
+        .collect();
+    processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    processes
+}
+
+// Detached processes and dev servers tagged with a workspace that isn't active, dev servers first,
+// then busiest first. Children of a shell that is still open are left alone.
+pub fn find_orphans(processes: &[ProcessInfo], workspaces: &WorkspaceManager) -> Vec<OrphanedProcess> {
+    let running: HashMap<u32, &ProcessInfo> = processes.iter().map(|process| (process.pid, process)).collect();
+
+    let mut orphans: Vec<OrphanedProcess> = processes
+        .iter()
+        .filter_map(|process| {
+            let workspace_id = process.workspace.as_deref()?;
+            let reason = match workspaces.get_workspace(workspace_id) {
+                Some(workspace) if workspace.status == "active" => return None,
+                Some(_) => OrphanReason::Inactive,
+                None if workspaces.was_deleted(workspace_id) => OrphanReason::Deleted,
+                None => OrphanReason::Unknown,
+            };
+            let detached = match process.parent_pid {
+                None | Some(1) => true,
+                Some(parent) => !running.contains_key(&parent),
+            };
+            if !detached && !process.is_dev_server() {
+                return None;
+            }
+            Some(OrphanedProcess {
+                process: process.clone(),
+                reason,
+                detached,
+                dev_server: process.is_dev_server(),
+            })
+        })
+        .collect();
+    orphans.sort_by(|a, b| {
+        b.dev_server
+            .cmp(&a.dev_server)
+            .then(b.process.cpu_usage.total_cmp(&a.process.cpu_usage))
+    });
+    orphans
+}
+
+// Refuses NUFFI itself and init; reads the process fresh so recently started ones can be signalled
+pub fn signal_process(pid: u32, signal: ProcessSignal) -> Result<(), String> {
+    if pid <= 1 || pid == std::process::id() {
+        return Err(format!("Refusing to signal process {}", pid));
+    }
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !system.refresh_process(pid) {
+        return Err(format!("Process {} not found", pid));
+    }
+    let process = system.process(pid).ok_or_else(|| format!("Process {} not found", pid))?;
+    match process.kill_with(signal.signal()) {
+        Some(true) => Ok(()),
+        Some(false) => Err(format!("Failed to send {:?} to process {}", signal, pid)),
+        None => Err(format!("{:?} is not supported on this platform", signal)),
+    }
+}
+
+#[command]
+pub async fn get_processes(
+    monitor: State<'_, SystemMonitor>,
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    workspace_id: Option<String>,
+) -> Result<Vec<ProcessInfo>, String> {
+    if let Some(id) = &workspace_id {
+        workspaces.lock().unwrap().get_workspace(id).ok_or("Workspace not found")?;
+    }
+    Ok(monitor.inspect(|system| list_processes(system, workspace_id.as_deref())).await)
+}
+
+#[command]
+pub async fn send_process_signal(pid: u32, signal: ProcessSignal) -> Result<(), String> {
+    signal_process(pid, signal)
+}
+
+#[command]
+pub async fn kill_process(pid: u32) -> Result<(), String> {
+    signal_process(pid, ProcessSignal::Kill)
+}
+
+#[command]
+pub async fn find_orphaned_processes(
+    monitor: State<'_, SystemMonitor>,
+    workspaces: State<'_, Arc<Mutex<WorkspaceManager>>>,
+) -> Result<Vec<OrphanedProcess>, String> {
+    let processes = monitor.inspect(|system| list_processes(system, None)).await;
+    let workspaces = workspaces.lock().unwrap();
+    Ok(find_orphans(&processes, &workspaces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace_manager::CreateWorkspaceRequest;
+
+    fn process(pid: u32, parent_pid: Option<u32>, name: &str, cpu_usage: f32, workspace: Option<&str>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent_pid,
+            name: name.to_string(),
+            command: vec![name.to_string()],
+            executable: String::new(),
+            working_directory: String::new(),
+            cpu_usage,
+            memory: 0,
+            status: "Run".to_string(),
+            started_at: None,
+            workspace: workspace.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let mut manager = WorkspaceManager::new();
+        let mut ids = HashMap::new();
+        for name in ["api", "web", "old"] {
+            let workspace = manager
+                .create_workspace(CreateWorkspaceRequest {
+                    name: name.to_string(),
+                    workspace_type: "node".to_string(),
+                    tools: vec![],
+                    config: None,
+                })
+                .unwrap();
+            ids.insert(name, workspace.id);
+        }
+        manager.activate_workspace(&ids["api"]).unwrap();
+        manager.delete_workspace(&ids["old"]).unwrap();
+
+        let processes = vec![
+            process(10, Some(1), "bash", 0.0, None),
+            process(20, Some(10), "node", 5.0, Some(&ids["api"])),
+            process(30, Some(1), "node", 90.0, Some(&ids["web"])),
+            process(31, Some(30), "esbuild", 95.0, Some(&ids["web"])),
+            process(32, Some(30), "node", 50.0, Some(&ids["web"])),
+            process(40, Some(999), "python3", 1.0, Some(&ids["old"])),
+            process(50, Some(1), "sleep", 0.5, Some("from-before-a-restart")),
+        ];
+        let orphans = find_orphans(&processes, &manager);
+        let pids: Vec<u32> = orphans.iter().map(|orphan| orphan.process.pid).collect();
+        // esbuild still has its parent and isn't a dev server, so it is left alone
+        assert_eq!(pids, vec![30, 32, 40, 50]);
+        assert_eq!((orphans[0].reason, orphans[0].detached, orphans[0].dev_server), (OrphanReason::Inactive, true, true));
+        assert_eq!((orphans[1].detached, orphans[1].dev_server), (false, true));
+        assert_eq!((orphans[2].reason, orphans[2].detached), (OrphanReason::Deleted, true));
+        assert_eq!((orphans[3].reason, orphans[3].dev_server), (OrphanReason::Unknown, false));
+    }
+
+    #[test]
+    fn test_list_and_signal_processes() {
+        let mut system = System::new();
+        system.refresh_processes();
+        let own = std::process::id();
+        assert!(list_processes(&system, None).iter().any(|process| process.pid == own));
+        assert!(list_processes(&system, Some("no-such-workspace")).is_empty());
+
+        assert!(signal_process(own, ProcessSignal::Kill).is_err());
+        assert!(signal_process(1, ProcessSignal::Terminate).is_err());
+        assert!(process(1, None, "node.exe", 0.0, None).is_dev_server());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_child_process() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        signal_process(child.id(), ProcessSignal::Kill).unwrap();
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+    }
+}
@@ -6,6 +6,8 @@ use tauri::command;
 use walkdir::WalkDir;
 use regex::Regex;
 
+use crate::version_manager::{managed_versions, ManagedVersion};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemScan {
     pub id: String,
@@ -15,6 +17,7 @@ pub struct SystemScan {
     pub suggestions: Vec<String>,
     pub scanned_at: chrono::DateTime<chrono::Utc>,
     pub os_info: SystemInfo,
+    pub managed_versions: Vec<ManagedVersion>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +93,8 @@ impl SystemScanner {
             suggestions,
             scanned_at: chrono::Utc::now(),
             os_info,
+            // Versions installed side by side through pyenv, nvm, rustup or mise
+            managed_versions: managed_versions(),
         })
     }
 
@@ -411,7 +416,7 @@ impl SystemScanner {
         suggestions
     }
 
-    fn get_system_info(&self) -> Result<SystemInfo, String> {
+    pub fn get_system_info(&self) -> Result<SystemInfo, String> {
         Ok(SystemInfo {
             os: std::env::consts::OS.to_string(),
             arch: std::env::consts::ARCH.to_string(),
@@ -0,0 +1,578 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{command, State};
+
+use crate::install_journal::expand_home;
+use crate::workspace_manager::{Workspace, WorkspaceManager};
+
+pub const BLOCK_START: &str = "# >>> nuffi >>>";
+pub const BLOCK_END: &str = "# <<< nuffi <<<";
+const BLOCK_NOTE: &str = "# Managed by NUFFI; lines in this block are rewritten on install and uninstall";
+const OWNER_TAG: &str = " # nuffi:";
+
+// Set by activation scripts, so processes started from an activated shell can be traced to their workspace
+pub const WORKSPACE_ENV_VAR: &str = "NUFFI_WORKSPACE";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// One line of the NUFFI block
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProfileSetting {
+    Path { dir: String },
+    Env { key: String, value: String },
+    Source { script: String },
+}
+
+// A setting and the tools or workspaces that asked for it; it stays until the last owner is removed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProfileEntry {
+    pub owners: Vec<String>,
+    pub setting: ProfileSetting,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 3] = [Shell::Bash, Shell::Zsh, Shell::Fish];
+
+    pub fn profile_path(&self, home: &Path) -> PathBuf {
+        match self {
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => home.join(".zshrc"),
+            Shell::Fish => home.join(".config").join("fish").join("config.fish"),
+        }
+    }
+
+    pub fn script_extension(&self) -> &'static str {
+        match self {
+            Shell::Fish => "fish",
+            Shell::Bash | Shell::Zsh => "sh",
+        }
+    }
+
+    // The login shell, plus every other shell the user already has a profile for
+    pub fn detect(home: &Path) -> Vec<Shell> {
+        let login = std::env::var("SHELL").unwrap_or_default();
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|shell| login.ends_with(&format!("/{:?}", shell).to_lowercase()) || shell.profile_path(home).exists())
+            .collect()
+    }
+
+    fn quote(&self, value: &str) -> String {
+        match self {
+            Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+            Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', r"'\''")),
+        }
+    }
+
+    // Reads one quoted word written by `quote`, returning it and the rest of the line
+    fn unquote<'a>(&self, text: &'a str) -> Option<(String, &'a str)> {
+        let body = text.strip_prefix('\'')?;
+        let mut chars = body.char_indices();
+        let mut value = String::new();
+        while let Some((index, c)) = chars.next() {
+            match (self, c) {
+                (Shell::Fish, '\\') => value.push(chars.next()?.1),
+                (Shell::Bash | Shell::Zsh, '\'') if body[index + 1..].starts_with(r"\''") => {
+                    value.push('\'');
+                    chars.nth(2);
+                }
+                (_, '\'') => return Some((value, &body[index + 1..])),
+                (_, c) => value.push(c),
+            }
+        }
+        None
+    }
+
+    pub fn render(&self, setting: &ProfileSetting) -> String {
+        match (self, setting) {
+            (Shell::Fish, ProfileSetting::Path { dir }) => format!("set -gx PATH {} $PATH", self.quote(dir)),
+            (Shell::Fish, ProfileSetting::Env { key, value }) => format!("set -gx {} {}", key, self.quote(value)),
+            (Shell::Fish, ProfileSetting::Source { script }) => {
+                format!("test -f {0}; and source {0}", self.quote(script))
+            }
+            (_, ProfileSetting::Path { dir }) => format!("export PATH={}:\"$PATH\"", self.quote(dir)),
+            (_, ProfileSetting::Env { key, value }) => format!("export {}={}", key, self.quote(value)),
+            (_, ProfileSetting::Source { script }) => format!("[ -f {0} ] && . {0}", self.quote(script)),
+        }
+    }
+
+    pub fn parse(&self, line: &str) -> Option<ProfileSetting> {
+        let (command, rest) = match self {
+            Shell::Fish => {
+                if let Some(rest) = line.strip_prefix("set -gx PATH ") {
+                    let (dir, rest) = self.unquote(rest)?;
+                    return (rest == " $PATH").then_some(ProfileSetting::Path { dir });
+                }
+                if let Some(rest) = line.strip_prefix("test -f ") {
+                    let (script, _) = self.unquote(rest)?;
+                    return Some(ProfileSetting::Source { script });
+                }
+                ("set -gx ", line.strip_prefix("set -gx ")?)
+            }
+            Shell::Bash | Shell::Zsh => {
+                if let Some(rest) = line.strip_prefix("export PATH=") {
+                    let (dir, rest) = self.unquote(rest)?;
+                    return (rest == ":\"$PATH\"").then_some(ProfileSetting::Path { dir });
+                }
+                if let Some(rest) = line.strip_prefix("[ -f ") {
+                    let (script, _) = self.unquote(rest)?;
+                    return Some(ProfileSetting::Source { script });
+                }
+                ("export ", line.strip_prefix("export ")?)
+            }
+        };
+
+        let separator = if command == "set -gx " { ' ' } else { '=' };
+        let (key, quoted) = rest.split_once(separator)?;
+        let (value, _) = self.unquote(quoted)?;
+        Some(ProfileSetting::Env { key: key.to_string(), value })
+    }
+}
+
+// Edits the marked NUFFI block of one shell profile, leaving everything outside it untouched
+pub struct ShellProfile {
+    pub shell: Shell,
+    pub path: PathBuf,
+}
+
+impl ShellProfile {
+    pub fn new(shell: Shell, path: PathBuf) -> Self {
+        Self { shell, path }
+    }
+
+    fn read(&self) -> Result<String, String> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        }
+    }
+
+    pub fn entries(&self) -> Result<Vec<ProfileEntry>, String> {
+        let content = self.read()?;
+        let mut entries = Vec::new();
+        let mut in_block = false;
+
+        for line in content.lines() {
+            match line.trim() {
+                BLOCK_START => in_block = true,
+                BLOCK_END => in_block = false,
+                line if in_block => {
+                    let (command, owners) = match line.rsplit_once(OWNER_TAG) {
+                        Some(split) => split,
+                        None => continue,
+                    };
+                    if let Some(setting) = self.shell.parse(command) {
+                        entries.push(ProfileEntry {
+                            owners: owners.split(',').map(|o| o.to_string()).collect(),
+                            setting,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(entries)
+    }
+
+    // Idempotent: returns false when the profile already had every setting for `owner`
+    pub fn add(&self, owner: &str, settings: &[ProfileSetting]) -> Result<bool, String> {
+        validate_owner(owner)?;
+        let mut entries = self.entries()?;
+        let before = entries.clone();
+
+        for setting in settings {
+            if let ProfileSetting::Env { key, .. } = setting {
+                validate_env_key(key)?;
+            }
+            if self.shell.render(setting).contains('\n') {
+                return Err(format!("Profile entries must fit on one line: {:?}", setting));
+            }
+            match entries.iter_mut().find(|entry| same_target(&entry.setting, setting)) {
+                Some(entry) if entry.setting == *setting => {
+                    if !entry.owners.iter().any(|o| o == owner) {
+                        entry.owners.push(owner.to_string());
+                    }
+                }
+                // A variable set to a different value now belongs to whoever set it last
+                Some(entry) => {
+                    entry.setting = setting.clone();
+                    entry.owners = vec![owner.to_string()];
+                }
+                None => entries.push(ProfileEntry {
+                    owners: vec![owner.to_string()],
+                    setting: setting.clone(),
+                }),
+            }
+        }
+
+        if entries == before {
+            return Ok(false);
+        }
+        self.write(&entries)?;
+        Ok(true)
+    }
+
+    // Drops `owner` from every entry; entries nobody owns any more are removed, and so is an empty block
+    pub fn remove_owner(&self, owner: &str) -> Result<bool, String> {
+        let mut entries = self.entries()?;
+        let before = entries.clone();
+        for entry in &mut entries {
+            entry.owners.retain(|o| o != owner);
+        }
+        entries.retain(|entry| !entry.owners.is_empty());
+
+        if entries == before {
+            return Ok(false);
+        }
+        self.write(&entries)?;
+        Ok(true)
+    }
+
+    fn write(&self, entries: &[ProfileEntry]) -> Result<(), String> {
+        let content = self.read()?;
+        let mut lines: Vec<&str> = content.lines().collect();
+
+        // Take out the old block, along with the blank line that was added in front of it
+        let start = lines.iter().position(|l| l.trim() == BLOCK_START);
+        let end = lines.iter().position(|l| l.trim() == BLOCK_END);
+        let insert_at = match (start, end) {
+            (Some(start), Some(end)) if end > start => {
+                lines.drain(start..=end);
+                if start > 0 && lines[start - 1].trim().is_empty() {
+                    lines.remove(start - 1);
+                    start - 1
+                } else {
+                    start
+                }
+            }
+            _ => lines.len(),
+        };
+
+        let mut block: Vec<String> = Vec::new();
+        if !entries.is_empty() {
+            if insert_at > 0 {
+                block.push(String::new());
+            }
+            block.push(BLOCK_START.to_string());
+            block.push(BLOCK_NOTE.to_string());
+            for entry in entries {
+                block.push(format!("{}{}{}", self.shell.render(&entry.setting), OWNER_TAG, entry.owners.join(",")));
+            }
+            block.push(BLOCK_END.to_string());
+        }
+
+        let mut updated: Vec<String> = lines[..insert_at].iter().map(|l| l.to_string()).collect();
+        updated.extend(block);
+        updated.extend(lines[insert_at..].iter().map(|l| l.to_string()));
+
+        let mut updated = updated.join("\n");
+        if !updated.is_empty() {
+            updated.push('\n');
+        }
+        if updated.is_empty() && !self.path.exists() {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&self.path, updated).map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+// PATH entries are identified by directory, variables by name, scripts by path
+fn same_target(a: &ProfileSetting, b: &ProfileSetting) -> bool {
+    match (a, b) {
+        (ProfileSetting::Path { dir: a }, ProfileSetting::Path { dir: b }) => a == b,
+        (ProfileSetting::Env { key: a, .. }, ProfileSetting::Env { key: b, .. }) => a == b,
+        (ProfileSetting::Source { script: a }, ProfileSetting::Source { script: b }) => a == b,
+        _ => false,
+    }
+}
+
+fn validate_owner(owner: &str) -> Result<(), String> {
+    if owner.is_empty() || owner.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!("Invalid profile owner: {:?}", owner));
+    }
+    Ok(())
+}
+
+fn validate_env_key(key: &str) -> Result<(), String> {
+    let valid = key.chars().next().map(|c| c.is_ascii_alphabetic() || c == '_').unwrap_or(false)
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || key == "PATH" {
+        return Err(format!("Invalid environment variable name: {}", key));
+    }
+    Ok(())
+}
+
+// Activation scripts carry the workspace's environment variables, which can hold secrets, so only
+// the user can read them, including scripts written by an earlier version
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::io::Write::write_all(&mut file, content.as_bytes())
+}
+
+// The profiles of every shell the user has, rooted at their home directory
+#[derive(Debug, Clone)]
+pub struct ShellProfiles {
+    home: PathBuf,
+    shells: Vec<Shell>,
+}
+
+impl ShellProfiles {
+    pub fn new(home: PathBuf, shells: Vec<Shell>) -> Self {
+        Self { home, shells }
+    }
+
+    // Windows PATH is managed by the installers themselves, so there is nothing to edit there
+    pub fn detect() -> Self {
+        let home = dirs::home_dir().unwrap_or_default();
+        let shells = if cfg!(windows) { Vec::new() } else { Shell::detect(&home) };
+        Self::new(home, shells)
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    pub fn profiles(&self) -> Vec<ShellProfile> {
+        self.shells
+            .iter()
+            .map(|shell| ShellProfile::new(*shell, shell.profile_path(&self.home)))
+            .collect()
+    }
+
+    // Manifest PATH updates, with `~` expanded; Windows-style entries are skipped
+    pub fn path_settings(path_updates: &[String]) -> Vec<ProfileSetting> {
+        path_updates
+            .iter()
+            .filter(|dir| !dir.contains('%'))
+            .map(|dir| ProfileSetting::Path { dir: expand_home(dir).display().to_string() })
+            .collect()
+    }
+
+    // Returns the profiles that changed
+    pub fn add(&self, owner: &str, settings: &[ProfileSetting]) -> Result<Vec<ShellProfile>, String> {
+        let mut changed = Vec::new();
+        if settings.is_empty() {
+            return Ok(changed);
+        }
+        for profile in self.profiles() {
+            if profile.add(owner, settings)? {
+                changed.push(profile);
+            }
+        }
+        Ok(changed)
+    }
+
+    pub fn remove_owner(&self, owner: &str) -> Result<Vec<ShellProfile>, String> {
+        let mut changed = Vec::new();
+        for profile in self.profiles() {
+            if profile.remove_owner(owner)? {
+                changed.push(profile);
+            }
+        }
+        Ok(changed)
+    }
+
+    pub fn activation_dir(&self, workspace_id: &str) -> PathBuf {
+        self.home.join(".config").join("nuffi").join("workspaces").join(workspace_id)
+    }
+
+    // Writes `activate.sh` / `activate.fish` with the workspace's variables and tool directories.
+    // With `persist`, every profile sources the script so new shells start inside the workspace.
+    pub fn write_activation(&self, workspace: &Workspace, persist: bool) -> Result<Vec<PathBuf>, String> {
+        validate_owner(&workspace.id)?;
+        // The id, not the name: names can repeat and change, and processes outlive both
+        let mut settings = vec![ProfileSetting::Env { key: WORKSPACE_ENV_VAR.to_string(), value: workspace.id.clone() }];
+        let mut variables: Vec<(&String, &String)> = workspace.config.environment_variables.iter().collect();
+        variables.sort();
+        for (key, value) in variables {
+            // A PATH override becomes PATH entries so the rest of the user's PATH survives
+            if key == "PATH" {
+                settings.extend(value.split(':').filter(|dir| !dir.is_empty()).map(|dir| ProfileSetting::Path { dir: dir.to_string() }));
+                continue;
+            }
+            validate_env_key(key)?;
+            settings.push(ProfileSetting::Env { key: key.clone(), value: value.clone() });
+        }
+        for tool in &workspace.tools {
+            if let Some(dir) = Path::new(&tool.path).parent().filter(|dir| dir.is_absolute()) {
+                let setting = ProfileSetting::Path { dir: dir.display().to_string() };
+                if !settings.contains(&setting) {
+                    settings.push(setting);
+                }
+            }
+        }
+
+        let dir = self.activation_dir(&workspace.id);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut scripts = Vec::new();
+        for shell in [Shell::Bash, Shell::Fish] {
+            let script = dir.join(format!("activate.{}", shell.script_extension()));
+            let mut content = format!("# NUFFI activation script for workspace {}\n", workspace.name);
+            for setting in &settings {
+                content.push_str(&shell.render(setting));
+                content.push('\n');
+            }
+            write_private(&script, &content).map_err(|e| format!("Failed to write {}: {}", script.display(), e))?;
+            scripts.push(script);
+        }
+
+        if persist {
+            let owner = activation_owner(&workspace.id);
+            for profile in self.profiles() {
+                let script = dir.join(format!("activate.{}", profile.shell.script_extension()));
+                profile.add(&owner, &[ProfileSetting::Source { script: script.display().to_string() }])?;
+            }
+        }
+        Ok(scripts)
+    }
+
+    pub fn remove_activation(&self, workspace_id: &str) -> Result<(), String> {
+        self.remove_owner(&activation_owner(workspace_id))?;
+        match std::fs::remove_dir_all(self.activation_dir(workspace_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove activation scripts: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn activation_owner(workspace_id: &str) -> String {
+    format!("workspace:{}", workspace_id)
+}
+
+// Tauri commands
+#[command]
+pub async fn get_shell_profiles() -> Result<Vec<(Shell, String, Vec<ProfileEntry>)>, String> {
+    ShellProfiles::detect()
+        .profiles()
+        .into_iter()
+        .map(|profile| Ok((profile.shell, profile.path.display().to_string(), profile.entries()?)))
+        .collect()
+}
+
+#[command]
+pub async fn write_workspace_activation(
+    manager: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    workspace_id: String,
+    persist: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let workspace = manager
+        .lock()
+        .unwrap()
+        .get_workspace(&workspace_id)
+        .ok_or_else(|| "Workspace not found".to_string())?;
+    let scripts = ShellProfiles::detect().write_activation(&workspace, persist.unwrap_or(false))?;
+    Ok(scripts.iter().map(|script| script.display().to_string()).collect())
+}
+
+#[command]
+pub async fn remove_workspace_activation(workspace_id: String) -> Result<(), String> {
+    ShellProfiles::detect().remove_activation(&workspace_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let settings = vec![
+            ProfileSetting::Path { dir: "/opt/it's here/bin".to_string() },
+            ProfileSetting::Env { key: "GOPATH".to_string(), value: r"C:\go 'x'".to_string() },
+            ProfileSetting::Source { script: "/home/me/.config/nuffi/activate.sh".to_string() },
+        ];
+        for shell in Shell::ALL {
+            for setting in &settings {
+                assert_eq!(shell.parse(&shell.render(setting)).as_ref(), Some(setting), "{:?}", shell);
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_edits_are_idempotent_and_reversible() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".zshrc");
+        let original = "alias ll='ls -l'\nexport EDITOR=vim\n";
+        std::fs::write(&path, original).unwrap();
+        let profile = ShellProfile::new(Shell::Zsh, path.clone());
+
+        let node = [ProfileSetting::Path { dir: "/usr/local/opt/node@20/bin".to_string() }];
+        assert!(profile.add("nodejs", &node).unwrap());
+        assert!(!profile.add("nodejs", &node).unwrap());
+        assert!(profile.add("npm", &node).unwrap());
+        assert!(profile.add("go", &[ProfileSetting::Env { key: "GOPATH".to_string(), value: "/go".to_string() }]).unwrap());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(original));
+        assert_eq!(content.matches("node@20").count(), 1);
+        assert!(content.contains("# nuffi:nodejs,npm"));
+        assert!(profile.add("go", &[ProfileSetting::Env { key: "PATH".to_string(), value: "/x".to_string() }]).is_err());
+
+        // The PATH entry stays until its last owner is uninstalled
+        profile.remove_owner("nodejs").unwrap();
+        assert_eq!(profile.entries().unwrap().len(), 2);
+        profile.remove_owner("npm").unwrap();
+        profile.remove_owner("go").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+        assert!(!profile.remove_owner("go").unwrap());
+    }
+
+    #[test]
+    fn test_workspace_activation() {
+        let dir = tempdir().unwrap();
+        let profiles = ShellProfiles::new(dir.path().to_path_buf(), vec![Shell::Bash, Shell::Fish]);
+        let mut manager = WorkspaceManager::new();
+        let mut workspace = manager
+            .create_workspace(crate::workspace_manager::CreateWorkspaceRequest {
+                name: "Backend".to_string(),
+                workspace_type: "development".to_string(),
+                tools: Vec::new(),
+                config: None,
+            })
+            .unwrap();
+        workspace.config.environment_variables.insert("DATABASE_URL".to_string(), "postgres://localhost/app".to_string());
+
+        let scripts = profiles.write_activation(&workspace, true).unwrap();
+        let bash = std::fs::read_to_string(&scripts[0]).unwrap();
+        assert!(bash.contains("export DATABASE_URL='postgres://localhost/app'"));
+        let fish = std::fs::read_to_string(&scripts[1]).unwrap();
+        assert!(fish.contains(&format!("set -gx NUFFI_WORKSPACE '{}'", workspace.id)));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for script in &scripts {
+                assert_eq!(std::fs::metadata(script).unwrap().permissions().mode() & 0o777, 0o600);
+            }
+        }
+        assert!(std::fs::read_to_string(dir.path().join(".bashrc")).unwrap().contains(". '"));
+
+        profiles.remove_activation(&workspace.id).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join(".bashrc")).unwrap(), "");
+        assert!(!profiles.activation_dir(&workspace.id).exists());
+    }
+}
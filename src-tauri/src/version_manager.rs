@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::install_planner::PlannedDownload;
+use crate::install_verifier::find_in_path;
+use crate::installer::InstallCommand;
+
+const NVM_RELEASE: &str = "v0.40.1";
+const PYENV_RELEASE: &str = "v2.4.17";
+const RUSTUP_RELEASE: &str = "1.27.1";
+const MISE_RELEASE: &str = "v2024.12.0";
+
+// nvm is a shell function, so it is sourced first; arguments follow as positional parameters.
+// With NVM_SYMLINK_CURRENT, `$NVM_DIR/current` points at the active version and can go on PATH.
+const NVM_SCRIPT: &str = r#"export NVM_SYMLINK_CURRENT=true; . "${NVM_DIR:-$HOME/.nvm}/nvm.sh" && nvm "$@""#;
+
+// Languages installed through mise, as (language, mise plugin)
+const MISE_LANGUAGES: &[(&str, &str)] = &[
+    ("Go", "go"),
+    ("Ruby", "ruby"),
+    ("Java", "java"),
+    ("Deno", "deno"),
+    ("Bun", "bun"),
+    ("Elixir", "elixir"),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionManagerKind {
+    Pyenv,
+    Nvm,
+    Rustup,
+    Mise,
+}
+
+// A language version installed by a version manager, as reported to the scanner
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManagedVersion {
+    pub language: String,
+    pub manager: VersionManagerKind,
+    pub version: String,
+    pub path: String,
+}
+
+// Installs language versions side by side through the language's version manager
+#[derive(Debug, Clone)]
+pub struct VersionManager {
+    pub kind: VersionManagerKind,
+    pub language: String,
+    home: PathBuf,
+}
+
+impl VersionManager {
+    pub fn for_language(language: &str) -> Option<Self> {
+        let kind = match language {
+            "Python" => VersionManagerKind::Pyenv,
+            "Node.js" => VersionManagerKind::Nvm,
+            "Rust" => VersionManagerKind::Rustup,
+            _ if MISE_LANGUAGES.iter().any(|(name, _)| *name == language) => VersionManagerKind::Mise,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            language: language.to_string(),
+            home: dirs::home_dir().unwrap_or_default(),
+        })
+    }
+
+    pub fn with_home(mut self, home: PathBuf) -> Self {
+        self.home = home;
+        self
+    }
+
+    pub fn languages() -> Vec<&'static str> {
+        ["Python", "Node.js", "Rust"]
+            .into_iter()
+            .chain(MISE_LANGUAGES.iter().map(|(name, _)| *name))
+            .collect()
+    }
+
+    fn plugin(&self) -> &'static str {
+        MISE_LANGUAGES
+            .iter()
+            .find(|(name, _)| *name == self.language)
+            .map(|(_, plugin)| *plugin)
+            .unwrap_or_default()
+    }
+
+    pub fn default_version(&self) -> &'static str {
+        match self.kind {
+            VersionManagerKind::Pyenv => "3.11.0",
+            VersionManagerKind::Nvm => "18",
+            VersionManagerKind::Rustup => "stable",
+            VersionManagerKind::Mise => "latest",
+        }
+    }
+
+    // The manager's own environment variable wins over the default location
+    fn env_dir(&self, variable: &str, default: &str) -> PathBuf {
+        std::env::var_os(variable)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.home.join(default))
+    }
+
+    pub fn root(&self) -> PathBuf {
+        match self.kind {
+            VersionManagerKind::Pyenv => self.env_dir("PYENV_ROOT", ".pyenv"),
+            VersionManagerKind::Nvm => self.env_dir("NVM_DIR", ".nvm"),
+            VersionManagerKind::Rustup => self.env_dir("RUSTUP_HOME", ".rustup"),
+            VersionManagerKind::Mise => self.env_dir("MISE_DATA_DIR", ".local/share/mise"),
+        }
+    }
+
+    // Directories that have to be on PATH for the manager and the versions it selects
+    pub fn bin_dirs(&self) -> Vec<PathBuf> {
+        match self.kind {
+            VersionManagerKind::Pyenv => vec![self.root().join("bin"), self.root().join("shims")],
+            VersionManagerKind::Nvm => vec![self.root().join("current").join("bin")],
+            VersionManagerKind::Rustup => vec![self.env_dir("CARGO_HOME", ".cargo").join("bin")],
+            VersionManagerKind::Mise => vec![self.home.join(".local").join("bin"), self.root().join("shims")],
+        }
+    }
+
+    pub fn path_updates(&self) -> Vec<String> {
+        self.bin_dirs().iter().map(|dir| dir.display().to_string()).collect()
+    }
+
+    fn binary(&self) -> &'static str {
+        match self.kind {
+            VersionManagerKind::Pyenv => "pyenv",
+            VersionManagerKind::Nvm => "nvm",
+            VersionManagerKind::Rustup => "rustup",
+            VersionManagerKind::Mise => "mise",
+        }
+    }
+
+    pub fn is_installed(&self) -> bool {
+        match self.kind {
+            VersionManagerKind::Nvm => self.root().join("nvm.sh").is_file(),
+            _ => find_in_path(self.binary(), &self.search_path()).is_some(),
+        }
+    }
+
+    // PATH with the manager's directories appended, so a freshly bootstrapped manager is found
+    pub fn search_path(&self) -> OsString {
+        let current = std::env::var_os("PATH").unwrap_or_default();
+        let dirs: Vec<PathBuf> = std::env::split_paths(&current).chain(self.bin_dirs()).collect();
+        std::env::join_paths(dirs).unwrap_or(current)
+    }
+
+    // Install scripts come from a pinned release through the download cache, which refuses them
+    // unverified unless the user opts in; the step runs its private copy of this path.
+    pub fn bootstrap_download(&self) -> Option<PlannedDownload> {
+        let url = match self.kind {
+            VersionManagerKind::Rustup => format!("https://raw.githubusercontent.com/rust-lang/rustup/{}/rustup-init.sh", RUSTUP_RELEASE),
+            VersionManagerKind::Mise => format!("https://github.com/jdx/mise/releases/download/{}/install.sh", MISE_RELEASE),
+            VersionManagerKind::Pyenv | VersionManagerKind::Nvm => return None,
+        };
+        Some(PlannedDownload {
+            url,
+            checksum: None,
+            signature: None,
+            destination: std::env::temp_dir()
+                .join("nuffi-downloads")
+                .join(format!("{}-install.sh", self.binary()))
+                .display()
+                .to_string(),
+        })
+    }
+
+    // Installs the manager itself. Nothing is piped into a shell: repositories are cloned at a
+    // release tag and install scripts run from the staged copy of `bootstrap_download`.
+    pub fn bootstrap_commands(&self) -> Vec<InstallCommand> {
+        let root = self.root().display().to_string();
+        let script = self.bootstrap_download().map(|download| download.destination).unwrap_or_default();
+        match self.kind {
+            VersionManagerKind::Pyenv => vec![InstallCommand::new(
+                "git",
+                &["clone", "--depth", "1", "--branch", PYENV_RELEASE, "https://github.com/pyenv/pyenv.git", &root],
+            )],
+            VersionManagerKind::Nvm => vec![InstallCommand::new(
+                "git",
+                &["clone", "--depth", "1", "--branch", NVM_RELEASE, "https://github.com/nvm-sh/nvm.git", &root],
+            )],
+            VersionManagerKind::Rustup => vec![InstallCommand::new(
+                "sh",
+                &[&script, "-y", "--no-modify-path", "--default-toolchain", "none"],
+            )],
+            VersionManagerKind::Mise => vec![InstallCommand::new("sh", &[&script])],
+        }
+    }
+
+    // Bootstraps the manager when it is missing, installs `version` and makes it the user default
+    pub fn install_commands(&self, version: Option<&str>) -> Result<Vec<InstallCommand>, String> {
+        let version = version.unwrap_or(self.default_version());
+        validate_version(version)?;
+
+        let mut commands = if self.is_installed() { Vec::new() } else { self.bootstrap_commands() };
+        match self.kind {
+            VersionManagerKind::Pyenv => {
+                commands.push(InstallCommand::new("pyenv", &["install", "-s", version]));
+                commands.push(InstallCommand::new("pyenv", &["global", version]));
+            }
+            VersionManagerKind::Nvm => {
+                commands.push(nvm_command(&["install", version]));
+                commands.push(nvm_command(&["alias", "default", version]));
+            }
+            VersionManagerKind::Rustup => {
+                commands.push(InstallCommand::new("rustup", &["toolchain", "install", version]));
+                commands.push(InstallCommand::new("rustup", &["default", version]));
+            }
+            VersionManagerKind::Mise => {
+                let tool = format!("{}@{}", self.plugin(), version);
+                commands.push(InstallCommand::new("mise", &["install", &tool]));
+                commands.push(InstallCommand::new("mise", &["use", "--global", &tool]));
+            }
+        }
+        Ok(commands)
+    }
+
+    pub fn local_version_file(&self, directory: &Path) -> PathBuf {
+        directory.join(match self.kind {
+            VersionManagerKind::Pyenv => ".python-version",
+            VersionManagerKind::Nvm => ".nvmrc",
+            VersionManagerKind::Rustup => "rust-toolchain.toml",
+            VersionManagerKind::Mise => ".tool-versions",
+        })
+    }
+
+    // Pins `version` for a project directory in the file the manager reads on `cd`
+    pub fn set_local_version(&self, directory: &Path, version: &str) -> Result<PathBuf, String> {
+        validate_version(version)?;
+        if !directory.is_dir() {
+            return Err(format!("{} is not a directory", directory.display()));
+        }
+
+        let file = self.local_version_file(directory);
+        let content = match self.kind {
+            VersionManagerKind::Pyenv | VersionManagerKind::Nvm => format!("{}\n", version),
+            VersionManagerKind::Rustup => format!("[toolchain]\nchannel = \"{}\"\n", version),
+            // .tool-versions is shared by every language, so only this plugin's line is replaced
+            VersionManagerKind::Mise => {
+                let existing = std::fs::read_to_string(&file).unwrap_or_default();
+                let mut lines: Vec<String> = existing
+                    .lines()
+                    .filter(|line| line.split_whitespace().next() != Some(self.plugin()))
+                    .map(|line| line.to_string())
+                    .collect();
+                lines.push(format!("{} {}", self.plugin(), version));
+                format!("{}\n", lines.join("\n"))
+            }
+        };
+
+        std::fs::write(&file, content).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+        Ok(file)
+    }
+
+    pub fn installed_versions(&self) -> Vec<ManagedVersion> {
+        let versions_dir = match self.kind {
+            VersionManagerKind::Pyenv => self.root().join("versions"),
+            VersionManagerKind::Nvm => self.root().join("versions").join("node"),
+            VersionManagerKind::Rustup => self.root().join("toolchains"),
+            VersionManagerKind::Mise => self.root().join("installs").join(self.plugin()),
+        };
+
+        let mut versions: Vec<ManagedVersion> = std::fs::read_dir(&versions_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    // mise links aliases such as `latest` next to the real versions
+                    .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                    .map(|entry| ManagedVersion {
+                        language: self.language.clone(),
+                        manager: self.kind,
+                        version: entry.file_name().to_string_lossy().trim_start_matches('v').to_string(),
+                        path: entry.path().display().to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        versions
+    }
+}
+
+fn validate_version(version: &str) -> Result<(), String> {
+    let valid = !version.is_empty()
+        && !version.starts_with('-')
+        && version.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+    if !valid {
+        return Err(format!("Invalid version: {:?}", version));
+    }
+    Ok(())
+}
+
+pub fn nvm_command(args: &[&str]) -> InstallCommand {
+    let mut argv = vec!["-c", NVM_SCRIPT, "nvm"];
+    argv.extend(args);
+    InstallCommand::new("bash", &argv)
+}
+
+// The nvm arguments of a command built by `nvm_command`
+pub fn nvm_args(command: &InstallCommand) -> Option<Vec<String>> {
+    match command.args.as_slice() {
+        [flag, script, name, rest @ ..] if command.program == "bash" && flag == "-c" && script == NVM_SCRIPT && name == "nvm" => {
+            Some(std::iter::once("nvm".to_string()).chain(rest.iter().cloned()).collect())
+        }
+        _ => None,
+    }
+}
+
+// PATH for install commands: the app's PATH plus every version manager's directories
+pub fn manager_search_path() -> OsString {
+    let current = std::env::var_os("PATH").unwrap_or_default();
+    let dirs: Vec<PathBuf> = std::env::split_paths(&current)
+        .chain(
+            VersionManager::languages()
+                .into_iter()
+                .filter_map(VersionManager::for_language)
+                .flat_map(|manager| manager.bin_dirs()),
+        )
+        .collect();
+    std::env::join_paths(dirs).unwrap_or(current)
+}
+
+pub fn managed_versions() -> Vec<ManagedVersion> {
+    VersionManager::languages()
+        .into_iter()
+        .filter_map(VersionManager::for_language)
+        .flat_map(|manager| manager.installed_versions())
+        .collect()
+}
+
+fn manager_for(language: &str) -> Result<VersionManager, String> {
+    VersionManager::for_language(language).ok_or_else(|| format!("No version manager for {}", language))
+}
+
+// Tauri commands
+#[command]
+pub async fn get_managed_versions(language: Option<String>) -> Result<Vec<ManagedVersion>, String> {
+    match language {
+        Some(language) => Ok(manager_for(&language)?.installed_versions()),
+        None => Ok(managed_versions()),
+    }
+}
+
+#[command]
+pub async fn set_project_version(language: String, version: String, directory: String) -> Result<String, String> {
+    let file = manager_for(&language)?.set_local_version(Path::new(&directory), &version)?;
+    Ok(file.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package_command::PackageInstall;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_install_commands() {
+        let dir = tempdir().unwrap();
+        let node = VersionManager::for_language("Node.js").unwrap().with_home(dir.path().to_path_buf());
+        let commands = node.install_commands(Some("20")).unwrap();
+
+        // nvm runs through bash with nvm.sh sourced, and its installs can still be undone
+        let install = commands.iter().find_map(PackageInstall::parse).unwrap();
+        assert_eq!(install.manager, "nvm");
+        assert_eq!(nvm_args(&install.undo(&install.packages)).unwrap(), vec!["nvm", "uninstall", "20"]);
+
+        let go = VersionManager::for_language("Go").unwrap().with_home(dir.path().to_path_buf());
+        let last = go.install_commands(Some("1.22")).unwrap().pop().unwrap();
+        assert_eq!(last.display(), "mise use --global go@1.22");
+        assert!(go.install_commands(Some("1.22; rm -rf /")).is_err());
+        assert!(VersionManager::for_language("COBOL").is_none());
+
+        let rust = VersionManager::for_language("Rust").unwrap().with_home(dir.path().to_path_buf());
+        let last = rust.install_commands(Some("1.75.0")).unwrap().pop().unwrap();
+        assert_eq!(last.display(), "rustup default 1.75.0");
+    }
+
+    #[test]
+    fn test_bootstrap_runs_the_planned_download() {
+        let rust = VersionManager::for_language("Rust").unwrap();
+        let download = rust.bootstrap_download().unwrap();
+        assert!(download.url.contains(RUSTUP_RELEASE));
+        let commands = rust.bootstrap_commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].args[0], download.destination);
+        assert!(!commands.iter().any(|command| command.program == "curl"));
+
+        let python = VersionManager::for_language("Python").unwrap();
+        assert!(python.bootstrap_download().is_none());
+        assert!(python.bootstrap_commands()[0].args.contains(&PYENV_RELEASE.to_string()));
+    }
+
+    #[test]
+    fn test_local_versions_and_listing() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(&project).unwrap();
+
+        let go = VersionManager::for_language("Go").unwrap().with_home(dir.path().to_path_buf());
+        let ruby = VersionManager::for_language("Ruby").unwrap().with_home(dir.path().to_path_buf());
+        go.set_local_version(&project, "1.21").unwrap();
+        ruby.set_local_version(&project, "3.3.0").unwrap();
+        go.set_local_version(&project, "1.22").unwrap();
+        assert_eq!(std::fs::read_to_string(project.join(".tool-versions")).unwrap(), "ruby 3.3.0\ngo 1.22\n");
+
+        let rust = VersionManager::for_language("Rust").unwrap();
+        let file = rust.set_local_version(&project, "1.75.0").unwrap();
+        assert!(std::fs::read_to_string(file).unwrap().contains("channel = \"1.75.0\""));
+
+        if std::env::var_os("MISE_DATA_DIR").is_none() {
+            std::fs::create_dir_all(dir.path().join(".local/share/mise/installs/go/1.22.1")).unwrap();
+            let versions = go.installed_versions();
+            assert_eq!(versions.len(), 1);
+            assert_eq!(versions[0].version, "1.22.1");
+        }
+    }
+}
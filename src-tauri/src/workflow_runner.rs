@@ -0,0 +1,317 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{command, State};
+
+use crate::command_runner::{CommandOutput, CommandSpec};
+use crate::database::ConfigStore;
+use crate::models::{
+    CoreError, Environment, Workflow, WorkflowError, WorkflowExecution, WorkflowStatus, WorkflowStep,
+    WorkflowStepResult, WorkflowStepStatus, WorkflowStepType,
+};
+
+// Where steps run and what they see, taken from the workflow's environment
+#[derive(Debug, Clone, Default)]
+struct StepContext {
+    working_dir: Option<PathBuf>,
+    env: HashMap<String, String>,
+}
+
+impl StepContext {
+    fn for_environment(environment: Option<&Environment>) -> Self {
+        environment
+            .map(|environment| Self {
+                working_dir: Some(environment.path.clone()),
+                env: environment.environment_variables.clone(),
+            })
+            .unwrap_or_default()
+    }
+
+    fn command(&self, step: &WorkflowStep) -> Result<Option<CommandSpec>, String> {
+        let mut spec = match step.step_type {
+            // Workflow commands are written by the user, so they get the full shell
+            WorkflowStepType::Command => CommandSpec::shell(step.command.as_deref().ok_or("No command")?),
+            WorkflowStepType::Script => {
+                let script = Path::new(step.script_path.as_deref().ok_or("No script path")?);
+                let script = match &self.working_dir {
+                    Some(dir) if script.is_relative() => dir.join(script),
+                    _ => script.to_path_buf(),
+                };
+                CommandSpec::from_argv(vec![script.to_string_lossy().into_owned()])
+            }
+            _ => return Ok(None),
+        };
+        // Step parameters are passed as environment variables, over the environment's own
+        for (key, value) in self.env.iter().chain(&step.parameters) {
+            spec = spec.env(key, value);
+        }
+        if let Some(dir) = &self.working_dir {
+            spec = spec.current_dir(dir);
+        }
+        Ok(Some(spec))
+    }
+}
+
+// Runs the workflow's steps now, in dependency order, recording the execution in the store.
+// A failed step stops the run unless it continues on error; steps depending on it are skipped.
+pub async fn run_workflow(store: &ConfigStore, workflow: &Workflow) -> Result<WorkflowExecution, CoreError> {
+    if !workflow.is_enabled() {
+        return Err(WorkflowError::ExecutionFailed(format!("Workflow '{}' is disabled", workflow.name)).into());
+    }
+    workflow.validate()?;
+    let environment = match &workflow.environment_id {
+        Some(id) => Some(store.get_environment(id).await?),
+        None => None,
+    };
+    let context = StepContext::for_environment(environment.as_ref());
+
+    let mut workflow = workflow.clone();
+    let mut execution = WorkflowExecution::new(workflow.id.clone(), None);
+    store.create_workflow_execution(&execution).await?;
+    workflow.status = WorkflowStatus::Running;
+    let outcome = match store.update_workflow(&workflow).await {
+        Ok(()) => run_steps(store, &workflow, &context, &mut execution).await,
+        Err(e) => Err(e),
+    };
+
+    // The workflow is back to idle however the run ended, so it never stays marked as running
+    if let Err(e) = &outcome {
+        execution.complete(WorkflowStatus::Failed, Some(e.to_string()));
+        let _ = store.update_workflow_execution(&execution).await;
+    }
+    workflow.status = WorkflowStatus::Idle;
+    workflow.last_executed = Some(Utc::now());
+    workflow.execution_count += 1;
+    workflow.updated_at = Utc::now();
+    store.update_workflow(&workflow).await?;
+    outcome.map(|()| execution)
+}
+
+async fn run_steps(
+    store: &ConfigStore,
+    workflow: &Workflow,
+    context: &StepContext,
+    execution: &mut WorkflowExecution,
+) -> Result<(), CoreError> {
+    let mut statuses: HashMap<String, WorkflowStepStatus> = HashMap::new();
+    let mut failure: Option<String> = None;
+    while statuses.len() < workflow.steps.len() {
+        // Steps are taken in the order they were added once everything they depend on has finished
+        let next = workflow.steps.iter().find(|step| {
+            !statuses.contains_key(&step.id) && step.depends_on.iter().all(|dep| statuses.contains_key(dep))
+        });
+        let Some(step) = next else {
+            break;
+        };
+
+        let blocked = step
+            .depends_on
+            .iter()
+            .find(|dep| statuses.get(*dep) != Some(&WorkflowStepStatus::Completed));
+        let result = if let Some(dep) = blocked {
+            let name = workflow.get_step(dep).map(|dep| dep.name.as_str()).unwrap_or(dep);
+            skipped(step, format!("step '{}' did not complete", name))
+        } else if failure.is_some() {
+            skipped(step, "an earlier step failed".to_string())
+        } else {
+            execution.add_log("info".to_string(), format!("Running step '{}'", step.name), Some(step.id.clone()));
+            run_step(step, context).await
+        };
+
+        match &result.status {
+            WorkflowStepStatus::Failed => {
+                let message = format!("Step '{}' failed: {}", step.name, result.error_message.as_deref().unwrap_or_default());
+                execution.add_log("error".to_string(), message.clone(), Some(step.id.clone()));
+                if !step.continue_on_error {
+                    failure = Some(message);
+                }
+            }
+            WorkflowStepStatus::Skipped => {
+                let message = format!("Skipped step '{}': {}", step.name, result.error_message.as_deref().unwrap_or_default());
+                execution.add_log("warn".to_string(), message, Some(step.id.clone()));
+            }
+            _ => {}
+        }
+        statuses.insert(step.id.clone(), result.status.clone());
+        execution.add_step_result(result);
+        store.update_workflow_execution(execution).await?;
+    }
+
+    // Dependencies on steps that aren't in the workflow can never be met
+    for step in workflow.steps.iter().filter(|step| !statuses.contains_key(&step.id)) {
+        execution.add_step_result(skipped(step, "its dependencies are not in the workflow".to_string()));
+    }
+
+    match failure {
+        Some(message) => execution.complete(WorkflowStatus::Failed, Some(message)),
+        None => execution.complete(WorkflowStatus::Completed, None),
+    }
+    store.update_workflow_execution(execution).await
+}
+
+fn skipped(step: &WorkflowStep, reason: String) -> WorkflowStepResult {
+    WorkflowStepResult {
+        step_id: step.id.clone(),
+        status: WorkflowStepStatus::Skipped,
+        started_at: Utc::now(),
+        completed_at: Some(Utc::now()),
+        output: None,
+        error_message: Some(reason),
+        exit_code: None,
+    }
+}
+
+// Tries the step up to `retry_count` more times after a failure or timeout
+async fn run_step(step: &WorkflowStep, context: &StepContext) -> WorkflowStepResult {
+    let started_at = Utc::now();
+    let spec = match context.command(step) {
+        Ok(Some(spec)) => spec,
+        Ok(None) => return skipped(step, format!("{:?} steps can't be run here", step.step_type)),
+        Err(e) => {
+            return WorkflowStepResult {
+                status: WorkflowStepStatus::Failed,
+                error_message: Some(e),
+                ..skipped(step, String::new())
+            }
+        }
+    };
+
+    let mut attempt = 0;
+    loop {
+        let outcome = run_with_timeout(&spec, step.timeout_seconds).await;
+        let failed = !matches!(&outcome, Ok(output) if output.success);
+        if failed && attempt < step.retry_count {
+            attempt += 1;
+            continue;
+        }
+
+        let (output, exit_code, error_message) = match outcome {
+            Ok(output) => {
+                let error = (!output.success).then(|| match output.exit_code {
+                    Some(code) => format!("exited with status {}: {}", code, output.stderr.trim()),
+                    None => format!("terminated by a signal: {}", output.stderr.trim()),
+                });
+                (Some(output.stdout), output.exit_code, error)
+            }
+            Err(e) => (None, None, Some(e)),
+        };
+        return WorkflowStepResult {
+            step_id: step.id.clone(),
+            status: if failed { WorkflowStepStatus::Failed } else { WorkflowStepStatus::Completed },
+            started_at,
+            completed_at: Some(Utc::now()),
+            output,
+            error_message,
+            exit_code,
+        };
+    }
+}
+
+// A step that times out is reported as failed, and its processes are killed before any retry
+async fn run_with_timeout(spec: &CommandSpec, timeout_seconds: Option<u64>) -> Result<CommandOutput, String> {
+    let spec = spec.clone().process_group();
+    let run = spec.run_async();
+    let result = match timeout_seconds {
+        Some(seconds) => tokio::time::timeout(Duration::from_secs(seconds), run)
+            .await
+            .map_err(|_| format!("timed out after {}s", seconds))?,
+        None => run.await,
+    };
+    result.map_err(|e| e.to_string())
+}
+
+// Tauri commands
+#[command]
+pub async fn execute_workflow(store: State<'_, ConfigStore>, workflow_id: String) -> Result<WorkflowExecution, String> {
+    let workflow = store.get_workflow(&workflow_id).await.map_err(|e| e.to_string())?;
+    run_workflow(&store, &workflow).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EnvironmentConfig, ProjectType};
+    use tempfile::tempdir;
+
+    fn command_step(name: &str, command: &str) -> WorkflowStep {
+        WorkflowStep::new(name.to_string(), WorkflowStepType::Command).with_command(command.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_run_workflow() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+        let project = tempdir().unwrap();
+        let mut config = EnvironmentConfig::new("app".to_string(), project.path().to_path_buf(), ProjectType::Rust);
+        config.environment_variables.insert("GREETING".to_string(), "hello".to_string());
+        let environment = store.create_environment(config).await.unwrap();
+
+        let mut workflow = Workflow::new("build".to_string(), "Build the app".to_string());
+        let write = command_step("write", "echo \"$GREETING $NAME\" > out.txt").with_parameter("NAME".to_string(), "world".to_string());
+        let read = command_step("read", "cat out.txt").depends_on_step(write.id.clone());
+        workflow.add_step(read).unwrap();
+        workflow.add_step(write).unwrap();
+        workflow.add_step(WorkflowStep::new("notify".to_string(), WorkflowStepType::Notification)).unwrap();
+        workflow.set_environment(environment.id.clone());
+        store.create_workflow(&workflow).await.unwrap();
+
+        let execution = run_workflow(&store, &workflow).await.unwrap();
+        assert_eq!(execution.status, WorkflowStatus::Completed);
+        // The dependency runs first, in the environment's directory with its variables
+        let steps: Vec<_> = execution.step_results.iter().map(|result| result.status.clone()).collect();
+        assert_eq!(steps, vec![WorkflowStepStatus::Completed, WorkflowStepStatus::Completed, WorkflowStepStatus::Skipped]);
+        assert_eq!(execution.step_results[1].output.as_deref(), Some("hello world\n"));
+
+        let stored = store.get_workflow_execution(&execution.id).await.unwrap();
+        assert_eq!(stored.status, WorkflowStatus::Completed);
+        assert_eq!(stored.step_results.len(), 3);
+        let workflow = store.get_workflow(&workflow.id).await.unwrap();
+        assert_eq!(workflow.execution_count, 1);
+        assert_eq!(workflow.status, WorkflowStatus::Idle);
+        assert!(workflow.last_executed.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_stops_the_run() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::new(&ConfigStore::url_for(dir.path())).await.unwrap();
+
+        let mut workflow = Workflow::new("test".to_string(), String::new());
+        let lint = command_step("lint", "exit 3").continue_on_error();
+        let compile = command_step("compile", "echo compiled >&2; exit 1").with_retry(1);
+        let test = command_step("test", "true").depends_on_step(compile.id.clone());
+        let slow = command_step("slow", "sleep 5");
+        workflow.add_step(lint).unwrap();
+        workflow.add_step(compile).unwrap();
+        workflow.add_step(test).unwrap();
+        workflow.add_step(slow).unwrap();
+        store.create_workflow(&workflow).await.unwrap();
+
+        let execution = run_workflow(&store, &workflow).await.unwrap();
+        assert_eq!(execution.status, WorkflowStatus::Failed);
+        assert_eq!(execution.error_message.as_deref(), Some("Step 'compile' failed: exited with status 1: compiled"));
+        let results = &execution.step_results;
+        assert_eq!(results[0].status, WorkflowStepStatus::Failed);
+        assert_eq!(results[0].exit_code, Some(3));
+        assert_eq!(results[1].status, WorkflowStepStatus::Failed);
+        assert_eq!(results[2].status, WorkflowStepStatus::Skipped);
+        assert_eq!(results[2].error_message.as_deref(), Some("step 'compile' did not complete"));
+        assert_eq!(results[3].status, WorkflowStepStatus::Skipped);
+
+        let marker = dir.path().join("finished");
+        let mut slow = Workflow::new("slow".to_string(), String::new());
+        // The marker is written by a grandchild, which has to die along with the step's shell
+        let sleep = command_step("sleep", &format!("sh -c \"sleep 2; touch '{}'\"; true", marker.display()));
+        slow.add_step(sleep.with_timeout(1).with_retry(1)).unwrap();
+        store.create_workflow(&slow).await.unwrap();
+        let execution = run_workflow(&store, &slow).await.unwrap();
+        assert_eq!(execution.step_results[0].error_message.as_deref(), Some("timed out after 1s"));
+        // Timed-out attempts don't keep running in the background
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+
+        slow.disable();
+        assert!(run_workflow(&store, &slow).await.is_err());
+    }
+}
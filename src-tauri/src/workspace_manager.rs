@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use tauri::command;
+use std::sync::{Arc, Mutex};
+use tauri::{command, State};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -66,12 +67,15 @@ pub struct CreateWorkspaceRequest {
 
 pub struct WorkspaceManager {
     workspaces: Vec<Workspace>,
+    // Ids deleted since the app started, so their leftover processes can be told apart
+    deleted: Vec<String>,
 }
 
 impl WorkspaceManager {
     pub fn new() -> Self {
         Self {
             workspaces: Vec::new(),
+            deleted: Vec::new(),
         }
     }
 
@@ -134,12 +138,17 @@ impl WorkspaceManager {
         self.workspaces.retain(|w| w.id != id);
         
         if self.workspaces.len() < initial_len {
+            self.deleted.push(id.to_string());
             Ok(())
         } else {
             Err("Workspace not found".to_string())
         }
     }
 
+    pub fn was_deleted(&self, id: &str) -> bool {
+        self.deleted.iter().any(|deleted| deleted == id)
+    }
+
     pub fn activate_workspace(&mut self, id: &str) -> Result<Workspace, String> {
         if let Some(workspace) = self.workspaces.iter_mut().find(|w| w.id == id) {
             workspace.status = "active".to_string();
@@ -158,54 +167,177 @@ impl WorkspaceManager {
             Err("Workspace not found".to_string())
         }
     }
+
+    // Describes every workspace that still relies on a tool, either directly or through another tool.
+    // `names` holds the aliases a tool is known by (manifest id, display name).
+    pub fn tool_dependents(&self, names: &[String], except_workspace: Option<&str>) -> Vec<String> {
+        let matches = |name: &str| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        let mut dependents = Vec::new();
+
+        for workspace in &self.workspaces {
+            for tool in &workspace.tools {
+                if matches(&tool.name) {
+                    if Some(workspace.id.as_str()) != except_workspace {
+                        dependents.push(format!("{} uses {}", workspace.name, tool.name));
+                    }
+                } else if let Some(dependency) = tool.dependencies.iter().find(|d| matches(d)) {
+                    dependents.push(format!("{} needs {} for {}", workspace.name, dependency, tool.name));
+                }
+            }
+        }
+
+        dependents
+    }
+
+    pub fn record_tool(&mut self, workspace_id: &str, tool: InstalledTool) -> Result<(), String> {
+        let workspace = self
+            .workspaces
+            .iter_mut()
+            .find(|w| w.id == workspace_id)
+            .ok_or_else(|| "Workspace not found".to_string())?;
+
+        match workspace.tools.iter_mut().find(|t| t.name.eq_ignore_ascii_case(&tool.name)) {
+            Some(existing) => *existing = tool,
+            None => workspace.tools.push(tool),
+        }
+        Ok(())
+    }
+
+    // Tools are installed machine-wide, so removal and upgrades apply to every workspace listing them
+    pub fn remove_tool(&mut self, names: &[String]) -> Vec<String> {
+        let mut affected = Vec::new();
+        for workspace in &mut self.workspaces {
+            let before = workspace.tools.len();
+            workspace.tools.retain(|t| !names.iter().any(|n| n.eq_ignore_ascii_case(&t.name)));
+            if workspace.tools.len() < before {
+                affected.push(workspace.id.clone());
+            }
+        }
+        affected
+    }
+
+    pub fn update_tool_version(&mut self, names: &[String], version: &str) -> Vec<String> {
+        let mut affected = Vec::new();
+        for workspace in &mut self.workspaces {
+            for tool in &mut workspace.tools {
+                if names.iter().any(|n| n.eq_ignore_ascii_case(&tool.name)) {
+                    tool.version = version.to_string();
+                    tool.status = "installed".to_string();
+                    affected.push(workspace.id.clone());
+                }
+            }
+        }
+        affected
+    }
 }
 
 // Tauri commands
 #[command]
-pub async fn create_workspace(request: CreateWorkspaceRequest) -> Result<Workspace, String> {
-    // TODO: Use global state manager
-    let mut manager = WorkspaceManager::new();
+pub async fn create_workspace(manager: State<'_, Arc<Mutex<WorkspaceManager>>>, request: CreateWorkspaceRequest) -> Result<Workspace, String> {
+    let mut manager = manager.lock().unwrap();
     manager.create_workspace(request)
 }
 
 #[command]
-pub async fn get_workspaces() -> Result<Vec<Workspace>, String> {
-    // TODO: Use global state manager
-    let manager = WorkspaceManager::new();
+pub async fn get_workspaces(manager: State<'_, Arc<Mutex<WorkspaceManager>>>) -> Result<Vec<Workspace>, String> {
+    let manager = manager.lock().unwrap();
     Ok(manager.get_workspaces())
 }
 
 #[command]
-pub async fn get_workspace(id: String) -> Result<Option<Workspace>, String> {
-    // TODO: Use global state manager
-    let manager = WorkspaceManager::new();
+pub async fn get_workspace(manager: State<'_, Arc<Mutex<WorkspaceManager>>>, id: String) -> Result<Option<Workspace>, String> {
+    let manager = manager.lock().unwrap();
     Ok(manager.get_workspace(&id))
 }
 
 #[command]
-pub async fn update_workspace(id: String, updates: HashMap<String, serde_json::Value>) -> Result<Workspace, String> {
-    // TODO: Use global state manager
-    let mut manager = WorkspaceManager::new();
+pub async fn update_workspace(
+    manager: State<'_, Arc<Mutex<WorkspaceManager>>>,
+    id: String,
+    updates: HashMap<String, serde_json::Value>,
+) -> Result<Workspace, String> {
+    let mut manager = manager.lock().unwrap();
     manager.update_workspace(&id, updates)
 }
 
 #[command]
-pub async fn delete_workspace(id: String) -> Result<(), String> {
-    // TODO: Use global state manager
-    let mut manager = WorkspaceManager::new();
+pub async fn delete_workspace(manager: State<'_, Arc<Mutex<WorkspaceManager>>>, id: String) -> Result<(), String> {
+    let mut manager = manager.lock().unwrap();
     manager.delete_workspace(&id)
 }
 
 #[command]
-pub async fn activate_workspace(id: String) -> Result<Workspace, String> {
-    // TODO: Use global state manager
-    let mut manager = WorkspaceManager::new();
+pub async fn activate_workspace(manager: State<'_, Arc<Mutex<WorkspaceManager>>>, id: String) -> Result<Workspace, String> {
+    let mut manager = manager.lock().unwrap();
     manager.activate_workspace(&id)
 }
 
 #[command]
-pub async fn deactivate_workspace(id: String) -> Result<Workspace, String> {
-    // TODO: Use global state manager
-    let mut manager = WorkspaceManager::new();
+pub async fn deactivate_workspace(manager: State<'_, Arc<Mutex<WorkspaceManager>>>, id: String) -> Result<Workspace, String> {
+    let mut manager = manager.lock().unwrap();
     manager.deactivate_workspace(&id)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, dependencies: Vec<&str>) -> InstalledTool {
+        InstalledTool {
+            name: name.to_string(),
+            tool_type: "cli".to_string(),
+            version: "1.0.0".to_string(),
+            path: String::new(),
+            size: 0,
+            status: "installed".to_string(),
+            dependencies: dependencies.into_iter().map(|d| d.to_string()).collect(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn workspace(manager: &mut WorkspaceManager, name: &str) -> String {
+        manager
+            .create_workspace(CreateWorkspaceRequest {
+                name: name.to_string(),
+                workspace_type: "development".to_string(),
+                tools: Vec::new(),
+                config: None,
+            })
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn test_tool_dependents() {
+        let mut manager = WorkspaceManager::new();
+        let backend = workspace(&mut manager, "Backend");
+        let frontend = workspace(&mut manager, "Frontend");
+        manager.record_tool(&backend, tool("Node.js", vec![])).unwrap();
+        manager.record_tool(&frontend, tool("Node.js", vec![])).unwrap();
+        manager.record_tool(&frontend, tool("pnpm", vec!["nodejs"])).unwrap();
+
+        let names = vec!["nodejs".to_string(), "Node.js".to_string()];
+        assert_eq!(manager.tool_dependents(&names, None).len(), 3);
+
+        // The requesting workspace's own copy does not block, but its dependent tools still do
+        let dependents = manager.tool_dependents(&names, Some(&frontend));
+        assert_eq!(dependents, vec!["Backend uses Node.js".to_string(), "Frontend needs nodejs for pnpm".to_string()]);
+    }
+
+    #[test]
+    fn test_remove_and_update_tool() {
+        let mut manager = WorkspaceManager::new();
+        let backend = workspace(&mut manager, "Backend");
+        manager.record_tool(&backend, tool("Git", vec![])).unwrap();
+        manager.record_tool(&backend, tool("git", vec![])).unwrap();
+        assert_eq!(manager.get_workspace(&backend).unwrap().tools.len(), 1);
+        assert!(manager.record_tool("missing", tool("Git", vec![])).is_err());
+
+        let names = vec!["git".to_string()];
+        assert_eq!(manager.update_tool_version(&names, "2.44.0"), vec![backend.clone()]);
+        assert_eq!(manager.get_workspace(&backend).unwrap().tools[0].version, "2.44.0");
+
+        assert_eq!(manager.remove_tool(&names), vec![backend.clone()]);
+        assert!(manager.get_workspace(&backend).unwrap().tools.is_empty());
+    }
+}
@@ -415,6 +415,14 @@ export interface SystemScan {
   suggestions: string[];
   scanned_at: Date;
   os_info: SystemInfo;
+  managed_versions: ManagedVersion[];
+}
+
+export interface ManagedVersion {
+  language: string;
+  manager: 'pyenv' | 'nvm' | 'rustup' | 'mise';
+  version: string;
+  path: string;
 }
 
 export interface SystemInfo {